@@ -0,0 +1,55 @@
+//! Compares the word based byte order conversion against a naive per-byte swap.
+//!
+//! Run with `cargo bench --bench byteswap`
+
+use libeverdrive::byteswap::{self, ByteOrder};
+use std::time::{Duration, Instant};
+
+const ROM_SIZE: usize = 64 * 1024 * 1024;
+const ITERATIONS: u32 = 5;
+
+fn naive_swap(data: &mut [u8], order: ByteOrder) {
+    match order {
+        ByteOrder::BigEndian => {}
+        ByteOrder::ByteSwapped => {
+            for i in (0..data.len()).step_by(2) {
+                data.swap(i, i + 1);
+            }
+        }
+        ByteOrder::LittleEndian => {
+            for i in (0..data.len()).step_by(4) {
+                data.swap(i, i + 3);
+                data.swap(i + 1, i + 2);
+            }
+        }
+    }
+}
+
+fn bench(name: &str, order: ByteOrder, swap: fn(&mut [u8], ByteOrder)) -> Duration {
+    let mut data: Vec<u8> = (0..ROM_SIZE).map(|i| i as u8).collect();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        swap(std::hint::black_box(&mut data), order);
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+
+    println!(
+        "{:<8} {:<14} {:>10.2?} ({:.0} MB/s)",
+        name,
+        format!("{:?}", order),
+        elapsed,
+        ROM_SIZE as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0)
+    );
+
+    elapsed
+}
+
+fn main() {
+    for order in [ByteOrder::ByteSwapped, ByteOrder::LittleEndian] {
+        let naive = bench("naive", order, naive_swap);
+        let word = bench("word", order, byteswap::to_big_endian_in_place);
+
+        println!("speedup {:.1}x\n", naive.as_secs_f64() / word.as_secs_f64());
+    }
+}
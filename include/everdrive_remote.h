@@ -0,0 +1,294 @@
+#ifndef EVERDRIVE_REMOTE_H
+#define EVERDRIVE_REMOTE_H
+
+/*
+ * Console side of the libeverdrive remote call protocol, the counterpart of `libeverdrive::remote`.
+ *
+ * Header only, include it after UNFLoader's usb.h, which provides usb_write, usb_poll, usb_read and
+ * usb_skip. Messages are DATATYPE_RAWBINARY packets starting with "RP", a kind byte and a u16
+ * message ID, all integers are big-endian:
+ *
+ *   call   RP 0x01 id  method length u8, method name, args
+ *   reply  RP 0x02 id  result
+ *   error  RP 0x03 id  UTF-8 error message
+ *
+ * rp_writer and rp_reader encode and decode arguments and results like PayloadWriter and
+ * PacketReader on the host: big-endian integers and u16 length prefixed strings.
+ *
+ *   uint8_t buf[RP_MAX_MESSAGE];
+ *   rp_writer args;
+ *   rp_writer_init(&args, buf, sizeof(buf));
+ *   rp_put_str(&args, "player.sav");
+ *   rp_write_call(1, "read_file", args.data, args.len);
+ *
+ *   uint8_t reply_buf[RP_MAX_MESSAGE];
+ *   rp_message reply;
+ *   if (rp_read_reply(1, reply_buf, sizeof(reply_buf), &reply) == RP_KIND_REPLY) {
+ *       rp_reader result;
+ *       rp_reader_init(&result, reply.payload, reply.payload_len);
+ *       uint32_t size = rp_get_u32(&result);
+ *   }
+ */
+
+#include <stdint.h>
+#include <string.h>
+
+/* Largest message sent or received, including the 5 byte message header */
+#ifndef RP_MAX_MESSAGE
+#define RP_MAX_MESSAGE 512
+#endif
+
+#define RP_KIND_CALL 0x01
+#define RP_KIND_REPLY 0x02
+#define RP_KIND_ERROR 0x03
+
+/* Results of rp_parse and rp_read_message besides the message kinds */
+#define RP_NONE 0
+#define RP_MALFORMED -1
+#define RP_TOO_LARGE -2
+
+/* Builds a payload in a caller provided buffer. Writes past the end set `overflow` and are dropped. */
+typedef struct {
+    uint8_t *data;
+    uint32_t size;
+    uint32_t len;
+    int overflow;
+} rp_writer;
+
+/* Reads a payload. Reads past the end set `error` and return zeros. */
+typedef struct {
+    const uint8_t *data;
+    uint32_t len;
+    uint32_t pos;
+    int error;
+} rp_reader;
+
+/* A parsed message, pointers refer to the buffer it was parsed from */
+typedef struct {
+    uint8_t kind;
+    uint16_t id;
+    /* Method name of calls, not NUL terminated */
+    const char *method;
+    uint8_t method_len;
+    const uint8_t *payload;
+    uint32_t payload_len;
+} rp_message;
+
+static inline void rp_writer_init(rp_writer *w, void *buf, uint32_t size)
+{
+    w->data = (uint8_t *)buf;
+    w->size = size;
+    w->len = 0;
+    w->overflow = 0;
+}
+
+/* Appends raw bytes without a length prefix */
+static inline void rp_put_bytes(rp_writer *w, const void *data, uint32_t len)
+{
+    if (len > w->size - w->len) {
+        w->overflow = 1;
+        return;
+    }
+    if (len == 0)
+        return;
+    memcpy(w->data + w->len, data, len);
+    w->len += len;
+}
+
+static inline void rp_put_u8(rp_writer *w, uint8_t value)
+{
+    rp_put_bytes(w, &value, 1);
+}
+
+static inline void rp_put_u16(rp_writer *w, uint16_t value)
+{
+    uint8_t bytes[2] = {(uint8_t)(value >> 8), (uint8_t)value};
+    rp_put_bytes(w, bytes, 2);
+}
+
+static inline void rp_put_u32(rp_writer *w, uint32_t value)
+{
+    uint8_t bytes[4] = {(uint8_t)(value >> 24), (uint8_t)(value >> 16), (uint8_t)(value >> 8), (uint8_t)value};
+    rp_put_bytes(w, bytes, 4);
+}
+
+/* Appends a NUL terminated string with a u16 length prefix, longer strings are truncated */
+static inline void rp_put_str(rp_writer *w, const char *str)
+{
+    size_t len = strlen(str);
+    if (len > 0xFFFF)
+        len = 0xFFFF;
+    rp_put_u16(w, (uint16_t)len);
+    rp_put_bytes(w, str, (uint32_t)len);
+}
+
+static inline void rp_reader_init(rp_reader *r, const void *data, uint32_t len)
+{
+    r->data = (const uint8_t *)data;
+    r->len = len;
+    r->pos = 0;
+    r->error = 0;
+}
+
+static inline uint32_t rp_remaining(const rp_reader *r)
+{
+    return r->len - r->pos;
+}
+
+/* Returns a pointer to the next `len` bytes and skips them, or NULL if fewer remain */
+static inline const uint8_t *rp_get_bytes(rp_reader *r, uint32_t len)
+{
+    const uint8_t *bytes;
+    if (len > rp_remaining(r)) {
+        r->error = 1;
+        return NULL;
+    }
+    bytes = r->data + r->pos;
+    r->pos += len;
+    return bytes;
+}
+
+static inline uint8_t rp_get_u8(rp_reader *r)
+{
+    const uint8_t *b = rp_get_bytes(r, 1);
+    return b ? b[0] : 0;
+}
+
+static inline uint16_t rp_get_u16(rp_reader *r)
+{
+    const uint8_t *b = rp_get_bytes(r, 2);
+    return b ? (uint16_t)((b[0] << 8) | b[1]) : 0;
+}
+
+static inline uint32_t rp_get_u32(rp_reader *r)
+{
+    const uint8_t *b = rp_get_bytes(r, 4);
+    return b ? ((uint32_t)b[0] << 24) | ((uint32_t)b[1] << 16) | ((uint32_t)b[2] << 8) | b[3] : 0;
+}
+
+/* Reads a u16 length prefixed string, not NUL terminated. Stores its length in `len`. */
+static inline const char *rp_get_str(rp_reader *r, uint16_t *len)
+{
+    const char *str;
+    *len = rp_get_u16(r);
+    str = (const char *)rp_get_bytes(r, *len);
+    if (!str)
+        *len = 0;
+    return str;
+}
+
+/* Parses a received binary packet. Returns the message kind, RP_NONE if the packet is not a remote
+ * call message or RP_MALFORMED. */
+static inline int rp_parse(const void *data, uint32_t len, rp_message *out)
+{
+    rp_reader r;
+    rp_reader_init(&r, data, len);
+
+    if (len < 2 || memcmp(data, "RP", 2) != 0)
+        return RP_NONE;
+    rp_get_bytes(&r, 2);
+
+    out->kind = rp_get_u8(&r);
+    out->id = rp_get_u16(&r);
+    out->method = NULL;
+    out->method_len = 0;
+
+    if (out->kind == RP_KIND_CALL) {
+        out->method_len = rp_get_u8(&r);
+        out->method = (const char *)rp_get_bytes(&r, out->method_len);
+    } else if (out->kind != RP_KIND_REPLY && out->kind != RP_KIND_ERROR) {
+        return RP_MALFORMED;
+    }
+
+    if (r.error)
+        return RP_MALFORMED;
+
+    out->payload_len = rp_remaining(&r);
+    out->payload = rp_get_bytes(&r, out->payload_len);
+    return out->kind;
+}
+
+/* Sends a message, returns 0 or RP_TOO_LARGE if it doesn't fit RP_MAX_MESSAGE */
+static inline int rp_write_message(uint8_t kind, uint16_t id, const void *header, uint32_t header_len,
+                                   const void *payload, uint32_t payload_len)
+{
+    uint8_t buf[RP_MAX_MESSAGE];
+    rp_writer w;
+    rp_writer_init(&w, buf, sizeof(buf));
+
+    rp_put_bytes(&w, "RP", 2);
+    rp_put_u8(&w, kind);
+    rp_put_u16(&w, id);
+    rp_put_bytes(&w, header, header_len);
+    rp_put_bytes(&w, payload, payload_len);
+
+    if (w.overflow)
+        return RP_TOO_LARGE;
+
+    usb_write(DATATYPE_RAWBINARY, buf, (int)w.len);
+    return 0;
+}
+
+/* Calls `method` on the host, wait for the result with rp_read_reply */
+static inline int rp_write_call(uint16_t id, const char *method, const void *args, uint32_t len)
+{
+    uint8_t header[256];
+    size_t method_len = strlen(method);
+
+    if (method_len > 255)
+        return RP_TOO_LARGE;
+
+    header[0] = (uint8_t)method_len;
+    memcpy(header + 1, method, method_len);
+    return rp_write_message(RP_KIND_CALL, id, header, (uint32_t)method_len + 1, args, len);
+}
+
+/* Answers call `id` from the host with a result */
+static inline int rp_write_reply(uint16_t id, const void *result, uint32_t len)
+{
+    return rp_write_message(RP_KIND_REPLY, id, NULL, 0, result, len);
+}
+
+/* Answers call `id` from the host with an error message */
+static inline int rp_write_error(uint16_t id, const char *message)
+{
+    return rp_write_message(RP_KIND_ERROR, id, NULL, 0, message, (uint32_t)strlen(message));
+}
+
+/* Reads the next pending packet into `buf` and parses it. Returns RP_NONE if no packet is pending
+ * or it is not a remote call message, RP_TOO_LARGE if it doesn't fit `size` (it is skipped) or the
+ * result of rp_parse. */
+static inline int rp_read_message(void *buf, uint32_t size, rp_message *out)
+{
+    uint32_t header = usb_poll();
+    uint32_t len = USBHEADER_GETSIZE(header);
+
+    if (header == 0)
+        return RP_NONE;
+
+    if (USBHEADER_GETTYPE(header) != DATATYPE_RAWBINARY) {
+        usb_skip((int)len);
+        return RP_NONE;
+    }
+
+    if (len > size) {
+        usb_skip((int)len);
+        return RP_TOO_LARGE;
+    }
+
+    usb_read(buf, (int)len);
+    return rp_parse(buf, len, out);
+}
+
+/* Blocks until the reply or error for call `id` arrives and returns its kind. Other packets
+ * received meanwhile are dropped. */
+static inline int rp_read_reply(uint16_t id, void *buf, uint32_t size, rp_message *out)
+{
+    for (;;) {
+        int kind = rp_read_message(buf, size, out);
+        if ((kind == RP_KIND_REPLY || kind == RP_KIND_ERROR) && out->id == id)
+            return kind;
+    }
+}
+
+#endif
@@ -0,0 +1,84 @@
+#ifndef LIBEVERDRIVE_H
+#define LIBEVERDRIVE_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define EVERDRIVE_OK 0
+
+// A pointer argument was null or a string was not valid UTF-8
+#define EVERDRIVE_INVALID_ARGUMENT -1
+
+#define EVERDRIVE_IO_ERROR -2
+
+// No response or packet arrived within the timeout
+#define EVERDRIVE_TIMED_OUT -3
+
+// The receive buffer is too small, the required size is stored in `len` and the packet is kept for the next call
+#define EVERDRIVE_BUFFER_TOO_SMALL -4
+
+// Opaque device handle
+typedef struct EverdriveHandle EverdriveHandle;
+
+// Opens the device on `port`, or the first responding device if `port` is null, and stores the handle in `out`.
+//
+// # Safety
+//
+// `port` must be null or a valid C string and `out` must be a valid pointer.
+int32_t everdrive_open(const char *port, EverdriveHandle **out);
+
+// Returns the message of the last failed `everdrive_open` on the calling thread, or an empty string.
+// Valid until the next `everdrive_open` on the thread.
+const char *everdrive_open_error(void);
+
+// Closes a handle returned by `everdrive_open`. Null is ignored.
+//
+// # Safety
+//
+// `handle` must be null or a handle from `everdrive_open` which has not been closed yet.
+void everdrive_close(EverdriveHandle *handle);
+
+// Returns the message of the last error of `handle`, valid until the next call with the handle
+//
+// # Safety
+//
+// `handle` must be a valid handle.
+const char *everdrive_last_error(const EverdriveHandle *handle);
+
+// Loads a rom image of `len` bytes like `Everdrive::ed_load_rom_auto`
+//
+// # Safety
+//
+// `handle` must be a valid handle and `data` must point to `len` readable bytes.
+int32_t everdrive_load_rom(EverdriveHandle *handle, const uint8_t *data, size_t len);
+
+// Starts the loaded rom, `save_file_name` may be null
+//
+// # Safety
+//
+// `handle` must be a valid handle and `save_file_name` null or a valid C string.
+int32_t everdrive_app_start(EverdriveHandle *handle, const char *save_file_name);
+
+// Sends a UNF packet of `datatype` with `len` bytes of `data`
+//
+// # Safety
+//
+// `handle` must be a valid handle and `data` must point to `len` readable bytes.
+int32_t everdrive_unf_send(EverdriveHandle *handle, uint8_t datatype, const uint8_t *data, size_t len);
+
+// Receives a UNF packet into `buf` of `capacity` bytes, storing its datatype and length.
+// Returns `EVERDRIVE_TIMED_OUT` if no packet arrived within the timeout.
+//
+// # Safety
+//
+// `handle` must be a valid handle, `buf` must point to `capacity` writable bytes and
+// `datatype` and `len` must be valid pointers.
+int32_t everdrive_unf_receive(EverdriveHandle *handle,
+                              uint8_t *datatype,
+                              uint8_t *buf,
+                              size_t capacity,
+                              size_t *len);
+
+#endif /* LIBEVERDRIVE_H */
@@ -0,0 +1,144 @@
+use crate::clock::Stopwatch;
+use crate::{Everdrive, SessionState};
+
+/// Number of status handshakes timed for the round-trip latency
+const BENCHMARK_HANDSHAKES: u32 = 16;
+
+/// Block size used for counting corrupted blocks, the granularity of the rom commands
+const BENCHMARK_BLOCK_SIZE: usize = 512;
+
+/// Results of `ed_usb_benchmark`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsbBenchmark {
+    /// Bytes written and read back
+    pub bytes: usize,
+    pub write_time: std::time::Duration,
+    pub read_time: std::time::Duration,
+    /// Average round-trip time of the status handshake
+    pub latency: std::time::Duration,
+    /// Bytes which read back differently than written
+    pub byte_errors: usize,
+    /// 512 byte blocks containing at least one corrupted byte
+    pub block_errors: usize,
+}
+
+impl UsbBenchmark {
+    /// Write throughput in bytes per second, or 0 if no time was measured
+    pub fn write_throughput(&self) -> f64 {
+        Self::throughput(self.bytes, self.write_time)
+    }
+
+    /// Read throughput in bytes per second, or 0 if no time was measured
+    pub fn read_throughput(&self) -> f64 {
+        Self::throughput(self.bytes, self.read_time)
+    }
+
+    /// Returns true if all data read back as written
+    pub fn is_error_free(&self) -> bool {
+        self.byte_errors == 0
+    }
+
+    fn throughput(bytes: usize, time: std::time::Duration) -> f64 {
+        if time.is_zero() {
+            0.0
+        } else {
+            bytes as f64 / time.as_secs_f64()
+        }
+    }
+}
+
+/// Fills `data` with a xorshift pattern, so shifted or repeated blocks don't read back as valid
+fn test_pattern(data: &mut [u8]) {
+    let mut state: u32 = 0x2545F491;
+
+    for word in data.chunks_mut(4) {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        word.copy_from_slice(&state.to_be_bytes()[..word.len()]);
+    }
+}
+
+impl Everdrive {
+    /// Measures the USB link by timing status handshakes and writing a test pattern of `len` bytes,
+    /// rounded up to 512 byte blocks, to the start of the rom and reading it back. Use it to check
+    /// cables, hubs and driver latency settings.
+    ///
+    /// The test pattern overwrites the loaded rom, `ed_app_start` is rejected until a rom has been
+    /// loaded again. Timing is not available on `wasm32-unknown-unknown`, where the times stay zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let result = ed.ed_usb_benchmark(8 * 1024 * 1024).unwrap();
+    ///
+    /// println!(
+    ///     "write {:.1} MB/s, read {:.1} MB/s, latency {:?}, {} bad blocks",
+    ///     result.write_throughput() / (1024.0 * 1024.0),
+    ///     result.read_throughput() / (1024.0 * 1024.0),
+    ///     result.latency,
+    ///     result.block_errors
+    /// );
+    /// ```
+    pub fn ed_usb_benchmark(&mut self, len: usize) -> std::io::Result<UsbBenchmark> {
+        let len = len.max(1).next_multiple_of(BENCHMARK_BLOCK_SIZE);
+
+        let start = Stopwatch::start();
+        for _ in 0..BENCHMARK_HANDSHAKES {
+            self.ed_status()?;
+        }
+        let latency = start.elapsed().unwrap_or_default() / BENCHMARK_HANDSHAKES;
+
+        let mut pattern = vec![0; len];
+        test_pattern(&mut pattern);
+
+        let base_address = self.memory_map().rom_base();
+
+        let start = Stopwatch::start();
+        let written = self.ed_rom_write(base_address, &pattern);
+        let write_time = start.elapsed().unwrap_or_default();
+
+        // The pattern replaced the loaded rom, starting it would boot garbage
+        self.session = SessionState::Menu;
+        written?;
+
+        let mut readback = vec![0; len];
+
+        let start = Stopwatch::start();
+        for (i, chunk) in readback
+            .chunks_mut(crate::edos::ROM_STREAM_CHUNK_SIZE)
+            .enumerate()
+        {
+            self.check_cancelled()?;
+
+            let addr = base_address + (i * crate::edos::ROM_STREAM_CHUNK_SIZE) as u32;
+            self.ed_rom_read(addr, chunk)?;
+        }
+        let read_time = start.elapsed().unwrap_or_default();
+
+        let byte_errors = pattern
+            .iter()
+            .zip(&readback)
+            .filter(|(written, read)| written != read)
+            .count();
+
+        let block_errors = pattern
+            .chunks(BENCHMARK_BLOCK_SIZE)
+            .zip(readback.chunks(BENCHMARK_BLOCK_SIZE))
+            .filter(|(written, read)| written != read)
+            .count();
+
+        Ok(UsbBenchmark {
+            bytes: len,
+            write_time,
+            read_time,
+            latency,
+            byte_errors,
+            block_errors,
+        })
+    }
+}
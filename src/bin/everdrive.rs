@@ -0,0 +1,278 @@
+//! Command line interface for loading and running roms and talking to running programs.
+//!
+//! Built with `cargo install libeverdrive --features cli`.
+
+use libeverdrive::{
+    CancellationToken, EdRtcRegionType, EdSaveType, Everdrive, RunOptions, SessionState,
+    UnfHandlers, UsbDeviceRule,
+};
+
+const USAGE: &str = "\
+Usage: everdrive [--port PORT] <command> [arguments]
+
+Commands:
+    list                          List connected Everdrive devices
+    load <rom> [options]          Load a rom without starting it
+    run <rom> [options]           Load and start a rom
+    listen                        Print text sent by the running program
+    screenshot <dir> [--count N]  Save screenshots sent by the running program as PNG files
+    sd                            Access the SD card (not supported over USB)
+
+Load and run options:
+    --save TYPE    Save type: eeprom4k, eeprom16k, sram, sram768k, flashram or sram128k
+    --rtc TYPE     RTC and region type: rtc, noregion or all, requires --save
+    --base ADDR    Address to load the rom to, in hex
+    --listen       Print text sent by the program after starting it (run only)
+
+Without --port, load and run use the first device answering the status handshake. listen and
+screenshot don't run the handshake, since the program is already running, and use the first
+connected device.";
+
+fn usage_error(msg: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{}\n\n{}", msg, USAGE),
+    )
+}
+
+/// Command line arguments left after the command name, with options taken out by name
+struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    /// Removes `--name value` and returns the value
+    fn option(&mut self, name: &str) -> std::io::Result<Option<String>> {
+        let Some(index) = self.args.iter().position(|arg| arg == name) else {
+            return Ok(None);
+        };
+
+        if index + 1 >= self.args.len() {
+            return Err(usage_error(format!("Missing value for {}", name)));
+        }
+
+        self.args.remove(index);
+        Ok(Some(self.args.remove(index)))
+    }
+
+    /// Removes `--name` and returns whether it was present
+    fn flag(&mut self, name: &str) -> bool {
+        let len = self.args.len();
+        self.args.retain(|arg| arg != name);
+        self.args.len() != len
+    }
+
+    /// Takes the next positional argument
+    fn positional(&mut self, name: &str) -> std::io::Result<String> {
+        if self.args.is_empty() || self.args[0].starts_with("--") {
+            return Err(usage_error(format!("Missing argument <{}>", name)));
+        }
+
+        Ok(self.args.remove(0))
+    }
+
+    /// Returns an error for arguments which were not consumed
+    fn finish(self) -> std::io::Result<()> {
+        match self.args.first() {
+            Some(arg) => Err(usage_error(format!("Unexpected argument {}", arg))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Load settings shared by `load` and `run`
+struct LoadOptions {
+    save_type: Option<EdSaveType>,
+    rtc_region_type: Option<EdRtcRegionType>,
+    base_address: Option<u32>,
+}
+
+impl LoadOptions {
+    fn parse(args: &mut Args) -> std::io::Result<Self> {
+        let save_type = args.option("--save")?.map(|s| s.parse()).transpose()?;
+        let rtc_region_type = args.option("--rtc")?.map(|s| s.parse()).transpose()?;
+
+        if rtc_region_type.is_some() && save_type.is_none() {
+            return Err(usage_error("--rtc requires --save"));
+        }
+
+        let base_address = args
+            .option("--base")?
+            .map(|addr| {
+                u32::from_str_radix(addr.trim_start_matches("0x"), 16)
+                    .map_err(|_| usage_error(format!("Invalid address {}", addr)))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            save_type,
+            rtc_region_type,
+            base_address,
+        })
+    }
+}
+
+fn open(port: Option<&str>) -> std::io::Result<Everdrive> {
+    let mut ed = match port {
+        Some(port) => Everdrive::new(port)?,
+        None => Everdrive::detect()?,
+    };
+
+    ed.ed_status()?;
+    Ok(ed)
+}
+
+/// Opens the port without the status handshake, which a running program doesn't answer and would
+/// receive as stray data
+fn attach(port: Option<&str>) -> std::io::Result<Everdrive> {
+    let port = match port {
+        Some(port) => port.to_string(),
+        None => Everdrive::find_usb_devices()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "No Everdrive device found")
+            })?,
+    };
+
+    let mut ed = Everdrive::new(&port)?;
+    ed.set_session_state(SessionState::Running);
+    Ok(ed)
+}
+
+fn print_text_handlers<'a>() -> UnfHandlers<'a> {
+    UnfHandlers::new()
+        .on_text(|text| print!("{}", text))
+        .on_crash(|report| eprintln!("Console crashed: {} at {:08X}", report.info, report.epc))
+}
+
+fn list() -> std::io::Result<()> {
+    let devices = Everdrive::find_usb_devices_matching(&UsbDeviceRule::defaults())?;
+
+    if devices.is_empty() {
+        println!("No devices found");
+    }
+
+    for device in devices {
+        match device.serial_number {
+            Some(serial) => println!("{} (serial {})", device.port_name, serial),
+            None => println!("{}", device.port_name),
+        }
+    }
+
+    Ok(())
+}
+
+fn load(port: Option<&str>, mut args: Args) -> std::io::Result<()> {
+    let options = LoadOptions::parse(&mut args)?;
+    let path = args.positional("rom")?;
+    args.finish()?;
+
+    let rom_data = std::fs::read(&path)?;
+    let mut ed = open(port)?;
+
+    let report = match options.save_type {
+        Some(save_type) => ed.ed_load_rom(
+            rom_data,
+            options.base_address,
+            Some(save_type),
+            options.rtc_region_type,
+        )?,
+        None => ed.ed_load_rom_auto(rom_data, options.base_address)?,
+    };
+
+    println!(
+        "Loaded {:?} at {:#010X}, {} bytes, CRC32 {:08X}",
+        report.kind, report.base_address, report.size, report.crc32
+    );
+
+    if let Some(save_type) = report.save_type {
+        println!("Save type {}", save_type);
+    }
+
+    Ok(())
+}
+
+fn run(port: Option<&str>, mut args: Args) -> std::io::Result<()> {
+    let options = LoadOptions::parse(&mut args)?;
+    let listen = args.flag("--listen");
+    let path = args.positional("rom")?;
+    args.finish()?;
+
+    let mut ed = open(port)?;
+
+    ed.run_rom(
+        path,
+        RunOptions {
+            base_address: options.base_address,
+            save_type: options.save_type,
+            rtc_region_type: options.rtc_region_type,
+            listen: listen.then(print_text_handlers),
+            ..Default::default()
+        },
+    )
+}
+
+fn listen(port: Option<&str>, args: Args) -> std::io::Result<()> {
+    args.finish()?;
+
+    let mut ed = attach(port)?;
+    ed.unf_listen(print_text_handlers(), &CancellationToken::new())
+}
+
+fn screenshot(port: Option<&str>, mut args: Args) -> std::io::Result<()> {
+    let count = args
+        .option("--count")?
+        .map(|count| {
+            count
+                .parse()
+                .map_err(|_| usage_error(format!("Invalid count {}", count)))
+        })
+        .transpose()?
+        .unwrap_or(1);
+    let dir = args.positional("dir")?;
+    args.finish()?;
+
+    let mut ed = attach(port)?;
+
+    for path in ed.unf_capture_screenshots(dir, count, &CancellationToken::new())? {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = Args {
+        args: std::env::args().skip(1).collect(),
+    };
+
+    let result = (|| {
+        let port = args.option("--port")?;
+
+        if args.flag("--help") || args.flag("-h") {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+
+        let command = args.positional("command")?;
+
+        match command.as_str() {
+            "list" => args.finish().and_then(|_| list()),
+            "load" => load(port.as_deref(), args),
+            "run" => run(port.as_deref(), args),
+            "listen" => listen(port.as_deref(), args),
+            "screenshot" => screenshot(port.as_deref(), args),
+            "sd" => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "The SD card is only accessible to the Everdrive OS, it can't be accessed over USB",
+            )),
+            _ => Err(usage_error(format!("Unknown command {}", command))),
+        }
+    })();
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Byte order conversion between the z64, v64 and n64 rom formats.
+
+const SWAP_READER_BUF_SIZE: usize = 0x10000;
+
+/// Byte order of a rom image, detected from the first word of the header
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ByteOrder {
+    /// Big-endian native order (.z64)
+    BigEndian,
+    /// Every 2 bytes swapped (.v64)
+    ByteSwapped,
+    /// Every 4 bytes swapped (.n64)
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// Detects the byte order from the first word of a rom header,
+    /// or returns `None` if the header is not a recognized N64 header.
+    pub fn from_header_word(header_word_be: u32) -> Option<Self> {
+        match header_word_be {
+            0x80371240 => Some(ByteOrder::BigEndian),
+            0x37804012 => Some(ByteOrder::ByteSwapped),
+            0x40123780 => Some(ByteOrder::LittleEndian),
+            _ => None,
+        }
+    }
+}
+
+/// Detects the byte order of a rom image from its first word,
+/// or returns `None` if the rom does not start with a recognized N64 header.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::byteswap::{self, ByteOrder};
+///
+/// assert_eq!(byteswap::detect_byte_order(&[0x37, 0x80, 0x40, 0x12]), Some(ByteOrder::ByteSwapped));
+/// assert_eq!(byteswap::detect_byte_order(b"NES\x1A"), None);
+/// ```
+pub fn detect_byte_order(rom: &[u8]) -> Option<ByteOrder> {
+    let header_word_be = u32::from_be_bytes(rom.get(0..4)?.try_into().ok()?);
+    ByteOrder::from_header_word(header_word_be)
+}
+
+/// Converts `data` stored in `order` to big-endian byte order in place.
+/// Trailing bytes which do not form a complete 2 or 4 byte group are left untouched.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::byteswap::{self, ByteOrder};
+///
+/// let mut data = [0x40, 0x12, 0x37, 0x80, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x80];
+/// byteswap::to_big_endian_in_place(&mut data, ByteOrder::LittleEndian);
+///
+/// assert_eq!(data, [0x80, 0x37, 0x12, 0x40, 0x00, 0x00, 0x00, 0x0F, 0x80, 0x40, 0x00, 0x00]);
+///
+/// let mut data = [0x37, 0x80, 0x40, 0x12, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x80, 0x01];
+/// byteswap::to_big_endian_in_place(&mut data, ByteOrder::ByteSwapped);
+///
+/// assert_eq!(data, [0x80, 0x37, 0x12, 0x40, 0x00, 0x00, 0x00, 0x0F, 0x80, 0x00, 0x01]);
+/// ```
+pub fn to_big_endian_in_place(data: &mut [u8], order: ByteOrder) {
+    // Swap 8 bytes at a time as a single word, the masks and rotation operate on
+    // memory order so the result is the same regardless of the host endianness
+    const LOW_BYTES: u64 = 0x00FF00FF00FF00FF;
+
+    match order {
+        ByteOrder::BigEndian => { /* No need to do anything */ }
+        ByteOrder::ByteSwapped => {
+            let mut words = data.chunks_exact_mut(8);
+
+            for word in &mut words {
+                let w = u64::from_ne_bytes(word.try_into().unwrap());
+                let w = ((w >> 8) & LOW_BYTES) | ((w & LOW_BYTES) << 8);
+                word.copy_from_slice(&w.to_ne_bytes());
+            }
+
+            for pair in words.into_remainder().chunks_exact_mut(2) {
+                pair.swap(0, 1);
+            }
+        }
+        ByteOrder::LittleEndian => {
+            let mut words = data.chunks_exact_mut(8);
+
+            for word in &mut words {
+                let w = u64::from_ne_bytes(word.try_into().unwrap());
+                let w = w.swap_bytes().rotate_left(32);
+                word.copy_from_slice(&w.to_ne_bytes());
+            }
+
+            for word in words.into_remainder().chunks_exact_mut(4) {
+                word.reverse();
+            }
+        }
+    }
+}
+
+/// Reader adapter which converts the data of the inner reader from `order` to big-endian
+/// byte order while it is being read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::byteswap::{ByteOrder, SwapReader};
+/// use std::fs::File;
+///
+/// let rom_file = File::open("your_rom.v64").unwrap();
+/// let mut out_file = File::create("your_rom.z64").unwrap();
+///
+/// let mut reader = SwapReader::new(rom_file, ByteOrder::ByteSwapped);
+/// std::io::copy(&mut reader, &mut out_file).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SwapReader<R> {
+    inner: R,
+    order: ByteOrder,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: std::io::Read> SwapReader<R> {
+    pub fn new(inner: R, order: ByteOrder) -> Self {
+        Self {
+            inner,
+            order,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for SwapReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.buf.len() {
+            // Refill whole chunks so that swapped groups never straddle two reads
+            self.buf.resize(SWAP_READER_BUF_SIZE, 0);
+            let len = crate::read_chunk(&mut self.inner, &mut self.buf)?;
+            self.buf.truncate(len);
+            self.pos = 0;
+
+            to_big_endian_in_place(&mut self.buf, self.order);
+        }
+
+        let len = out.len().min(self.buf.len() - self.pos);
+        out[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Copies all data from `reader` to `writer`, converting it from `order` to big-endian byte order.
+/// Returns the number of bytes copied.
+pub fn copy_to_big_endian<R: std::io::Read, W: std::io::Write>(
+    reader: R,
+    writer: &mut W,
+    order: ByteOrder,
+) -> std::io::Result<u64> {
+    std::io::copy(&mut SwapReader::new(reader, order), writer)
+}
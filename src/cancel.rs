@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Error payload of the `std::io::Error` returned when an operation is cancelled
+/// through a `CancellationToken`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl Cancelled {
+    /// Returns true if `err` was caused by a cancelled operation
+    pub fn is(err: &std::io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+/// Token used to cancel long running operations from another thread or from a callback.
+///
+/// Transfers check the token between chunks and fail with a `Cancelled` error, while
+/// receive loops such as `unf_listen` return cleanly.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Cancelled, CancellationToken, Everdrive};
+/// use std::fs;
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// let cancel = CancellationToken::new();
+/// ed.set_cancellation_token(Some(cancel.clone()));
+///
+/// let thread_cancel = cancel.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(5));
+///     thread_cancel.cancel();
+/// });
+///
+/// let rom_data = fs::read("your_rom.z64").unwrap();
+///
+/// match ed.ed_load_rom(rom_data, None, None, None) {
+///     Err(err) if Cancelled::is(&err) => println!("Upload cancelled"),
+///     result => {
+///         result.unwrap();
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns a `Cancelled` error if the token has been cancelled
+    pub fn check(&self) -> std::io::Result<()> {
+        if self.is_cancelled() {
+            Err(std::io::Error::other(Cancelled))
+        } else {
+            Ok(())
+        }
+    }
+}
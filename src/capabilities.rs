@@ -0,0 +1,139 @@
+use crate::Everdrive;
+use crate::edos::{EdRtcRegionType, EdSaveType};
+
+/// Features of the connected cart, consulted by the rom loading functions, `ed_app_start_with` and
+/// `ed_fpga_init` to reject requests the cart can't serve before anything is sent. The space
+/// available for roms is described by the memory map, see `Everdrive::set_memory_map`.
+///
+/// The Everdrive OS reports no version or hardware information over USB, so the capabilities
+/// describe the cart model rather than being read from the device. `EdCapabilities::everdrive64()`
+/// is used by default, carts which differ are described with `Everdrive::set_capabilities`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{EdCapabilities, EdRtcRegionType, EdSaveType, Everdrive};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// // A cart without a clock
+/// ed.set_capabilities(EdCapabilities {
+///     rtc: Some(false),
+///     ..EdCapabilities::everdrive64()
+/// });
+///
+/// let rom_data = std::fs::read("your_rom.z64").unwrap();
+///
+/// // Rejected with an `Unsupported` error before uploading
+/// let err = ed
+///     .ed_load_rom(rom_data, None, Some(EdSaveType::Eeprom16k), Some(EdRtcRegionType::Rtc))
+///     .unwrap_err();
+/// println!("{}", err);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdCapabilities {
+    /// Save types the cart can emulate
+    pub save_types: Vec<EdSaveType>,
+    /// Whether the cart has a real-time clock, `None` if not known
+    pub rtc: Option<bool>,
+    /// Whether the Everdrive OS accepts FPGA configurations with `ed_fpga_init`
+    pub fpga_init: bool,
+}
+
+impl EdCapabilities {
+    /// Capabilities of the Everdrive 64 X-series and V3. Whether the clock is fitted is not known, so
+    /// RTC settings are not rejected.
+    pub fn everdrive64() -> Self {
+        Self {
+            save_types: EdSaveType::ALL.to_vec(),
+            rtc: None,
+            fpga_init: true,
+        }
+    }
+
+    fn unsupported(msg: String) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::Unsupported, msg)
+    }
+
+    /// Returns an `Unsupported` error if the save and RTC settings can't be used on the cart
+    pub(crate) fn check_rom(
+        &self,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+    ) -> std::io::Result<()> {
+        if let Some(save_type) = save_type
+            && !self.save_types.contains(&save_type)
+        {
+            return Err(Self::unsupported(format!(
+                "Save type {} is not supported by the cart",
+                save_type
+            )));
+        }
+
+        // The RTC settings are only written together with a save type
+        if self.rtc == Some(false)
+            && save_type.is_some()
+            && matches!(
+                rtc_region_type,
+                Some(EdRtcRegionType::Rtc | EdRtcRegionType::All)
+            )
+        {
+            return Err(Self::unsupported(
+                "The cart has no real-time clock".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_fpga_init(&self) -> std::io::Result<()> {
+        match self.fpga_init {
+            true => Ok(()),
+            false => Err(Self::unsupported(
+                "The cart does not support FPGA configuration".to_string(),
+            )),
+        }
+    }
+}
+
+impl Default for EdCapabilities {
+    fn default() -> Self {
+        Self::everdrive64()
+    }
+}
+
+impl Everdrive {
+    /// Returns the capabilities of the cart, see `EdCapabilities`
+    pub fn ed_capabilities(&self) -> &EdCapabilities {
+        &self.capabilities
+    }
+
+    /// Describes the capabilities of the connected cart. Defaults to `EdCapabilities::everdrive64()`.
+    pub fn set_capabilities(&mut self, capabilities: EdCapabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// Returns an `Unsupported` error if a rom of `size` bytes doesn't fit the writable region of
+    /// the memory map containing `base_address`, such as a large rom loaded for the emulators at
+    /// the emulator base
+    pub(crate) fn check_rom_size(&self, base_address: u32, size: usize) -> std::io::Result<()> {
+        let available = self
+            .memory_map
+            .regions()
+            .iter()
+            .filter(|region| region.writable && region.contains(base_address, 0))
+            .map(|region| region.start as u64 + region.size as u64 - base_address as u64)
+            .max()
+            .unwrap_or(0);
+
+        if size as u64 > available {
+            return Err(EdCapabilities::unsupported(format!(
+                "Rom of {} bytes is larger than the {} bytes available at {:#010X}",
+                size, available, base_address
+            )));
+        }
+
+        Ok(())
+    }
+}
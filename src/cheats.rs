@@ -0,0 +1,167 @@
+//! Parsing of GameShark code lists.
+//!
+//! Codes are parsed into typed writes and conditions so they can be inspected or converted for other
+//! tools. They cannot be activated over USB: the Everdrive OS has no cheat command on the USB port, and
+//! GameShark codes write to RAM while the game runs, which requires the cheat engine of the Everdrive menu.
+
+/// Single GameShark code. Addresses are full KSEG0 (cached) or KSEG1 (uncached) RAM addresses.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CheatCode {
+    /// `80`/`A0`: write a byte every frame
+    Write8 { address: u32, value: u8 },
+    /// `81`/`A1`: write a halfword every frame
+    Write16 { address: u32, value: u16 },
+    /// `88`: write a byte when the GameShark button is pressed
+    ButtonWrite8 { address: u32, value: u8 },
+    /// `89`: write a halfword when the GameShark button is pressed
+    ButtonWrite16 { address: u32, value: u16 },
+    /// `D0`: run the next code only if the byte equals `value`
+    IfEqual8 { address: u32, value: u8 },
+    /// `D1`: run the next code only if the halfword equals `value`
+    IfEqual16 { address: u32, value: u16 },
+    /// `D2`: run the next code only if the byte differs from `value`
+    IfNotEqual8 { address: u32, value: u8 },
+    /// `D3`: run the next code only if the halfword differs from `value`
+    IfNotEqual16 { address: u32, value: u16 },
+    /// `F0`: write a byte once at boot
+    BootWrite8 { address: u32, value: u8 },
+    /// `F1`: write a halfword once at boot
+    BootWrite16 { address: u32, value: u16 },
+    /// `50`: repeat the next code `count` times, adding the steps to its address and value each time
+    Repeat {
+        count: u8,
+        address_step: u8,
+        value_step: u16,
+    },
+    /// `EE`: hide the Expansion Pak from the game
+    DisableExpansionPak,
+}
+
+/// Named group of codes from a code list
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    pub name: String,
+    pub codes: Vec<CheatCode>,
+}
+
+fn invalid_code(code: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Invalid GameShark code \"{}\"", code),
+    )
+}
+
+impl std::str::FromStr for CheatCode {
+    type Err = std::io::Error;
+
+    /// Parses a code in the `8033B21D 0064` format
+    fn from_str(code: &str) -> std::io::Result<Self> {
+        let (word, value) = code
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid_code(code))?;
+
+        if word.len() != 8 || value.trim().len() != 4 {
+            return Err(invalid_code(code));
+        }
+
+        let word = u32::from_str_radix(word, 16).map_err(|_| invalid_code(code))?;
+        let value = u16::from_str_radix(value.trim(), 16).map_err(|_| invalid_code(code))?;
+
+        let kind = (word >> 24) as u8;
+        let offset = word & 0x00FFFFFF;
+        let address = 0x80000000 | offset;
+        let byte = value as u8;
+
+        Ok(match kind {
+            0x80 => CheatCode::Write8 {
+                address,
+                value: byte,
+            },
+            0x81 => CheatCode::Write16 { address, value },
+            0xA0 => CheatCode::Write8 {
+                address: 0xA0000000 | offset,
+                value: byte,
+            },
+            0xA1 => CheatCode::Write16 {
+                address: 0xA0000000 | offset,
+                value,
+            },
+            0x88 => CheatCode::ButtonWrite8 {
+                address,
+                value: byte,
+            },
+            0x89 => CheatCode::ButtonWrite16 { address, value },
+            0xD0 => CheatCode::IfEqual8 {
+                address,
+                value: byte,
+            },
+            0xD1 => CheatCode::IfEqual16 { address, value },
+            0xD2 => CheatCode::IfNotEqual8 {
+                address,
+                value: byte,
+            },
+            0xD3 => CheatCode::IfNotEqual16 { address, value },
+            0xF0 => CheatCode::BootWrite8 {
+                address,
+                value: byte,
+            },
+            0xF1 => CheatCode::BootWrite16 { address, value },
+            0x50 => CheatCode::Repeat {
+                count: (offset >> 8) as u8,
+                address_step: offset as u8,
+                value_step: value,
+            },
+            0xEE => CheatCode::DisableExpansionPak,
+            _ => return Err(invalid_code(code)),
+        })
+    }
+}
+
+/// Parses a code list. Lines containing a code belong to the cheat named by the closest preceding
+/// non-code line, blank lines and lines starting with `#` or `;` are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::cheats::{self, CheatCode};
+///
+/// let cheats = cheats::parse_cheats("Infinite Lives\n8033B21D 0064\n").unwrap();
+///
+/// assert_eq!(cheats[0].name, "Infinite Lives");
+/// assert_eq!(cheats[0].codes, [CheatCode::Write8 { address: 0x8033B21D, value: 0x64 }]);
+/// ```
+pub fn parse_cheats(list: &str) -> std::io::Result<Vec<Cheat>> {
+    let mut cheats: Vec<Cheat> = Vec::new();
+
+    for line in list.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        let looks_like_code = line.len() == 13
+            && line
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() || c.is_whitespace());
+
+        if !looks_like_code {
+            cheats.push(Cheat {
+                name: line.to_string(),
+                codes: Vec::new(),
+            });
+            continue;
+        }
+
+        let code = line.parse()?;
+
+        match cheats.last_mut() {
+            Some(cheat) => cheat.codes.push(code),
+            None => cheats.push(Cheat {
+                name: String::new(),
+                codes: vec![code],
+            }),
+        }
+    }
+
+    Ok(cheats)
+}
@@ -0,0 +1,43 @@
+//! Timing helpers which degrade gracefully on `wasm32-unknown-unknown`, where `std::time::Instant`
+//! and `std::thread::sleep` are unavailable and panic. Timing reachable from the protocol code goes
+//! through these.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Stopwatch(std::time::Instant);
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    /// Returns the time since the stopwatch was started, or `None` where no clock is available
+    pub(crate) fn elapsed(&self) -> Option<std::time::Duration> {
+        Some(self.0.elapsed())
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn sleep(duration: std::time::Duration) {
+    std::thread::sleep(duration);
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Stopwatch;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+/// Blocking is not possible on the browser main thread, waits are skipped
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn sleep(_duration: std::time::Duration) {}
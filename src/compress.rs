@@ -0,0 +1,129 @@
+//! LZ4 compressed UNF transfers, for pushing more data through the serial link than its raw rate.
+//!
+//! Compressed payloads are `DataTypeBinary` packets starting with the `LZ` prefix, followed by the
+//! uncompressed size as a big-endian `u32` and an LZ4 block. The uncompressed size is limited to
+//! `MAX_DECOMPRESSED_SIZE`, the data size limit of a UNF packet. The program on the console has to
+//! decompress them itself, for example with a port of the LZ4 reference decoder. Rom uploads can't
+//! be compressed, since the Everdrive OS has no decompression support.
+
+use crate::Everdrive;
+use crate::PacketReader;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+
+const LZ_PREFIX: &[u8; 2] = b"LZ";
+
+/// Largest uncompressed size of a compressed payload, the same as the data size limit of a UNF
+/// packet. Larger sizes declared by a received packet are rejected before anything is allocated.
+pub const MAX_DECOMPRESSED_SIZE: usize = 0x00FFFFFF;
+
+/// Sizes of a compressed transfer
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    pub raw_bytes: usize,
+    /// Bytes sent over the link, including the packet prefix and size
+    pub compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    /// Compressed size relative to the raw size, below 1.0 when compression saved bytes
+    pub fn ratio(&self) -> f64 {
+        match self.raw_bytes {
+            0 => 1.0,
+            raw => self.compressed_bytes as f64 / raw as f64,
+        }
+    }
+}
+
+/// Compresses `data` into the payload of a compressed packet
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::compress::{compress_payload, decompress_payload};
+///
+/// let data = vec![0x55; 0x1000];
+/// let payload = compress_payload(&data);
+///
+/// assert!(payload.len() < data.len());
+/// assert_eq!(decompress_payload(&payload).unwrap(), data);
+/// ```
+pub fn compress_payload(data: &[u8]) -> Vec<u8> {
+    let mut payload = LZ_PREFIX.to_vec();
+    payload.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&lz4_flex::block::compress(data));
+    payload
+}
+
+/// Decompresses the payload of a compressed packet. Returns an `InvalidData` error if the declared
+/// size exceeds `MAX_DECOMPRESSED_SIZE`.
+pub fn decompress_payload(payload: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut reader = PacketReader::new(payload);
+
+    if reader.consume_bytes(2)? != LZ_PREFIX {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Packet is not compressed",
+        ));
+    }
+
+    let size = reader.consume_word()? as usize;
+
+    if size > MAX_DECOMPRESSED_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Decompressed size of {:#X} bytes exceeds the limit of {:#X} bytes",
+                size, MAX_DECOMPRESSED_SIZE
+            ),
+        ));
+    }
+
+    let block = reader.consume_bytes(reader.remaining())?;
+
+    lz4_flex::block::decompress(block, size)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+}
+
+/// Returns true if `packet` carries a compressed payload
+pub fn is_compressed(packet: &UnfRecvPacket) -> bool {
+    packet.get_datatype() == UnfDataType::DataTypeBinary && packet.get_data().starts_with(LZ_PREFIX)
+}
+
+impl Everdrive {
+    /// Sends `data` LZ4 compressed in a single packet and returns the sizes of the transfer.
+    /// See the `compress` module for the format. Returns an `InvalidInput` error if `data` is larger
+    /// than `MAX_DECOMPRESSED_SIZE`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let level = std::fs::read("level.bin").unwrap();
+    /// let stats = ed.unf_tx_compressed(&level).unwrap();
+    ///
+    /// println!("Sent {} of {} bytes ({:.0}%)", stats.compressed_bytes, stats.raw_bytes, stats.ratio() * 100.0);
+    /// ```
+    pub fn unf_tx_compressed(&mut self, data: &[u8]) -> std::io::Result<CompressionStats> {
+        if data.len() > MAX_DECOMPRESSED_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Data is larger than the decompressed size limit",
+            ));
+        }
+
+        let payload = compress_payload(data);
+
+        self.unf_tx(&UnfSendPacket::from_bytes(
+            UnfDataType::DataTypeBinary,
+            &payload,
+        )?)?;
+
+        Ok(CompressionStats {
+            raw_bytes: data.len(),
+            compressed_bytes: payload.len(),
+        })
+    }
+}
@@ -0,0 +1,345 @@
+//! Parsing of the exception dumps libdragon prints to the debug log.
+//!
+//! When a program built with libdragon crashes with debugging over USB enabled
+//! (`debug_init_usblog`), the default exception handler prints the exception as text, which arrives
+//! as `DataTypeText` packets. The dump starts with a header line and is followed by the COP0 and
+//! general purpose registers, one `name:value` pair each:
+//!
+//! ```text
+//! Address error (load) exception at PC:80001234
+//! CR:00000010 (COP:0 BD:0)
+//! SR:20000003 FCR31:00000000 BVAdr:00000004
+//! z0:00000000 at:80010000 v0:00000004 v1:00000000
+//! ...
+//! lo:0000000000000000 hi:0000000000000000
+//! Backtrace:
+//!     main+0x1c (main.c:12) [0x80001234]
+//!
+//! ```
+//!
+//! Register values are accepted both as plain hex and in the grouped form of the libdragon
+//! inspector (`---- ---- 8000 1234`), and `Exception address:` is accepted in place of `BVAdr:`. The
+//! registers end with `hi`, the lines following it are collected as the backtrace and the dump is
+//! complete at the next blank line. The dump may be split over any number of packets, so
+//! `CrashCollector` reassembles the lines. Programs which don't use
+//! libdragon's exception handler produce no `CrashReport`.
+
+use crate::symbols::SymbolTable;
+
+const CRASH_HEADER: &str = " exception at PC:";
+
+const GPR_NAMES: [&str; 32] = [
+    "zr", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+/// Exception dump printed by the console
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrashReport {
+    /// Description of the exception printed by libdragon, such as "Address error (load)"
+    pub info: String,
+    /// Address of the instruction which caused the exception
+    pub epc: u32,
+    /// COP0 Cause register, the exception code is in bits 2 to 6. `None` if it was not printed.
+    pub cause: Option<u32>,
+    /// Faulting address for address errors and TLB exceptions, `None` if it was not printed
+    pub bad_vaddr: Option<u32>,
+    /// COP0 Status register, `None` if it was not printed
+    pub status: Option<u32>,
+    /// General purpose registers `$0` to `$31`, registers which were not printed are 0
+    pub gpr: [u64; 32],
+    /// Backtrace lines printed after the registers without the `Backtrace:` heading, such as
+    /// `main+0x1c (main.c:12) [0x80001234]`. Empty if none were printed.
+    pub backtrace: Vec<String>,
+}
+
+impl CrashReport {
+    /// Parses a complete exception dump, returns `None` if `text` contains none
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::crash::CrashReport;
+    ///
+    /// let dump = "Address error (load) exception at PC:80001234\n\
+    ///             CR:00000010 (COP:0 BD:0)\n\
+    ///             SR:20000003 FCR31:00000000 BVAdr:00000004\n\
+    ///             z0:00000000 at:80010000 v0:00000004 v1:00000000\n\
+    ///             lo:0000000000000000 hi:0000000000000000\n\
+    ///             Backtrace:\n\
+    ///             \x20   main+0x1c (main.c:12) [0x80001234]\n";
+    ///
+    /// let report = CrashReport::parse(dump).unwrap();
+    ///
+    /// assert_eq!(report.epc, 0x80001234);
+    /// assert_eq!(report.backtrace, ["main+0x1c (main.c:12) [0x80001234]"]);
+    /// assert_eq!(report.bad_vaddr, Some(4));
+    /// assert_eq!(report.exception_code(), Some(4));
+    /// assert_eq!(report.gpr[1], 0x80010000);
+    /// ```
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut collector = CrashCollector::new();
+
+        collector
+            .push(text)
+            .into_iter()
+            .next()
+            .or_else(|| collector.finish())
+    }
+
+    /// Exception code from the Cause register
+    pub fn exception_code(&self) -> Option<u8> {
+        self.cause.map(|cause| ((cause >> 2) & 0x1F) as u8)
+    }
+
+    /// Name of the exception code, such as "Address error (load)"
+    pub fn exception_name(&self) -> Option<&'static str> {
+        let name = match self.exception_code()? {
+            0 => "Interrupt",
+            1 => "TLB modification",
+            2 => "TLB miss (load)",
+            3 => "TLB miss (store)",
+            4 => "Address error (load)",
+            5 => "Address error (store)",
+            6 => "Bus error (instruction)",
+            7 => "Bus error (data)",
+            8 => "Syscall",
+            9 => "Breakpoint",
+            10 => "Reserved instruction",
+            11 => "Coprocessor unusable",
+            12 => "Arithmetic overflow",
+            13 => "Trap",
+            15 => "Floating point",
+            23 => "Watch",
+            _ => "Unknown exception",
+        };
+
+        Some(name)
+    }
+
+    /// Formats the report like its `Display` implementation, with code addresses annotated with `symbols`
+    pub fn format_with_symbols(&self, symbols: &SymbolTable) -> String {
+        symbols.annotate(&self.to_string())
+    }
+
+    /// Applies a `name:value` field of the dump, returns true for the `hi` register which ends it
+    fn apply(&mut self, name: &str, value: &str) -> bool {
+        let Some(value) = parse_register(value) else {
+            return false;
+        };
+
+        match name {
+            "CR" => self.cause = Some(value as u32),
+            "SR" => self.status = Some(value as u32),
+            "BVAdr" => self.bad_vaddr = Some(value as u32),
+            "z0" => self.gpr[0] = value,
+            "s8" => self.gpr[30] = value,
+            "hi" => return true,
+            name => {
+                if let Some(i) = GPR_NAMES.iter().position(|reg| *reg == name) {
+                    self.gpr[i] = value;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl std::fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {:08X}", self.info, self.epc)?;
+
+        match (self.exception_name(), self.exception_code()) {
+            (Some(name), Some(code)) => writeln!(f, ": {} (code {})", name, code)?,
+            _ => writeln!(f)?,
+        }
+
+        let optional = |value: Option<u32>| match value {
+            Some(value) => format!("{:08X}", value),
+            None => "--------".to_string(),
+        };
+
+        writeln!(
+            f,
+            "BadVAddr {} Cause {} Status {}",
+            optional(self.bad_vaddr),
+            optional(self.cause),
+            optional(self.status)
+        )?;
+
+        for (i, regs) in self.gpr.chunks(4).enumerate() {
+            for (j, reg) in regs.iter().enumerate() {
+                // Registers hold sign-extended 32-bit values in practice, print the low word for addresses
+                write!(f, "{} {:08X} ", GPR_NAMES[i * 4 + j], *reg as u32)?;
+            }
+            writeln!(f)?;
+        }
+
+        for line in &self.backtrace {
+            writeln!(f, "    {}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reassembles exception dumps from text split over any number of `DataTypeText` packets, used by
+/// `UnfHandlers::on_crash`
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::crash::CrashCollector;
+///
+/// let mut collector = CrashCollector::new();
+///
+/// assert!(collector.push("Hello\nBus error (data) exception at P").is_empty());
+/// assert!(collector.push("C:80000400\nat:00000001 ").is_empty());
+///
+/// // The backtrace after the registers ends with a blank line
+/// assert!(collector.push("lo:00000000 hi:00000000\nBacktrace:\n").is_empty());
+///
+/// let reports = collector.push("\n");
+/// assert_eq!(reports[0].epc, 0x80000400);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CrashCollector {
+    line: String,
+    report: Option<CrashReport>,
+    /// The registers of `report` are complete and backtrace lines are being collected
+    in_backtrace: bool,
+}
+
+impl CrashCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds received text, returns the dumps completed by it
+    pub fn push(&mut self, text: &str) -> Vec<CrashReport> {
+        let mut reports = Vec::new();
+
+        for c in text.chars() {
+            if c != '\n' {
+                self.line.push(c);
+                continue;
+            }
+
+            let line = std::mem::take(&mut self.line);
+            if let Some(report) = self.push_line(&line) {
+                reports.push(report);
+            }
+        }
+
+        reports
+    }
+
+    /// Ends the current dump, returns it if it was missing its last registers or the blank line
+    /// ending its backtrace
+    pub fn finish(&mut self) -> Option<CrashReport> {
+        let line = std::mem::take(&mut self.line);
+        let report = self.push_line(&line).or_else(|| self.report.take());
+
+        self.in_backtrace = false;
+        report
+    }
+
+    fn push_line(&mut self, line: &str) -> Option<CrashReport> {
+        let line = line.trim_end_matches('\r');
+
+        if let Some(header) = line.find(CRASH_HEADER) {
+            let epc = line[header + CRASH_HEADER.len()..]
+                .split_whitespace()
+                .next()
+                .and_then(|pc| u32::from_str_radix(pc, 16).ok())?;
+
+            // A new dump ends the backtrace of the previous one
+            let previous = match std::mem::take(&mut self.in_backtrace) {
+                true => self.report.take(),
+                false => None,
+            };
+
+            self.report = Some(CrashReport {
+                info: line[..header].trim().to_string(),
+                epc,
+                cause: None,
+                bad_vaddr: None,
+                status: None,
+                gpr: [0; 32],
+                backtrace: Vec::new(),
+            });
+
+            return previous;
+        }
+
+        let report = self.report.as_mut()?;
+
+        if self.in_backtrace {
+            let line = line.trim();
+
+            if line.is_empty() {
+                self.in_backtrace = false;
+                return self.report.take();
+            }
+
+            if line != "Backtrace:" {
+                report.backtrace.push(line.to_string());
+            }
+
+            return None;
+        }
+
+        if let Some(addr) = line.trim().strip_prefix("Exception address:") {
+            report.apply("BVAdr", addr);
+            return None;
+        }
+
+        for (name, value) in fields(line) {
+            if report.apply(name, &value) {
+                self.in_backtrace = true;
+                return None;
+            }
+        }
+
+        None
+    }
+}
+
+/// Splits a line into its `name:value` fields, values may span several words
+fn fields(line: &str) -> Vec<(&str, String)> {
+    let mut fields: Vec<(&str, String)> = Vec::new();
+
+    for word in line.split_whitespace() {
+        match word.split_once(':') {
+            Some((name, value)) => fields.push((name, value.to_string())),
+            None => {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(word);
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// Parses a register value in plain hex or in groups of 4 digits where `----` marks the sign
+/// extension of a 32-bit value
+fn parse_register(value: &str) -> Option<u64> {
+    let groups: Vec<&str> = value.split_whitespace().take(4).collect();
+
+    match groups.as_slice() {
+        ["----", "----", high, low] => {
+            let value = u32::from_str_radix(&format!("{}{}", high, low), 16).ok()?;
+            Some(value as i32 as i64 as u64)
+        }
+        [_, _, _, _] if groups.iter().all(|group| group.len() == 4) => {
+            u64::from_str_radix(&groups.concat(), 16).ok()
+        }
+        [hex, ..] => u64::from_str_radix(hex, 16).ok(),
+        [] => None,
+    }
+}
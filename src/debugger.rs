@@ -0,0 +1,281 @@
+use crate::CancellationToken;
+use crate::Everdrive;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+use std::io::{Read, Write};
+
+/// GDB interrupt request, sent outside of packet framing
+const GDB_INTERRUPT: u8 = 0x03;
+
+/// Largest memory access sent in a single request, kept small for the packet buffers of console stubs
+const GDB_MEMORY_CHUNK: usize = 0x200;
+
+enum GdbParseState {
+    Idle,
+    Payload,
+    Checksum(u8),
+}
+
+/// Parses GDB remote serial protocol packets (`$payload#cs`) out of a byte stream
+struct GdbPacketParser {
+    state: GdbParseState,
+    payload: Vec<u8>,
+    checksum: [u8; 2],
+}
+
+enum GdbEvent {
+    Packet(Vec<u8>),
+    BadChecksum,
+    Interrupt,
+}
+
+impl GdbPacketParser {
+    fn new() -> Self {
+        Self {
+            state: GdbParseState::Idle,
+            payload: Vec::new(),
+            checksum: [0; 2],
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Option<GdbEvent> {
+        match self.state {
+            GdbParseState::Idle => match byte {
+                b'$' => {
+                    self.payload.clear();
+                    self.state = GdbParseState::Payload;
+                    None
+                }
+                GDB_INTERRUPT => Some(GdbEvent::Interrupt),
+                // Acks from gdb are not needed since the USB link is reliable
+                _ => None,
+            },
+            GdbParseState::Payload => {
+                if byte == b'#' {
+                    self.state = GdbParseState::Checksum(0);
+                } else {
+                    self.payload.push(byte);
+                }
+                None
+            }
+            GdbParseState::Checksum(n) => {
+                self.checksum[n as usize] = byte;
+
+                if n == 0 {
+                    self.state = GdbParseState::Checksum(1);
+                    return None;
+                }
+
+                self.state = GdbParseState::Idle;
+
+                let expected = std::str::from_utf8(&self.checksum)
+                    .ok()
+                    .and_then(|cs| u8::from_str_radix(cs, 16).ok());
+
+                if expected == Some(gdb_checksum(&self.payload)) {
+                    Some(GdbEvent::Packet(std::mem::take(&mut self.payload)))
+                } else {
+                    Some(GdbEvent::BadChecksum)
+                }
+            }
+        }
+    }
+}
+
+fn gdb_checksum(payload: &[u8]) -> u8 {
+    payload
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn gdb_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn gdb_unhex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn gdb_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 4);
+    frame.push(b'$');
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(format!("#{:02x}", gdb_checksum(payload)).as_bytes());
+    frame
+}
+
+impl Everdrive {
+    /// Listens for a GDB connection on `addr` and bridges the GDB remote serial protocol to the console
+    /// through `DataTypeRdbPacket` packets. The console side must run a GDB stub speaking over UNF, such as
+    /// the one in the UNFLoader debug library.
+    ///
+    /// Packet framing, checksums and acknowledgements are handled on the host, only packet payloads are sent
+    /// to the console. Other packets received from the console are passed to `on_packet`.
+    /// Returns when the GDB client disconnects or `stop` is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// // Connect with `gdb-multiarch -ex "target remote localhost:8080" your_rom.elf`
+    /// ed.debugger_serve("127.0.0.1:8080", |packet| println!("{:?}", packet), &CancellationToken::new())
+    ///     .unwrap();
+    /// ```
+    pub fn debugger_serve(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        mut on_packet: impl FnMut(&UnfRecvPacket),
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        let (mut stream, _) = listener.accept()?;
+
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(std::time::Duration::from_millis(10)))?;
+
+        let mut parser = GdbPacketParser::new();
+        let mut buf = [0; 1024];
+
+        while !stop.is_cancelled() {
+            match stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => {
+                    for byte in &buf[..n] {
+                        match parser.push(*byte) {
+                            Some(GdbEvent::Packet(payload)) => {
+                                stream.write_all(b"+")?;
+                                self.debugger_tx(&payload)?;
+                            }
+                            Some(GdbEvent::Interrupt) => self.debugger_tx(&[GDB_INTERRUPT])?,
+                            Some(GdbEvent::BadChecksum) => stream.write_all(b"-")?,
+                            None => {}
+                        }
+                    }
+                }
+                Err(err)
+                    if err.kind() == std::io::ErrorKind::WouldBlock
+                        || err.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(err) => return Err(err),
+            }
+
+            while let Some(packet) = self.unf_try_rx()? {
+                if packet.get_datatype() == UnfDataType::DataTypeRdbPacket {
+                    stream.write_all(&gdb_frame(packet.get_data()))?;
+                } else {
+                    on_packet(&packet);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn debugger_tx(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let packet = UnfSendPacket::from_bytes(UnfDataType::DataTypeRdbPacket, payload)?;
+        self.unf_tx(&packet)
+    }
+
+    /// Sends a command to the console's GDB stub and waits for its reply. Packets other than
+    /// `DataTypeRdbPacket` received while waiting are dropped.
+    fn debugger_request(&mut self, payload: &[u8]) -> std::io::Result<Vec<u8>> {
+        self.debugger_tx(payload)?;
+
+        loop {
+            let packet = self.unf_rx()?;
+
+            if packet.get_datatype() != UnfDataType::DataTypeRdbPacket {
+                continue;
+            }
+
+            let reply = packet.into_data();
+
+            if reply.len() == 3 && reply[0] == b'E' {
+                return Err(std::io::Error::other(format!(
+                    "Console debugger returned error {}",
+                    String::from_utf8_lossy(&reply[1..])
+                )));
+            }
+
+            return Ok(reply);
+        }
+    }
+
+    /// Reads `len` bytes of console memory at `addr` while a program is running.
+    ///
+    /// Memory is accessed through the GDB memory commands over `DataTypeRdbPacket` packets, so the
+    /// running program must include a GDB stub speaking over UNF, such as the one in the UNFLoader
+    /// debug library. Other packets received during the access are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let lives = ed.ed_ram_read(0x8033B21D, 1).unwrap();
+    /// println!("Lives: {}", lives[0]);
+    /// ```
+    pub fn ed_ram_read(&mut self, addr: u32, len: usize) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len);
+
+        while data.len() < len {
+            let chunk_addr = addr.wrapping_add(data.len() as u32);
+            let chunk_len = (len - data.len()).min(GDB_MEMORY_CHUNK);
+
+            let reply =
+                self.debugger_request(format!("m{:x},{:x}", chunk_addr, chunk_len).as_bytes())?;
+
+            let chunk = gdb_unhex(&reply)
+                .filter(|chunk| chunk.len() == chunk_len)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Invalid memory read reply from console debugger",
+                    )
+                })?;
+
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(data)
+    }
+
+    /// Writes `data` to console memory at `addr` while a program is running. Requires a GDB stub
+    /// on the console like `ed_ram_read`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_ram_write(0x8033B21D, &[99]).unwrap();
+    /// ```
+    pub fn ed_ram_write(&mut self, addr: u32, data: &[u8]) -> std::io::Result<()> {
+        for (index, chunk) in data.chunks(GDB_MEMORY_CHUNK).enumerate() {
+            let chunk_addr = addr.wrapping_add((index * GDB_MEMORY_CHUNK) as u32);
+
+            let request = format!("M{:x},{:x}:{}", chunk_addr, chunk.len(), gdb_hex(chunk));
+            let reply = self.debugger_request(request.as_bytes())?;
+
+            if reply != b"OK" {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid memory write reply from console debugger",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
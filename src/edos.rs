@@ -1,17 +1,384 @@
 use crate::Everdrive;
+use crate::ProgressOperation;
+use crate::byteswap;
+use crate::rom::{CRC_AREA_SIZE, RomKind, TvStandard, rom_swap_to_native};
+use crate::session::SessionState;
+use crate::trace::{trace_event, trace_span};
 
 pub const ROM_BASE_ADDR: u32 = 0x10000000;
 pub const ROM_BASE_ADDR_EMU: u32 = 0x10200000;
 
+pub const ROM_STREAM_CHUNK_SIZE: usize = 0x10000;
+
+/// Rom writes are split into separate commands of this size, so they can be cancelled between chunks
+const ROM_WRITE_CHUNK_SIZE: usize = 0x100000;
+
+const VERIFY_CHUNK_SIZE: usize = 0x10000;
+const VERIFY_BLOCK_SIZE: usize = 512;
+/// Granularity of the rom read and write commands
+const ROM_BLOCK_SIZE: u32 = 512;
+/// Value the last block of a rom is padded with
+const ROM_PADDING_BYTE: u8 = 0xFF;
+
+/// Selects how much of the rom is read back by `ed_verify_rom`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdVerifyMode {
+    /// Read back and compare the whole region
+    Full,
+    /// Read back and compare the given number of evenly spaced 512 byte blocks,
+    /// always including the first and the last block
+    Sampled(usize),
+}
+
+/// Load settings for `ed_load_rom_path`
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RomLoadOptions {
+    /// Address to load N64 roms to, defaults to the rom base of the memory map
+    pub base_address: Option<u32>,
+    /// Save type written to the rom header
+    pub save_type: Option<EdSaveType>,
+    /// RTC and region settings, only used when `save_type` is set
+    pub rtc_region_type: Option<EdRtcRegionType>,
+}
+
+/// What `ed_load_rom` decided and uploaded, for logging and assertions
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RomLoadReport {
+    /// Detected rom kind, including the byte order of N64 roms
+    pub kind: RomKind,
+    /// Address the rom was written to
+    pub base_address: u32,
+    /// Save type written to the rom header, if any
+    pub save_type: Option<EdSaveType>,
+    /// RTC and region settings written to the rom header, if any
+    pub rtc_region_type: Option<EdRtcRegionType>,
+    /// Size of the uploaded image before padding
+    pub size: usize,
+    /// Size of the uploaded image after padding to 512 byte blocks
+    pub padded_size: usize,
+    /// Bytes of overdump padding removed from the end of the rom, see `set_rom_trimming`
+    pub trimmed_bytes: usize,
+    /// Checksums from the header of N64 roms, as `(crc1, crc2)`
+    pub header_crc: Option<(u32, u32)>,
+    /// Video standard of the uploaded N64 rom after region patching, `None` for unknown regions
+    pub tv_standard: Option<TvStandard>,
+    /// CRC32 of the uploaded image before padding, in the byte order it was uploaded in
+    pub crc32: u32,
+}
+
+/// Error payload returned by `ed_verify_rom` when the data read back from the
+/// device does not match. Available through `std::io::Error::get_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdVerifyMismatch {
+    /// Offset of the first mismatching byte, relative to the start of the verified data
+    pub offset: usize,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+impl std::fmt::Display for EdVerifyMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rom verification failed at offset {:#X}, expected {:#04X} got {:#04X}",
+            self.offset, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for EdVerifyMismatch {}
+
+/// Error payload returned by `ed_fpga_init` when the Everdrive reports a failed FPGA
+/// configuration. Available through `std::io::Error::get_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdFpgaError {
+    /// Non-zero status code reported by the Everdrive
+    pub code: u8,
+}
+
+impl std::fmt::Display for EdFpgaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FPGA configuration failed with status {:#04X}",
+            self.code
+        )
+    }
+}
+
+impl std::error::Error for EdFpgaError {}
+
+/// Size of the file name buffer sent with `AppStart`, including the terminating zero
+const FILE_NAME_BUF_SIZE: usize = 256;
+
+/// Characters FAT does not allow in long file names
+const FILE_NAME_INVALID_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+/// Device names which can't be used as file names on FAT, with or without an extension
+const FILE_NAME_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Reason a file name was rejected by `EdFileName::new`. Returned by `ed_app_start` as the payload
+/// of an `InvalidInput` error, available through `std::io::Error::get_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdFileNameError {
+    Empty,
+    /// The name is longer than the 255 bytes the Everdrive OS accepts
+    TooLong(usize),
+    /// The name contains a path separator, only a file name in the save folder can be given
+    PathSeparator,
+    /// The name contains a character FAT does not allow, or a control character
+    InvalidChar(char),
+    /// The name contains a character outside of ASCII, which the Everdrive OS can't encode
+    NonAscii(char),
+    /// The name ends with a dot or space, which FAT silently strips
+    TrailingDotOrSpace,
+    /// The name is a reserved device name such as `CON` or `NUL`
+    Reserved,
+}
+
+impl std::fmt::Display for EdFileNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "File name is empty"),
+            Self::TooLong(len) => write!(
+                f,
+                "File name is {} bytes long, at most {} are allowed",
+                len,
+                FILE_NAME_BUF_SIZE - 1
+            ),
+            Self::PathSeparator => write!(f, "File name contains a path separator"),
+            Self::InvalidChar(c) => write!(f, "File name contains invalid character {:?}", c),
+            Self::NonAscii(c) => write!(f, "File name contains non-ASCII character {:?}", c),
+            Self::TrailingDotOrSpace => write!(f, "File name ends with a dot or space"),
+            Self::Reserved => write!(f, "File name is a reserved device name"),
+        }
+    }
+}
+
+impl std::error::Error for EdFileNameError {}
+
+/// Name of the save file on the FAT formatted SD card, as passed to `ed_app_start`.
+///
+/// `new` checks that the name can be stored as a long file name by the Everdrive OS: ASCII only,
+/// at most 255 bytes, without path separators, characters reserved by FAT, trailing dots or
+/// spaces, or device names. `sanitized` converts any name instead, such as one taken from a host
+/// path.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::{EdFileName, EdFileNameError};
+///
+/// assert_eq!(EdFileName::new("Super Mario 64.z64").unwrap().as_str(), "Super Mario 64.z64");
+/// assert_eq!(EdFileName::new("roms/game.z64"), Err(EdFileNameError::PathSeparator));
+/// assert_eq!(EdFileName::new("Pokémon.z64"), Err(EdFileNameError::NonAscii('é')));
+///
+/// assert_eq!(EdFileName::sanitized("roms/Pokémon: Snap?.z64").as_str(), "Pokemon_ Snap_.z64");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdFileName(String);
+
+impl EdFileName {
+    pub fn new(name: &str) -> Result<Self, EdFileNameError> {
+        if name.is_empty() {
+            return Err(EdFileNameError::Empty);
+        }
+
+        if name.len() >= FILE_NAME_BUF_SIZE {
+            return Err(EdFileNameError::TooLong(name.len()));
+        }
+
+        for c in name.chars() {
+            if c == '/' || c == '\\' {
+                return Err(EdFileNameError::PathSeparator);
+            } else if !c.is_ascii() {
+                return Err(EdFileNameError::NonAscii(c));
+            } else if c.is_ascii_control() || FILE_NAME_INVALID_CHARS.contains(&c) {
+                return Err(EdFileNameError::InvalidChar(c));
+            }
+        }
+
+        if name.ends_with(['.', ' ']) {
+            return Err(EdFileNameError::TrailingDotOrSpace);
+        }
+
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        if FILE_NAME_RESERVED
+            .iter()
+            .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+        {
+            return Err(EdFileNameError::Reserved);
+        }
+
+        Ok(Self(name.to_string()))
+    }
+
+    /// Converts `name` to a valid file name: directories are removed, accented latin letters lose
+    /// their accents, other invalid characters become `_`, trailing dots and spaces are removed
+    /// and the name is shortened to 255 bytes keeping the extension. Reserved and empty names get a
+    /// `_` appended to the part before the extension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::EdFileName;
+    ///
+    /// assert_eq!(EdFileName::sanitized("CON.txt").as_str(), "CON_.txt");
+    /// assert_eq!(EdFileName::sanitized("...").as_str(), "_");
+    /// ```
+    pub fn sanitized(name: &str) -> Self {
+        let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+
+        let mut converted: String = name
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii()
+                    && !c.is_ascii_control()
+                    && !FILE_NAME_INVALID_CHARS.contains(&c) =>
+                {
+                    c
+                }
+                c => strip_accent(c).unwrap_or('_'),
+            })
+            .collect();
+
+        if converted.len() >= FILE_NAME_BUF_SIZE {
+            let ext = match converted.rfind('.') {
+                Some(dot) if converted.len() - dot <= 16 => converted.split_off(dot),
+                _ => String::new(),
+            };
+
+            converted.truncate(FILE_NAME_BUF_SIZE - 1 - ext.len());
+            converted.push_str(&ext);
+        }
+
+        let mut converted = converted.trim_end_matches(['.', ' ']).to_string();
+
+        if Self::new(&converted).is_err() {
+            let stem_end = converted.find('.').unwrap_or(converted.len());
+            converted.insert(stem_end, '_');
+            converted.truncate(FILE_NAME_BUF_SIZE - 1);
+            converted = converted.trim_end_matches(['.', ' ']).to_string();
+        }
+
+        debug_assert!(
+            Self::new(&converted).is_ok(),
+            "sanitized file name {:?} is invalid",
+            converted
+        );
+
+        Self(converted)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Zero terminated buffer sent after the `AppStart` command
+    fn to_buf(&self) -> [u8; FILE_NAME_BUF_SIZE] {
+        let mut buf = [0; FILE_NAME_BUF_SIZE];
+        buf[..self.0.len()].copy_from_slice(self.0.as_bytes());
+        buf
+    }
+}
+
+impl std::fmt::Display for EdFileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for EdFileName {
+    type Err = EdFileNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+/// Settings for starting the loaded rom with `ed_app_start_with`.
+///
+/// The save type override is written into the header of the rom already loaded at `base_address`,
+/// so a rom can be started with a different save type without uploading it again. The header is
+/// not covered by the rom checksums. To change the region code of the header, see
+/// `rom::patch_tv_standard`. Cheats can't be enabled over USB, see the `cheats`
+/// module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BootOptions {
+    /// Name of the save file on the SD card. Without a name the Everdrive OS starts the rom without
+    /// loading or storing saves.
+    pub save_file_name: Option<EdFileName>,
+    /// Overrides the save type in the header of the loaded rom
+    pub save_type: Option<EdSaveType>,
+    /// Overrides the RTC and region settings, only used when `save_type` is set
+    pub rtc_region_type: Option<EdRtcRegionType>,
+    /// Address the rom was loaded to, defaults to the rom base of the memory map
+    pub base_address: Option<u32>,
+}
+
+/// Returns the base letter of accented latin letters
+fn strip_accent(c: char) -> Option<char> {
+    let base = match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        _ => return None,
+    };
+
+    Some(base)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum EdCommand {
     Test,
     RomWrite(u32, u32),
+    RomRead(u32, u32),
     RomFill(u32, u32, u32),
     FpgaInit(u32),
     AppStart(bool),
+    /// Raw command with command byte, address, size and argument.
+    /// Unlike the other commands, the size is sent as is without converting it to 512 byte blocks.
+    Custom(u8, u32, u32, u32),
 }
 
+/// Save type emulated by the Everdrive.
+///
+/// Converts to and from the value stored in the rom header and to and from the names "eeprom4k",
+/// "eeprom16k", "sram", "sram768k", "flashram" and "sram128k". The `serde` feature uses the same names.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::EdSaveType;
+///
+/// let save_type: EdSaveType = "eeprom16k".parse().unwrap();
+///
+/// assert_eq!(save_type, EdSaveType::Eeprom16k);
+/// assert_eq!(save_type.to_string(), "eeprom16k");
+/// assert_eq!(EdSaveType::try_from(0x50).unwrap(), EdSaveType::FlashRam);
+/// assert_eq!(u8::from(EdSaveType::FlashRam), 0x50);
+/// ```
 #[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum EdSaveType {
     Eeprom4k = 0x10,
     Eeprom16k = 0x20,
@@ -21,23 +388,219 @@ pub enum EdSaveType {
     Sram128k = 0x60,
 }
 
+/// RTC and region options of the Everdrive, with the names "rtc", "noregion" and "all", which the
+/// `serde` feature uses as well
 #[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum EdRtcRegionType {
     Rtc = 0x01,
     NoRegion = 0x02,
     All = 0x03,
 }
 
-impl EdCommand {
-    fn to_bytes(&self) -> std::io::Result<[u8; 16]> {
-        const CMD_PREFIX: &[u8; 3] = b"cmd";
+fn unknown_variant(kind: &str, value: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("Unknown {} {}", kind, value),
+    )
+}
+
+impl EdSaveType {
+    pub const ALL: [EdSaveType; 6] = [
+        EdSaveType::Eeprom4k,
+        EdSaveType::Eeprom16k,
+        EdSaveType::Sram,
+        EdSaveType::Sram768k,
+        EdSaveType::FlashRam,
+        EdSaveType::Sram128k,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdSaveType::Eeprom4k => "eeprom4k",
+            EdSaveType::Eeprom16k => "eeprom16k",
+            EdSaveType::Sram => "sram",
+            EdSaveType::Sram768k => "sram768k",
+            EdSaveType::FlashRam => "flashram",
+            EdSaveType::Sram128k => "sram128k",
+        }
+    }
+}
+
+impl From<EdSaveType> for u8 {
+    fn from(save_type: EdSaveType) -> u8 {
+        save_type as u8
+    }
+}
+
+impl TryFrom<u8> for EdSaveType {
+    type Error = std::io::Error;
+
+    fn try_from(value: u8) -> std::io::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|save_type| *save_type as u8 == value)
+            .ok_or_else(|| unknown_variant("save type", format!("{:#04X}", value)))
+    }
+}
+
+impl std::str::FromStr for EdSaveType {
+    type Err = std::io::Error;
+
+    /// Parses a save type name, ignoring case, dashes and underscores
+    fn from_str(name: &str) -> std::io::Result<Self> {
+        let normalized = normalize_name(name);
+
+        Self::ALL
+            .into_iter()
+            .find(|save_type| save_type.name() == normalized)
+            .ok_or_else(|| unknown_variant("save type", name))
+    }
+}
+
+impl std::fmt::Display for EdSaveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl EdRtcRegionType {
+    pub const ALL: [EdRtcRegionType; 3] = [
+        EdRtcRegionType::Rtc,
+        EdRtcRegionType::NoRegion,
+        EdRtcRegionType::All,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdRtcRegionType::Rtc => "rtc",
+            EdRtcRegionType::NoRegion => "noregion",
+            EdRtcRegionType::All => "all",
+        }
+    }
+}
+
+impl From<EdRtcRegionType> for u8 {
+    fn from(rtc_region_type: EdRtcRegionType) -> u8 {
+        rtc_region_type as u8
+    }
+}
+
+impl TryFrom<u8> for EdRtcRegionType {
+    type Error = std::io::Error;
+
+    fn try_from(value: u8) -> std::io::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|rtc_region_type| *rtc_region_type as u8 == value)
+            .ok_or_else(|| unknown_variant("RTC and region type", format!("{:#04X}", value)))
+    }
+}
+
+impl std::str::FromStr for EdRtcRegionType {
+    type Err = std::io::Error;
+
+    /// Parses an RTC and region type name, ignoring case, dashes and underscores
+    fn from_str(name: &str) -> std::io::Result<Self> {
+        let normalized = normalize_name(name);
+
+        Self::ALL
+            .into_iter()
+            .find(|rtc_region_type| rtc_region_type.name() == normalized)
+            .ok_or_else(|| unknown_variant("RTC and region type", name))
+    }
+}
+
+impl std::fmt::Display for EdRtcRegionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Lowercases `name` and strips dashes, underscores and surrounding whitespace
+fn normalize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl TryFrom<[u8; 16]> for EdCommand {
+    type Error = std::io::Error;
+
+    /// Decodes a command frame. Unknown command bytes decode to `Custom`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::EdCommand;
+    ///
+    /// let mut frame = *b"cmdW\x10\0\0\0\0\0\0\x02\0\0\0\0";
+    ///
+    /// assert_eq!(
+    ///     EdCommand::try_from(frame).unwrap(),
+    ///     EdCommand::RomWrite(0x10000000, 1024)
+    /// );
+    ///
+    /// frame[0] = b'x';
+    /// assert!(EdCommand::try_from(frame).is_err());
+    /// ```
+    fn try_from(frame: [u8; 16]) -> std::io::Result<Self> {
+        if &frame[0..3] != b"cmd" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not an Everdrive command frame",
+            ));
+        }
+
+        let word = |offset: usize| {
+            u32::from_be_bytes([
+                frame[offset],
+                frame[offset + 1],
+                frame[offset + 2],
+                frame[offset + 3],
+            ])
+        };
+        let (addr, blocks, arg) = (word(4), word(8), word(12));
+        let size = blocks.wrapping_mul(512);
+
+        Ok(match frame[3] {
+            b't' => EdCommand::Test,
+            b'W' => EdCommand::RomWrite(addr, size),
+            b'R' => EdCommand::RomRead(addr, size),
+            b'c' => EdCommand::RomFill(addr, size, arg),
+            b'f' => EdCommand::FpgaInit(size),
+            b's' => EdCommand::AppStart(arg != 0),
+            cmd => EdCommand::Custom(cmd, addr, blocks, arg),
+        })
+    }
+}
 
+impl TryFrom<EdCommand> for [u8; 16] {
+    type Error = std::io::Error;
+
+    /// Encodes a command frame. Returns an `InvalidInput` error if the size is not a multiple of
+    /// 512 bytes.
+    fn try_from(command: EdCommand) -> std::io::Result<Self> {
+        command.to_bytes()
+    }
+}
+
+impl EdCommand {
+    fn to_bytes(self) -> std::io::Result<[u8; 16]> {
         let (cmd, addr, size, arg) = match self {
             EdCommand::Test => (b't', 0u32, 0u32, 0u32),
-            EdCommand::RomWrite(addr, size) => (b'W', *addr, *size, 0),
-            EdCommand::RomFill(addr, size, arg) => (b'c', *addr, *size, *arg),
-            EdCommand::FpgaInit(size) => (b'f', 0, *size, 0),
-            EdCommand::AppStart(save_path) => (b's', 0, 0, *save_path as u32),
+            EdCommand::RomWrite(addr, size) => (b'W', addr, size, 0),
+            EdCommand::RomRead(addr, size) => (b'R', addr, size, 0),
+            EdCommand::RomFill(addr, size, arg) => (b'c', addr, size, arg),
+            EdCommand::FpgaInit(size) => (b'f', 0, size, 0),
+            EdCommand::AppStart(save_path) => (b's', 0, 0, save_path as u32),
+            EdCommand::Custom(cmd, addr, size, arg) => {
+                return Ok(Self::encode(cmd, addr, size, arg));
+            }
         };
 
         let size = if size % 512 != 0 {
@@ -49,6 +612,12 @@ impl EdCommand {
             size / 512
         };
 
+        Ok(Self::encode(cmd, addr, size, arg))
+    }
+
+    fn encode(cmd: u8, addr: u32, size: u32, arg: u32) -> [u8; 16] {
+        const CMD_PREFIX: &[u8; 3] = b"cmd";
+
         let mut buf = [0; 16];
         buf[0..3].copy_from_slice(CMD_PREFIX);
 
@@ -58,10 +627,60 @@ impl EdCommand {
         buf[8..12].copy_from_slice(&size.to_be_bytes());
         buf[12..16].copy_from_slice(&arg.to_be_bytes());
 
-        Ok(buf)
+        buf
+    }
+}
+
+impl crate::PacketReader<'_> {
+    /// Reads a 16 byte EDOS response frame and returns the response byte and the 12 payload bytes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::PacketReader;
+    ///
+    /// let frame = [b'c', b'm', b'd', b'r', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let (resp, payload) = PacketReader::new(&frame).consume_ed_frame().unwrap();
+    ///
+    /// assert_eq!(resp, b'r');
+    /// assert_eq!(payload, [0; 12]);
+    /// ```
+    pub fn consume_ed_frame(&mut self) -> std::io::Result<(u8, [u8; 12])> {
+        let frame = self.consume_bytes(16)?;
+
+        if frame[0..3] != *b"cmd" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid EDOS frame prefix {:02X?}", &frame[0..3]),
+            ));
+        }
+
+        Ok((frame[3], frame[4..].try_into().unwrap()))
     }
 }
 
+/// Copies `data` padded with `ROM_PADDING_BYTE` to a whole number of 512 byte blocks
+fn pad_rom(data: &[u8]) -> Vec<u8> {
+    let mut padded = data.to_vec();
+    padded.resize(
+        data.len().next_multiple_of(ROM_BLOCK_SIZE as usize),
+        ROM_PADDING_BYTE,
+    );
+    padded
+}
+
+fn rom_patch_save_type(
+    data: &mut [u8],
+    save_type: EdSaveType,
+    rtc_region_type: Option<EdRtcRegionType>,
+) {
+    let region_type = rtc_region_type.map(|val| val as u8).unwrap_or(0);
+    data[0x3C] = 0x45;
+    data[0x3D] = 0x44;
+    // Save type values are already in the upper nibble
+    data[0x3F] = (save_type as u8) | region_type;
+}
+
 impl Everdrive {
     /// Tests a handshake with the Everdrive device and returns an error if the handshake fails.
     ///
@@ -78,8 +697,15 @@ impl Everdrive {
     /// }
     /// ```
     pub fn ed_status(&mut self) -> std::io::Result<()> {
-        self.ed_tx(EdCommand::Test)?;
-        self.ed_rx(b'r')?;
+        self.with_retry(|ed| {
+            ed.ed_tx(EdCommand::Test)?;
+            ed.ed_rx(b'r')
+        })?;
+
+        if self.session == SessionState::Unknown {
+            self.session = SessionState::Menu;
+        }
+
         Ok(())
     }
 
@@ -95,6 +721,7 @@ impl Everdrive {
     /// ed.ed_rom_fill(0x10000000, 0x1000, 0xFF).unwrap();
     /// ```
     pub fn ed_rom_fill(&mut self, addr: u32, size: u32, val: u32) -> std::io::Result<()> {
+        self.upload_cache_invalidate()?;
         self.ed_tx(EdCommand::RomFill(addr, size, val))
     }
 
@@ -111,11 +738,51 @@ impl Everdrive {
     /// ed.ed_rom_write(0x10000000, &data).unwrap();
     /// ```
     pub fn ed_rom_write(&mut self, addr: u32, data: &[u8]) -> std::io::Result<()> {
-        self.ed_tx(EdCommand::RomWrite(addr, data.len() as u32))?;
-        self.write_all(data)
+        if !data.len().is_multiple_of(512) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Size must be a multiple of 512",
+            ));
+        }
+
+        self.upload_cache_invalidate()?;
+        self.rom_write_blocks(addr, data)
     }
 
-    /// Inits fpga with a RBF file. Data size must be divisible by 512.
+    /// Writes whole 512 byte blocks of the rom in chunks of `ROM_WRITE_CHUNK_SIZE` bytes
+    pub(crate) fn rom_write_blocks(&mut self, addr: u32, data: &[u8]) -> std::io::Result<()> {
+        for (i, chunk) in data.chunks(ROM_WRITE_CHUNK_SIZE).enumerate() {
+            self.check_cancelled()?;
+
+            let chunk_addr = addr + (i * ROM_WRITE_CHUNK_SIZE) as u32;
+
+            // A failed write may have moved part of the chunk, which can't be told from the error,
+            // so only the command frame is retried
+            let sending = std::cell::Cell::new(false);
+
+            self.with_retry_if(
+                |ed| {
+                    ed.ed_tx(EdCommand::RomWrite(chunk_addr, chunk.len() as u32))?;
+                    sending.set(true);
+                    ed.write_all(chunk)
+                },
+                || !sending.get(),
+            )?;
+
+            self.emit_event(|events| events.on_chunk_written(chunk_addr, chunk.len()));
+            self.progress_advance(chunk.len());
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` to the rom at any address and size, for updating small regions such as
+    /// assets without uploading the whole rom again.
+    ///
+    /// The 512 byte blocks containing the region are read, modified and written back, so only the
+    /// bytes covered by `data` change. Like the other rom commands this is served by the Everdrive
+    /// OS, so it has to run before `ed_app_start` or after returning to the menu. A running program
+    /// can only be patched in RAM, see `ed_ram_write`.
     ///
     /// # Examples
     ///
@@ -124,161 +791,1016 @@ impl Everdrive {
     ///
     /// let mut ed = Everdrive::new("COM3").unwrap();
     ///
-    /// let fpga_data = vec![0; 0x100000];
-    /// ed.ed_fpga_init(0x100000, &fpga_data).unwrap();
+    /// ed.ed_rom_patch(0x10123456, b"NEW TEXT").unwrap();
     /// ```
-    pub fn ed_fpga_init(&mut self, size: u32, data: &[u8]) -> std::io::Result<()> {
-        self.ed_tx(EdCommand::FpgaInit(size))?;
-        self.write_all(data)?;
+    pub fn ed_rom_patch(&mut self, addr: u32, data: &[u8]) -> std::io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // The block aligned end may lie past u32::MAX even when the patch itself doesn't
+        let end = addr as u64 + data.len() as u64;
+        let block_end =
+            u32::try_from(end.next_multiple_of(ROM_BLOCK_SIZE as u64)).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Patch region exceeds the address space",
+                )
+            })?;
+
+        let block_start = addr - addr % ROM_BLOCK_SIZE;
 
-        // @todo - Check that the second response byte is 0
-        // non-zero are error codes
-        self.ed_rx(b'r')
+        let mut blocks = vec![0; (block_end - block_start) as usize];
+        let offset = (addr - block_start) as usize;
+
+        // Only the partial first and last blocks need their surrounding bytes read back
+        let first = ROM_BLOCK_SIZE as usize;
+        self.ed_rom_read(block_start, &mut blocks[..first])?;
+
+        if blocks.len() > first {
+            let last = blocks.len() - ROM_BLOCK_SIZE as usize;
+            self.ed_rom_read(block_end - ROM_BLOCK_SIZE, &mut blocks[last..])?;
+        }
+
+        blocks[offset..offset + data.len()].copy_from_slice(data);
+
+        self.ed_rom_write(block_start, &blocks)
     }
 
-    /// Starts a rom file. The rom file must be loaded first using `ed_load_rom`
-    ///
-    /// Optional `file_name` is used for specifying save file on the SD card
+    /// Reads a region of the rom into `buf`. Buffer size must be divisible by 512.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use libeverdrive::Everdrive;
-    /// use std::fs;
     ///
     /// let mut ed = Everdrive::new("COM3").unwrap();
     ///
-    /// let rom_data = fs::read("your_rom.z64").unwrap();
-    ///
-    /// ed.ed_load_rom(rom_data, None, None, None).unwrap();
-    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// let mut buf = vec![0; 512];
+    /// ed.ed_rom_read(0x10000000, &mut buf).unwrap();
     /// ```
-    pub fn ed_app_start(&mut self, file_name: Option<&str>) -> std::io::Result<()> {
-        let file_name_buf = if let Some(file_name) = file_name {
-            if file_name.len() >= 256 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "File name is too long",
-                ));
-            }
-
-            let mut buf = [0; 256];
-            buf[0..file_name.len()].copy_from_slice(file_name.as_bytes());
-
-            Some(buf)
-        } else {
-            None
-        };
+    pub fn ed_rom_read(&mut self, addr: u32, buf: &mut [u8]) -> std::io::Result<()> {
+        // Once part of the response has arrived the rest of it is still on its way, so the read
+        // is only retried while nothing has been received
+        let received = std::cell::Cell::new(false);
 
-        self.ed_tx(EdCommand::AppStart(file_name_buf.is_some()))?;
+        self.with_retry_if(
+            |ed| {
+                ed.ed_tx(EdCommand::RomRead(addr, buf.len() as u32))?;
 
-        if let Some(buf) = file_name_buf {
-            self.write_all(&buf)?;
-        }
+                let mut done = 0;
+                while done < buf.len() {
+                    match ed.read(&mut buf[done..]) {
+                        Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                        Ok(len) => {
+                            done += len;
+                            received.set(true);
+                        }
+                        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                        Err(err) => return Err(err),
+                    }
+                }
 
-        Ok(())
+                Ok(())
+            },
+            || !received.get(),
+        )
     }
 
-    /// Loads a rom file into the specified base address
+    /// Reads back a region of the rom and compares it against `data`. Data size must be divisible by 512.
     ///
-    /// `rom_file` should contain the rom file as data. The base address is optional and defaults to ROM_BASE_ADDR.
-    /// `save_type` and `rtc_region_type` are optional and are used to specify the save type and RTC region type respectively.
-    /// Additional checks are done to determine the endianness of the rom file and swap bytes accordingly, and
-    /// to set the save type and RTC region type in the rom file header.
+    /// Returns an `InvalidData` error carrying an `EdVerifyMismatch` with the offset of the first mismatching
+    /// byte if the rom contents differ. Note that `ed_load_rom` swaps and patches the rom in place, so pass
+    /// the same buffer that was loaded to verify it.
     ///
     /// # Examples
     ///
     /// ```no_run
-    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::{EdVerifyMismatch, EdVerifyMode, Everdrive};
     /// use std::fs;
     ///
     /// let mut ed = Everdrive::new("COM3").unwrap();
     ///
-    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    /// let mut rom_data = fs::read("your_rom.z64").unwrap();
     ///
-    /// ed.ed_load_rom(rom_data, None, None, None).unwrap();
-    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ed.ed_load_rom(&mut rom_data, None, None, None).unwrap();
+    ///
+    /// if let Err(err) = ed.ed_verify_rom(0x10000000, &rom_data, EdVerifyMode::Sampled(64)) {
+    ///     match err.get_ref().and_then(|e| e.downcast_ref::<EdVerifyMismatch>()) {
+    ///         Some(mismatch) => eprintln!("Mismatch at {:#X}", mismatch.offset),
+    ///         None => eprintln!("Read error: {:?}", err),
+    ///     }
+    /// }
     /// ```
-    pub fn ed_load_rom(
+    pub fn ed_verify_rom(
         &mut self,
-        rom_file: Vec<u8>,
-        base_address: Option<u32>,
-        save_type: Option<EdSaveType>,
-        rtc_region_type: Option<EdRtcRegionType>,
+        addr: u32,
+        data: &[u8],
+        mode: EdVerifyMode,
     ) -> std::io::Result<()> {
-        // reference https://github.com/krikzz/ED64/blob/master/usb64/usb64/CommandProcessor.cs#L125
-        let mut rom_file = rom_file.clone();
+        if addr as u64 + data.len() as u64 > u32::MAX as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Verify region exceeds the address space",
+            ));
+        }
 
-        let header_word_be =
-            u32::from_be_bytes([rom_file[0], rom_file[1], rom_file[2], rom_file[3]]);
+        let ranges: Vec<(usize, usize)> = match mode {
+            EdVerifyMode::Full => (0..data.len())
+                .step_by(VERIFY_CHUNK_SIZE)
+                .map(|offset| (offset, VERIFY_CHUNK_SIZE.min(data.len() - offset)))
+                .collect(),
+            EdVerifyMode::Sampled(count) => {
+                let blocks = data.len() / VERIFY_BLOCK_SIZE;
+                let count = count.clamp(1, blocks.max(1));
 
-        let mut base_address = base_address.unwrap_or(ROM_BASE_ADDR);
+                let mut offsets: Vec<usize> = (0..count)
+                    .map(|i| match count {
+                        1 => 0,
+                        _ => i * (blocks - 1) / (count - 1) * VERIFY_BLOCK_SIZE,
+                    })
+                    .collect();
+                offsets.dedup();
 
-        match header_word_be {
-                0x80371240 /* Big-endian native */ => { /* No need to do anything */}
-                0x37804012 /* Byte-swapped, swap every 2 bytes */=> {
-                    for i in (0..rom_file.len()).step_by(2) {
-                        rom_file.swap(i, i + 1);
-                    }
-                }
-                0x40123780 /* Little-endian, swap every 4 bytes */ => {
-                    for i in (0..rom_file.len()).step_by(4) {
-                        rom_file.swap(i, i + 3);
-                        rom_file.swap(i + 1, i + 2);
-                    }
-                }
-                _ => {
-                    // Don't swap and assume emulator rom
-                    base_address = ROM_BASE_ADDR_EMU;
-                }
-            };
+                offsets
+                    .into_iter()
+                    .map(|offset| (offset, VERIFY_BLOCK_SIZE.min(data.len() - offset)))
+                    .collect()
+            }
+        };
 
-        if let Some(st) = save_type {
-            let region_type = rtc_region_type.map(|val| val as u8).unwrap_or(0);
-            rom_file[0x3C] = 0x45;
-            rom_file[0x3D] = 0x44;
-            rom_file[0x3F] = ((st as u8) << 4) | region_type;
-        }
+        let mut buf = vec![0; VERIFY_CHUNK_SIZE];
 
-        self.ed_load_rom_force(rom_file, base_address)
+        for (offset, len) in ranges {
+            self.check_cancelled()?;
+            self.ed_rom_read(addr + offset as u32, &mut buf[..len])?;
+
+            let expected = &data[offset..offset + len];
+
+            if let Some(i) = (0..len).find(|&i| buf[i] != expected[i]) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    EdVerifyMismatch {
+                        offset: offset + i,
+                        expected: expected[i],
+                        actual: buf[i],
+                    },
+                ));
+            }
+        }
+
+        Ok(())
     }
 
-    /// Loads a rom file into the specified base address. But does not do checks for
-    /// endianness or base_address.
-    pub fn ed_load_rom_force(&mut self, data: Vec<u8>, base_address: u32) -> std::io::Result<()> {
-        const CRC_AREA_SIZE: usize = 0x101000;
+    /// Inits fpga with a RBF file. Data size must be divisible by 512.
+    ///
+    /// Returns an `Other` error carrying an `EdFpgaError` if the Everdrive reports a non-zero
+    /// configuration status.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let fpga_data = vec![0; 0x100000];
+    /// ed.ed_fpga_init(0x100000, &fpga_data).unwrap();
+    /// ```
+    pub fn ed_fpga_init(&mut self, size: u32, data: &[u8]) -> std::io::Result<()> {
+        self.capabilities.check_fpga_init()?;
+
+        self.with_progress(ProgressOperation::FpgaInit, Some(data.len() as u64), |ed| {
+            ed.ed_tx(EdCommand::FpgaInit(size))?;
+
+            for chunk in data.chunks(ROM_WRITE_CHUNK_SIZE) {
+                ed.check_cancelled()?;
+                ed.write_all(chunk)?;
+                ed.progress_advance(chunk.len());
+            }
+
+            // The first payload byte is the configuration status, non-zero values are error codes
+            let status = ed.ed_rx_payload(b'r')?[0];
+
+            if status != 0 {
+                return Err(std::io::Error::other(EdFpgaError { code: status }));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Inits fpga with the RBF file at `path`.
+    ///
+    /// The file is checked to look like a raw bitstream, which starts with a preamble of 0xFF bytes,
+    /// so roms and other files passed by mistake are rejected with an `InvalidData` error. The data
+    /// is padded with 0xFF to a whole number of 512 byte blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{EdFpgaError, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// if let Err(err) = ed.ed_fpga_init_file("core.rbf") {
+    ///     match err.get_ref().and_then(|e| e.downcast_ref::<EdFpgaError>()) {
+    ///         Some(fpga_err) => eprintln!("FPGA rejected the bitstream: {}", fpga_err),
+    ///         None => eprintln!("FPGA init failed: {:?}", err),
+    ///     }
+    /// }
+    /// ```
+    pub fn ed_fpga_init_file(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let mut data = std::fs::read(path)?;
+
+        if data.first() != Some(&0xFF) || data.iter().all(|byte| *byte == 0xFF) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "File is not an RBF bitstream",
+            ));
+        }
+
+        data.resize(data.len().next_multiple_of(ROM_BLOCK_SIZE as usize), 0xFF);
+
+        let size = u32::try_from(data.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "RBF file is too large")
+        })?;
+
+        self.ed_fpga_init(size, &data)
+    }
 
-        if data.len() < CRC_AREA_SIZE {
-            self.ed_rom_fill(base_address, CRC_AREA_SIZE as u32, 0)?;
+    /// Starts a rom file. The rom file must be loaded first using `ed_load_rom`
+    ///
+    /// Optional `file_name` is used for specifying save file on the SD card. It must be a valid
+    /// `EdFileName`, otherwise an `InvalidInput` error carrying an `EdFileNameError` is returned.
+    /// Names taken from host paths can be converted with `EdFileName::sanitized`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    ///
+    /// ed.ed_load_rom(rom_data, None, None, None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_app_start(&mut self, file_name: Option<&str>) -> std::io::Result<()> {
+        let save_file_name = file_name
+            .map(|file_name| {
+                EdFileName::new(file_name)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))
+            })
+            .transpose()?;
+
+        self.app_start(save_file_name.as_ref())
+    }
+
+    /// Starts the loaded rom with the settings of `options`, see `BootOptions`. The save type
+    /// override is written before the start command is sent.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{BootOptions, EdFileName, EdSaveType, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_load_rom(std::fs::read("your_rom.z64").unwrap(), None, None, None)
+    ///     .unwrap();
+    /// ed.ed_app_start_with(&BootOptions {
+    ///     save_file_name: Some(EdFileName::sanitized("your_rom.z64")),
+    ///     save_type: Some(EdSaveType::Eeprom16k),
+    ///     ..Default::default()
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn ed_app_start_with(&mut self, options: &BootOptions) -> std::io::Result<()> {
+        self.session_check_command(true)?;
+        self.capabilities
+            .check_rom(options.save_type, options.rtc_region_type)?;
+
+        if let Some(save_type) = options.save_type {
+            let base_address = options.base_address.unwrap_or(self.memory_map.rom_base());
+
+            let mut block = [0; ROM_BLOCK_SIZE as usize];
+            self.ed_rom_read(base_address, &mut block)?;
+            rom_patch_save_type(&mut block, save_type, options.rtc_region_type);
+            self.ed_rom_write(base_address, &block)?;
         }
 
-        self.ed_rom_write(base_address, &data)
+        self.app_start(options.save_file_name.as_ref())
+    }
+
+    fn app_start(&mut self, save_file_name: Option<&EdFileName>) -> std::io::Result<()> {
+        self.ed_tx(EdCommand::AppStart(save_file_name.is_some()))?;
+
+        if let Some(name) = save_file_name {
+            self.write_all(&name.to_buf())?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a rom file into the specified base address
+    ///
+    /// `rom_file` should contain the rom file as data, either owned (`Vec<u8>`) or borrowed mutably (`&mut [u8]`).
+    /// Byte swapping and header patching are done in place, so a borrowed rom is modified by the call.
+    /// The base address is optional and defaults to the rom base of the memory map, `ROM_BASE_ADDR` unless
+    /// changed with `set_memory_map`.
+    /// `save_type` and `rtc_region_type` are optional and are used to specify the save type and RTC region type respectively.
+    /// Additional checks are done to determine the endianness of the rom file and swap bytes accordingly, and
+    /// to set the save type and RTC region type in the rom file header.
+    ///
+    /// NES, SNES, Game Boy and Master System roms are detected by their headers (see `RomKind`) and loaded
+    /// for the emulators of the Everdrive menu at the emulator base of the memory map, ignoring the base address and save type.
+    /// Roms which are not recognized return an `InvalidData` error.
+    ///
+    /// Returns a `RomLoadReport` describing what was uploaded.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    ///
+    /// let report = ed.ed_load_rom(rom_data, None, None, None).unwrap();
+    /// println!("Loaded {:?} at {:#X}, CRC32 {:08X}", report.kind, report.base_address, report.crc32);
+    ///
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_load_rom(
+        &mut self,
+        mut rom_file: impl AsMut<[u8]>,
+        base_address: Option<u32>,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+    ) -> std::io::Result<RomLoadReport> {
+        // reference https://github.com/krikzz/ED64/blob/master/usb64/usb64/CommandProcessor.cs#L125
+        let rom_file = rom_file.as_mut();
+
+        trace_span!("ed_load_rom", size = rom_file.len());
+
+        let kind = RomKind::detect(rom_file).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Unrecognized rom format")
+        })?;
+
+        trace_event!(debug, kind = ?kind, "rom prepared");
+
+        self.capabilities.check_rom(save_type, rtc_region_type)?;
+
+        let order = match kind {
+            RomKind::N64(order) => order,
+            // Emulator roms are loaded as is, the save type is configured by the emulator
+            _ => {
+                let image = kind.emulator_image(rom_file);
+                let base_address = self.memory_map.base_address(kind);
+                self.check_rom_size(base_address, image.len())?;
+
+                let report = self.rom_load_report(kind, base_address, None, None, &image);
+
+                self.ed_load_rom_force(image, base_address)?;
+                return Ok(report);
+            }
+        };
+
+        byteswap::to_big_endian_in_place(rom_file, order);
+        let base_address = base_address.unwrap_or(self.memory_map.rom_base());
+        let rom_len = rom_file.len();
+
+        // The RTC settings are only written together with a save type
+        let rtc_region_type = save_type.and(rtc_region_type);
+
+        if let Some(st) = save_type {
+            rom_patch_save_type(rom_file, st, rtc_region_type);
+        }
+
+        self.rom_check_tv_standard(rom_file)?;
+
+        // Never trim into the checksummed area, which would be cleared to zeros instead
+        let size = match self.rom_trimming {
+            true => crate::rom::content_size(rom_file)
+                .next_multiple_of(ROM_BLOCK_SIZE as usize)
+                .max(CRC_AREA_SIZE)
+                .min(rom_file.len()),
+            false => rom_file.len(),
+        };
+
+        let rom_file = &rom_file[..size];
+        self.check_rom_size(base_address, size)?;
+
+        let mut report =
+            self.rom_load_report(kind, base_address, save_type, rtc_region_type, rom_file);
+        report.trimmed_bytes = rom_len - size;
+
+        self.ed_load_rom_force(rom_file, base_address)?;
+        Ok(report)
+    }
+
+    fn rom_load_report(
+        &self,
+        kind: RomKind,
+        base_address: u32,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+        image: &[u8],
+    ) -> RomLoadReport {
+        let header_crc = match kind {
+            RomKind::N64(_) if image.len() >= crate::rom::HEADER_SIZE => {
+                Some(crate::rom::header_crc(image))
+            }
+            _ => None,
+        };
+
+        let tv_standard = match kind {
+            RomKind::N64(_) => crate::rom::RomHeader::parse(image)
+                .ok()
+                .and_then(|header| header.tv_standard()),
+            _ => None,
+        };
+
+        let padded_size = match self.rom_padding {
+            true => image.len().next_multiple_of(ROM_BLOCK_SIZE as usize),
+            false => image.len(),
+        };
+
+        RomLoadReport {
+            kind,
+            base_address,
+            save_type,
+            rtc_region_type,
+            size: image.len(),
+            padded_size,
+            trimmed_bytes: 0,
+            header_crc,
+            tv_standard,
+            crc32: crate::rom::crc32(image),
+        }
+    }
+
+    /// Loads a rom file like `ed_load_rom`, but looks up the save type and RTC requirement
+    /// of the game from the built-in game database using the game id in the rom header.
+    ///
+    /// Roms which are not found in the database are loaded without a save type.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    ///
+    /// ed.ed_load_rom_auto(rom_data, None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_load_rom_auto(
+        &mut self,
+        mut rom_file: impl AsMut<[u8]>,
+        base_address: Option<u32>,
+    ) -> std::io::Result<RomLoadReport> {
+        let rom_file = rom_file.as_mut();
+
+        if rom_file.len() < 0x40 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Rom file is too small",
+            ));
+        }
+
+        // Swap to native order first so the header can be read, ed_load_rom will see a native rom
+        let header_word_be =
+            u32::from_be_bytes([rom_file[0], rom_file[1], rom_file[2], rom_file[3]]);
+        rom_swap_to_native(header_word_be, rom_file);
+
+        match crate::rom::lookup_save_type(rom_file) {
+            Some((save_type, rtc)) => {
+                let rtc_region_type = if rtc {
+                    Some(EdRtcRegionType::Rtc)
+                } else {
+                    None
+                };
+
+                self.ed_load_rom(rom_file, base_address, Some(save_type), rtc_region_type)
+            }
+            None => self.ed_load_rom(rom_file, base_address, None, None),
+        }
+    }
+
+    /// Loads a rom file from a reader into the specified base address, in chunks of `ROM_STREAM_CHUNK_SIZE` bytes.
+    ///
+    /// Works like `ed_load_rom`, but the rom is never held in memory as a whole, which keeps memory usage flat
+    /// when loading large roms from files or network streams. The end of the rom is padded with 0xFF to a
+    /// whole number of 512 byte blocks. SNES roms with a copier header are not recognized while streaming,
+    /// since the header can only be detected from the total rom size.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs::File;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_file = File::open("your_rom.z64").unwrap();
+    ///
+    /// ed.ed_load_rom_streaming(rom_file, None, None, None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_load_rom_streaming<R: std::io::Read>(
+        &mut self,
+        mut reader: R,
+        base_address: Option<u32>,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+    ) -> std::io::Result<()> {
+        self.with_progress(ProgressOperation::RomUpload, None, |ed| {
+            let (addr, header_word_be, chunk, chunk_len) =
+                ed.rom_stream_start(&mut reader, base_address, save_type, rtc_region_type)?;
+
+            ed.rom_stream_write(addr, chunk, chunk_len, |mut chunk| {
+                let chunk_len = crate::read_chunk(&mut reader, &mut chunk)?;
+                rom_swap_to_native(header_word_be, &mut chunk[..chunk_len]);
+                Ok((chunk, chunk_len))
+            })
+        })
+    }
+
+    /// Loads a rom file from a reader like `ed_load_rom_streaming`, but reads and byte-swaps the next
+    /// chunk on a worker thread while the previous chunk is written to the serial port. Two chunk
+    /// buffers are used, so memory usage stays flat.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs::File;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_file = File::open("your_rom.v64").unwrap();
+    ///
+    /// ed.ed_load_rom_pipelined(rom_file, None, None, None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.v64")).unwrap();
+    /// ```
+    pub fn ed_load_rom_pipelined<R: std::io::Read + Send>(
+        &mut self,
+        mut reader: R,
+        base_address: Option<u32>,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+    ) -> std::io::Result<()> {
+        self.with_progress(ProgressOperation::RomUpload, None, |ed| {
+            let (addr, header_word_be, chunk, chunk_len) =
+                ed.rom_stream_start(&mut reader, base_address, save_type, rtc_region_type)?;
+
+            std::thread::scope(|scope| {
+                // Swapped chunks go to the writer, written chunks go back to the worker for reuse
+                let (full_tx, full_rx) = std::sync::mpsc::sync_channel(1);
+                let (empty_tx, empty_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(1);
+
+                scope.spawn(move || {
+                    // Ends after the last chunk, or when the writer stops early and drops its channels
+                    while let Ok(mut chunk) = empty_rx.recv() {
+                        let result = crate::read_chunk(&mut reader, &mut chunk);
+
+                        let last = match &result {
+                            Ok(chunk_len) => *chunk_len < chunk.len(),
+                            Err(_) => true,
+                        };
+
+                        let next = result.map(|chunk_len| {
+                            rom_swap_to_native(header_word_be, &mut chunk[..chunk_len]);
+                            (chunk, chunk_len)
+                        });
+
+                        if full_tx.send(next).is_err() || last {
+                            break;
+                        }
+                    }
+                });
+
+                // Start reading the second chunk while the first one is written
+                if chunk_len == chunk.len() {
+                    let _ = empty_tx.send(vec![0; ROM_STREAM_CHUNK_SIZE]);
+                }
+
+                ed.rom_stream_write(addr, chunk, chunk_len, |chunk| {
+                    let next = full_rx.recv().map_err(|_| {
+                        std::io::Error::other("Rom reader thread stopped unexpectedly")
+                    })?;
+
+                    // The worker has already finished if this was the last chunk
+                    let _ = empty_tx.send(chunk);
+                    next
+                })
+            })
+        })
+    }
+
+    /// Reads and prepares the first chunk of a streamed rom and clears the checksummed area.
+    /// Returns the load address, the header word and the first chunk with its length.
+    fn rom_stream_start<R: std::io::Read>(
+        &mut self,
+        reader: &mut R,
+        base_address: Option<u32>,
+        save_type: Option<EdSaveType>,
+        rtc_region_type: Option<EdRtcRegionType>,
+    ) -> std::io::Result<(u32, u32, Vec<u8>, usize)> {
+        self.capabilities.check_rom(save_type, rtc_region_type)?;
+
+        let mut chunk = vec![0; ROM_STREAM_CHUNK_SIZE];
+        let chunk_len = crate::read_chunk(reader, &mut chunk)?;
+
+        if chunk_len < 0x40 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Rom file is too small",
+            ));
+        }
+
+        let kind = RomKind::detect(&chunk[..chunk_len]).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Unrecognized rom format")
+        })?;
+
+        let header_word_be = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        rom_swap_to_native(header_word_be, &mut chunk[..chunk_len]);
+
+        let addr = self.rom_stream_address(kind, base_address);
+
+        if let Some(st) = save_type
+            && !kind.is_emulated()
+        {
+            rom_patch_save_type(&mut chunk, st, rtc_region_type);
+        }
+
+        if !kind.is_emulated() {
+            self.rom_check_tv_standard(&mut chunk[..chunk_len])?;
+        }
+
+        // The total size is not known up front, so always clear the CRC area
+        self.ed_rom_fill(addr, CRC_AREA_SIZE as u32, 0)?;
+
+        Ok((addr, header_word_be, chunk, chunk_len))
+    }
+
+    /// Address a streamed rom of `kind` is written to, `base_address` only applies to N64 roms
+    fn rom_stream_address(&self, kind: RomKind, base_address: Option<u32>) -> u32 {
+        match kind {
+            RomKind::N64(_) => base_address.unwrap_or(self.memory_map.rom_base()),
+            _ => self.memory_map.base_address(kind),
+        }
+    }
+
+    /// Writes streamed rom chunks starting at `addr`. `next` receives the written chunk buffer and
+    /// returns the next swapped chunk with its length, a short chunk ends the rom.
+    fn rom_stream_write(
+        &mut self,
+        mut addr: u32,
+        mut chunk: Vec<u8>,
+        mut chunk_len: usize,
+        mut next: impl FnMut(Vec<u8>) -> std::io::Result<(Vec<u8>, usize)>,
+    ) -> std::io::Result<()> {
+        loop {
+            // Only the last chunk can be partial, pad it to a whole block
+            let write_len = chunk_len.next_multiple_of(512);
+
+            if write_len != chunk_len && !self.rom_padding {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Size must be a multiple of 512",
+                ));
+            }
+
+            chunk[chunk_len..write_len].fill(ROM_PADDING_BYTE);
+
+            self.ed_rom_write(addr, &chunk[..write_len])?;
+            addr += write_len as u32;
+
+            if chunk_len < chunk.len() {
+                break;
+            }
+
+            (chunk, chunk_len) = next(chunk)?;
+
+            if chunk_len == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the rom file at `path` by memory-mapping it and streaming it like `ed_load_rom_pipelined`,
+    /// so the file is never copied to the heap as a whole. Only two `ROM_STREAM_CHUNK_SIZE` buffers
+    /// are used for byte-swapping, pages of the file are read by the OS as they are uploaded.
+    ///
+    /// The file must not be modified while it is loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{EdSaveType, Everdrive, RomLoadOptions};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_load_rom_path(
+    ///     "your_rom.z64",
+    ///     &RomLoadOptions {
+    ///         save_type: Some(EdSaveType::Eeprom4k),
+    ///         ..Default::default()
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn ed_load_rom_path(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        options: &RomLoadOptions,
+    ) -> std::io::Result<()> {
+        let file = std::fs::File::open(path)?;
+
+        let len = file.metadata()?.len();
+
+        // Mapping an empty file fails on some platforms, report it like any other short rom
+        if len < 0x40 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Rom file is too small",
+            ));
+        }
+
+        // SAFETY: the map is only read while uploading, the caller must not modify the file meanwhile
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        // Detect the kind from the same data the upload does, emulator roms ignore the base address
+        let kind =
+            RomKind::detect(&map[..map.len().min(ROM_STREAM_CHUNK_SIZE)]).ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "Unrecognized rom format")
+            })?;
+
+        self.check_rom_size(
+            self.rom_stream_address(kind, options.base_address),
+            map.len(),
+        )?;
+
+        self.with_progress(ProgressOperation::RomUpload, Some(map.len() as u64), |ed| {
+            ed.ed_load_rom_pipelined(
+                &map[..],
+                options.base_address,
+                options.save_type,
+                options.rtc_region_type,
+            )
+        })
+    }
+
+    /// Enables or disables padding of roms whose size is not a multiple of 512 bytes. When enabled,
+    /// which is the default, `ed_load_rom` and the other rom loading functions pad the last block of
+    /// trimmed roms with 0xFF. When disabled, such roms return an `InvalidInput` error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.set_rom_padding(false);
+    /// ```
+    pub fn set_rom_padding(&mut self, pad: bool) {
+        self.rom_padding = pad;
+    }
+
+    /// Enables or disables trimming of overdumps. When enabled, `ed_load_rom` and the functions built
+    /// on it skip the trailing 0xFF or 0x00 padding of N64 roms (see `rom::content_size`), which
+    /// shortens uploads of overdumped roms. Streamed loads are not trimmed. Defaults to disabled.
+    ///
+    /// The skipped part of the cartridge memory keeps its previous contents instead of the padding,
+    /// which only matters for programs reading beyond the end of their data.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.set_rom_trimming(true);
+    ///
+    /// let report = ed.ed_load_rom(std::fs::read("overdump.z64").unwrap(), None, None, None).unwrap();
+    /// println!("Skipped {} bytes of padding", report.trimmed_bytes);
+    /// ```
+    pub fn set_rom_trimming(&mut self, trim: bool) {
+        self.rom_trimming = trim;
+    }
+
+    /// Declares the video standard of the console roms are loaded for. N64 roms released for a
+    /// region with a different standard log a warning when loaded, or get their region code changed
+    /// to the default region of the console if `patch` is set (see `rom::patch_tv_standard`).
+    /// `RomLoadReport::tv_standard` reports the standard of the uploaded rom.
+    ///
+    /// No console standard is declared by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::rom::TvStandard;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.set_console_tv_standard(Some(TvStandard::Pal), true);
+    ///
+    /// let report = ed.ed_load_rom(std::fs::read("your_rom.z64").unwrap(), None, None, None).unwrap();
+    /// assert_ne!(report.tv_standard, Some(TvStandard::Ntsc));
+    /// ```
+    pub fn set_console_tv_standard(&mut self, standard: Option<TvStandard>, patch: bool) {
+        self.console_tv_standard = standard;
+        self.tv_standard_patching = patch;
+    }
+
+    /// Warns about or patches a N64 rom in big-endian byte order which does not match the declared
+    /// console video standard
+    fn rom_check_tv_standard(&self, rom: &mut [u8]) -> std::io::Result<()> {
+        let Some(console) = self.console_tv_standard else {
+            return Ok(());
+        };
+
+        if crate::rom::tv_standard_mismatch(rom, console).is_none() {
+            return Ok(());
+        }
+
+        if self.tv_standard_patching {
+            trace_event!(debug, console = ?console, "patching rom region");
+            crate::rom::patch_tv_standard(rom, console)?;
+        } else {
+            trace_event!(warn, console = ?console, "rom region does not match the console");
+        }
+
+        Ok(())
+    }
+
+    /// Loads a rom file into the specified base address. But does not do checks for
+    /// endianness or base_address. The rom is padded to a whole number of 512 byte blocks
+    /// unless disabled with `set_rom_padding`.
+    pub fn ed_load_rom_force(
+        &mut self,
+        data: impl AsRef<[u8]>,
+        base_address: u32,
+    ) -> std::io::Result<()> {
+        let data = data.as_ref();
+
+        let padded_rom;
+        let data = if self.rom_padding && !data.len().is_multiple_of(ROM_BLOCK_SIZE as usize) {
+            padded_rom = pad_rom(data);
+            &padded_rom[..]
+        } else {
+            data
+        };
+
+        self.with_progress(
+            ProgressOperation::RomUpload,
+            Some(data.len() as u64),
+            |ed| {
+                if ed.upload_cache().is_some() && data.len().is_multiple_of(512) {
+                    // Small roms are padded over the CRC area instead of filling it, which would
+                    // overwrite the cached blocks
+                    let mut padded;
+                    let data = if data.len() < CRC_AREA_SIZE {
+                        padded = data.to_vec();
+                        padded.resize(CRC_AREA_SIZE, 0);
+                        &padded[..]
+                    } else {
+                        data
+                    };
+
+                    ed.ed_rom_write_cached(base_address, data)?;
+                    return Ok(());
+                }
+
+                if data.len() < CRC_AREA_SIZE {
+                    ed.ed_rom_fill(base_address, CRC_AREA_SIZE as u32, 0)?;
+                }
+
+                ed.ed_rom_write(base_address, data)
+            },
+        )
     }
 
     /// Transmits an EdCommand to the Everdrive device
     /// and returns an error if sending the command fails.
+    ///
+    /// Bytes left in the receive buffer, such as a response a previous caller never read, are discarded
+    /// first so they can't be mistaken for the response to this command.
     pub fn ed_tx(&mut self, cmd: EdCommand) -> std::io::Result<()> {
-        self.write_all(&cmd.to_bytes()?)
+        self.session_check_command(matches!(cmd, EdCommand::AppStart(_)))?;
+        self.memory_map_check(&cmd)?;
+
+        let buf = cmd.to_bytes()?;
+
+        let stale = self.bytes_to_read()?;
+        if stale > 0 {
+            trace_event!(debug, stale, "discarding stale bytes before EDOS command");
+            self.port.clear_input()?;
+        }
+
+        trace_event!(
+            debug,
+            command = %(buf[3] as char),
+            data = %crate::trace::HexDump(&buf[4..]),
+            "EDOS command sent"
+        );
+
+        self.write_all(&buf)?;
+        self.emit_event(|events| events.on_command_sent(cmd));
+
+        match cmd {
+            EdCommand::RomWrite(..) | EdCommand::RomFill(..) => {
+                self.session = SessionState::RomLoaded;
+            }
+            EdCommand::AppStart(_) => self.session = SessionState::Running,
+            _ => {}
+        }
+
+        Ok(())
     }
 
     /// Receives a response from the Everdrive device
     /// and returns an error if reading from the device fails
     /// or if the response is invalid.
     pub fn ed_rx(&mut self, resp: u8) -> std::io::Result<()> {
-        let mut recv_buf = vec![0; 16];
+        self.ed_rx_payload(resp).map(|_| ())
+    }
 
-        match self.read_exact(&mut recv_buf) {
-            Ok(_) => {
-                if recv_buf[0..4] == [b'c', b'm', b'd', resp] {
-                    Ok(())
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Invalid response from Everdrive device",
-                    ))
-                }
-            }
-            Err(err) => return Err(err),
+    /// Receives a full 16 byte response frame like `ed_rx` and returns the 12 payload bytes
+    /// following the `cmd` prefix and response byte.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{EdCommand, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_tx(EdCommand::Test).unwrap();
+    /// let payload = ed.ed_rx_payload(b'r').unwrap();
+    ///
+    /// println!("Status payload: {:02X?}", payload);
+    /// ```
+    pub fn ed_rx_payload(&mut self, resp: u8) -> std::io::Result<[u8; 12]> {
+        let recv_buf = self.ed_rx_raw()?;
+
+        trace_event!(
+            debug,
+            response = %(recv_buf[3] as char),
+            expected = %(resp as char),
+            "EDOS response received"
+        );
+
+        if recv_buf[0..4] == [b'c', b'm', b'd', resp] {
+            Ok(recv_buf[4..].try_into().unwrap())
+        } else {
+            Err(self.emit_error(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid response from Everdrive device, expected cmd{} got {:02X?}",
+                    resp as char,
+                    &recv_buf[0..4]
+                ),
+            )))
         }
     }
+
+    /// Receives a raw 16 byte response frame from the Everdrive device without validating it.
+    ///
+    /// Together with `EdCommand::Custom` this can be used to experiment with commands not supported by the library.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{EdCommand, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_tx(EdCommand::Custom(b't', 0, 0, 0)).unwrap();
+    /// let response = ed.ed_rx_raw().unwrap();
+    ///
+    /// println!("Response: {:02X?}", response);
+    /// ```
+    pub fn ed_rx_raw(&mut self) -> std::io::Result<[u8; 16]> {
+        let mut recv_buf = [0; 16];
+        self.read_exact(&mut recv_buf)?;
+        Ok(recv_buf)
+    }
 }
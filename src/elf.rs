@@ -0,0 +1,374 @@
+//! Loading of ELF executables produced by homebrew toolchains such as libdragon.
+//!
+//! Builds usually produce both an ELF and a rom image. The ELF is used to find the rom next to it,
+//! or to build one from its loadable segments, and its symbol table is kept for debug output.
+
+#[cfg(feature = "edos")]
+use crate::Everdrive;
+use crate::symbols::Symbol;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_BIG_ENDIAN: u8 = 2;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+/// Offset of the program code in a rom image, after the header and IPL3 bootcode
+const ROM_CODE_OFFSET: usize = 0x1000;
+
+/// Rom image extensions searched for next to an ELF file
+const ROM_EXTENSIONS: &[&str] = &["z64", "v64", "n64"];
+
+/// Loadable segment of an ELF file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElfSegment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Parsed big-endian MIPS ELF executable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elf {
+    pub entry_point: u32,
+    pub segments: Vec<ElfSegment>,
+    /// Named symbols sorted by address
+    pub symbols: Vec<Symbol>,
+}
+
+fn invalid_elf(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads ELF32 and ELF64 fields, which only differ in the size of addresses and offsets
+struct ElfReader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+}
+
+impl ElfReader<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> std::io::Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| invalid_elf("ELF file is truncated"))
+    }
+
+    fn u16(&self, offset: usize) -> std::io::Result<u16> {
+        Ok(u16::from_be_bytes(
+            self.bytes(offset, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    fn u32(&self, offset: usize) -> std::io::Result<u32> {
+        Ok(u32::from_be_bytes(
+            self.bytes(offset, 4)?.try_into().unwrap(),
+        ))
+    }
+
+    /// Reads an address or offset field. 64-bit values are truncated, since the N64 uses
+    /// sign-extended 32-bit addresses.
+    fn addr(&self, offset: usize) -> std::io::Result<u32> {
+        if self.is_64 {
+            Ok(self.u32(offset + 4)?)
+        } else {
+            self.u32(offset)
+        }
+    }
+
+    /// Size of an address or offset field
+    fn addr_size(&self) -> usize {
+        if self.is_64 { 8 } else { 4 }
+    }
+}
+
+impl Elf {
+    /// Parses a big-endian ELF32 or ELF64 executable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::elf::Elf;
+    /// use std::fs;
+    ///
+    /// let elf = Elf::parse(&fs::read("build/your_rom.elf").unwrap()).unwrap();
+    ///
+    /// println!("Entry point {:08X}, {} symbols", elf.entry_point, elf.symbols.len());
+    /// ```
+    pub fn parse(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < 0x34 || &data[0..4] != ELF_MAGIC {
+            return Err(invalid_elf("Not an ELF file"));
+        }
+
+        let is_64 = match data[4] {
+            ELF_CLASS_32 => false,
+            ELF_CLASS_64 => true,
+            _ => return Err(invalid_elf("Unsupported ELF class")),
+        };
+
+        if data[5] != ELF_DATA_BIG_ENDIAN {
+            return Err(invalid_elf("Only big-endian ELF files are supported"));
+        }
+
+        let elf = ElfReader { data, is_64 };
+        let a = elf.addr_size();
+
+        // Offsets of the header fields after the 0x18 byte ident, type, machine and version
+        let entry_point = elf.addr(0x18)?;
+        let ph_offset = elf.addr(0x18 + a)? as usize;
+        let sh_offset = elf.addr(0x18 + 2 * a)? as usize;
+        let tail = 0x18 + 3 * a + 4;
+        let ph_size = elf.u16(tail + 2)? as usize;
+        let ph_count = elf.u16(tail + 4)? as usize;
+        let sh_size = elf.u16(tail + 6)? as usize;
+        let sh_count = elf.u16(tail + 8)? as usize;
+
+        let mut segments = Vec::new();
+
+        for i in 0..ph_count {
+            let ph = ph_offset + i * ph_size;
+
+            if elf.u32(ph)? != PT_LOAD {
+                continue;
+            }
+
+            // Segments are placed at their physical address (p_paddr), like the linker script
+            // intends for the load image
+            let (offset, address, file_size) = if is_64 {
+                (
+                    elf.addr(ph + 0x08)?,
+                    elf.addr(ph + 0x18)?,
+                    elf.addr(ph + 0x20)?,
+                )
+            } else {
+                (
+                    elf.u32(ph + 0x04)?,
+                    elf.u32(ph + 0x0C)?,
+                    elf.u32(ph + 0x10)?,
+                )
+            };
+
+            // Segments without file data (bss) are cleared by the program itself
+            if file_size > 0 {
+                segments.push(ElfSegment {
+                    address,
+                    data: elf.bytes(offset as usize, file_size as usize)?.to_vec(),
+                });
+            }
+        }
+
+        segments.sort_by_key(|segment| segment.address);
+
+        let section = |i: usize| -> std::io::Result<(u32, usize, usize, usize, usize)> {
+            let sh = sh_offset + i * sh_size;
+            let kind = elf.u32(sh + 0x04)?;
+            let offset = elf.addr(sh + 0x08 + 2 * a)? as usize;
+            let size = elf.addr(sh + 0x08 + 3 * a)? as usize;
+            let link = elf.u32(sh + 0x08 + 4 * a)? as usize;
+            let entry_size = elf.addr(sh + 0x10 + 5 * a)? as usize;
+            Ok((kind, offset, size, link, entry_size))
+        };
+
+        let mut symbols = Vec::new();
+
+        for i in 0..sh_count {
+            let (kind, offset, size, link, entry_size) = section(i)?;
+
+            if kind != SHT_SYMTAB || entry_size == 0 {
+                continue;
+            }
+
+            let (_, str_offset, str_size, _, _) = section(link)?;
+            let strings = elf.bytes(str_offset, str_size)?;
+
+            for sym in (offset..offset + size).step_by(entry_size) {
+                let (name, address, size, info) = if is_64 {
+                    (
+                        elf.u32(sym)?,
+                        elf.addr(sym + 0x08)?,
+                        elf.addr(sym + 0x10)?,
+                        elf.bytes(sym + 4, 1)?[0],
+                    )
+                } else {
+                    (
+                        elf.u32(sym)?,
+                        elf.u32(sym + 0x04)?,
+                        elf.u32(sym + 0x08)?,
+                        elf.bytes(sym + 0x0C, 1)?[0],
+                    )
+                };
+
+                let name = strings
+                    .get(name as usize..)
+                    .and_then(|name| name.split(|b| *b == 0).next())
+                    .unwrap_or_default();
+
+                if name.is_empty() {
+                    continue;
+                }
+
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    address,
+                    size,
+                    is_function: info & 0x0F == STT_FUNC,
+                });
+            }
+        }
+
+        symbols.sort_by_key(|symbol| symbol.address);
+
+        Ok(Self {
+            entry_point,
+            segments,
+            symbols,
+        })
+    }
+
+    /// Builds a rom image from the loadable segments of the ELF. `boot` provides the rom header and
+    /// IPL3 bootcode, usually the first 0x1000 bytes of another rom built with the same toolchain.
+    ///
+    /// Segments are placed after the bootcode in address order, the header entry point is set to
+    /// the lowest segment address and the header checksums are updated for the detected CIC.
+    ///
+    /// Returns an `InvalidData` error if the image would be larger than `max_size` bytes, usually
+    /// the size of the `Rom` region of the memory map, such as for a stray segment at a high address.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::elf::Elf;
+    /// use libeverdrive::{Everdrive, RegionKind};
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let elf = Elf::parse(&fs::read("build/your_rom.elf").unwrap()).unwrap();
+    /// let boot = fs::read("other_rom.z64").unwrap();
+    ///
+    /// let max_size = ed.memory_map().region(RegionKind::Rom).unwrap().size as usize;
+    /// let rom = elf.to_rom(&boot, max_size).unwrap();
+    ///
+    /// ed.ed_load_rom(rom, None, None, None).unwrap();
+    /// ```
+    pub fn to_rom(&self, boot: &[u8], max_size: usize) -> std::io::Result<Vec<u8>> {
+        if boot.len() < ROM_CODE_OFFSET {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Boot data must contain the rom header and IPL3 bootcode",
+            ));
+        }
+
+        let load_address = self
+            .segments
+            .first()
+            .map(|segment| segment.address)
+            .ok_or_else(|| invalid_elf("ELF file has no loadable segments"))?;
+
+        let image_end = self
+            .segments
+            .iter()
+            .map(|segment| {
+                ROM_CODE_OFFSET as u64
+                    + (segment.address - load_address) as u64
+                    + segment.data.len() as u64
+            })
+            .max()
+            .unwrap_or_default();
+
+        if image_end > max_size as u64 {
+            return Err(invalid_elf(&format!(
+                "Rom image of {:#X} bytes is larger than the maximum of {:#X} bytes",
+                image_end, max_size
+            )));
+        }
+
+        let mut rom = boot[..ROM_CODE_OFFSET].to_vec();
+        let header_word_be = u32::from_be_bytes([rom[0], rom[1], rom[2], rom[3]]);
+        crate::rom::rom_swap_to_native(header_word_be, &mut rom);
+
+        for segment in &self.segments {
+            let offset = ROM_CODE_OFFSET + (segment.address - load_address) as usize;
+            let end = offset + segment.data.len();
+
+            if rom.len() < end {
+                rom.resize(end, 0);
+            }
+
+            rom[offset..end].copy_from_slice(&segment.data);
+        }
+
+        // The bootcode checksums the first megabyte of code, pad so it is always present
+        let size = rom
+            .len()
+            .max(crate::rom::CRC_AREA_SIZE)
+            .next_multiple_of(512);
+        rom.resize(size, 0);
+
+        rom[0x08..0x0C].copy_from_slice(&load_address.to_be_bytes());
+
+        if let Some(cic) = crate::rom::detect_cic(&rom) {
+            crate::rom::fix_crc(&mut rom, cic);
+        }
+
+        Ok(rom)
+    }
+}
+
+/// Looks for the rom image built alongside the ELF at `elf_path`, with the same file name and a rom
+/// extension, in the directory of the ELF or its parent directory (libdragon builds the ELF into `build/`).
+pub fn find_rom(elf_path: impl AsRef<std::path::Path>) -> Option<std::path::PathBuf> {
+    let elf_path = elf_path.as_ref();
+    let stem = elf_path.file_stem()?;
+    let dir = elf_path.parent().unwrap_or(std::path::Path::new(""));
+
+    [Some(dir), dir.parent()]
+        .into_iter()
+        .flatten()
+        .flat_map(|dir| {
+            ROM_EXTENSIONS
+                .iter()
+                .map(move |ext| dir.join(stem).with_extension(ext))
+        })
+        .find(|path| path.is_file())
+}
+
+#[cfg(feature = "edos")]
+impl Everdrive {
+    /// Loads the rom built alongside the ELF at `elf_path` and returns the parsed ELF, whose symbol table can
+    /// be used to resolve addresses in debug output. The rom is found with `elf::find_rom` and loaded with
+    /// `ed_load_rom_auto`. Use `Elf::to_rom` to build a rom when the toolchain does not produce one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let elf = ed.ed_load_elf("build/your_rom.elf", None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_load_elf(
+        &mut self,
+        elf_path: impl AsRef<std::path::Path>,
+        base_address: Option<u32>,
+    ) -> std::io::Result<Elf> {
+        let elf_path = elf_path.as_ref();
+        let elf = Elf::parse(&std::fs::read(elf_path)?)?;
+
+        let rom_path = find_rom(elf_path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No rom image found for {}", elf_path.display()),
+            )
+        })?;
+
+        self.ed_load_rom_auto(std::fs::read(rom_path)?, base_address)?;
+
+        Ok(elf)
+    }
+}
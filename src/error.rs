@@ -0,0 +1,210 @@
+/// Errors returned by device discovery
+#[derive(Debug)]
+pub enum EverdriveError {
+    /// Listing the serial ports of the system failed
+    #[cfg(not(target_arch = "wasm32"))]
+    Enumeration(serialport::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for EverdriveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            EverdriveError::Enumeration(err) => {
+                write!(f, "Failed to enumerate serial ports: {}", err)
+            }
+            EverdriveError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EverdriveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            EverdriveError::Enumeration(err) => Some(err),
+            EverdriveError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for EverdriveError {
+    fn from(err: std::io::Error) -> Self {
+        EverdriveError::Io(err)
+    }
+}
+
+impl From<EverdriveError> for std::io::Error {
+    fn from(err: EverdriveError) -> Self {
+        match err {
+            EverdriveError::Io(err) => err,
+            #[cfg(not(target_arch = "wasm32"))]
+            err => std::io::Error::other(err),
+        }
+    }
+}
+
+/// Reason opening a serial port failed, see `PortOpenError`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortOpenFailure {
+    /// The user has no access to the device node. `group` is the group owning it, such as
+    /// `dialout` or `uucp`, which the user can be added to.
+    PermissionDenied {
+        group: Option<String>,
+    },
+    /// Another process has the port open. `process` names it as `name (pid)` where it can be
+    /// determined, which is only on Linux.
+    Busy {
+        process: Option<String>,
+    },
+    /// The port does not exist, the device is unplugged or the name is wrong
+    NotFound,
+    Other,
+}
+
+/// Error payload returned by `Everdrive::new` when the serial port can't be opened, available
+/// through `std::io::Error::get_ref`. The kind of the `std::io::Error` is `PermissionDenied`,
+/// `ResourceBusy`, `NotFound` or `Other` according to `failure`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Everdrive, PortOpenError, PortOpenFailure};
+///
+/// match Everdrive::new("/dev/ttyUSB0") {
+///     Ok(_) => println!("Connected"),
+///     Err(err) => match err.get_ref().and_then(|e| e.downcast_ref::<PortOpenError>()) {
+///         Some(PortOpenError {
+///             failure: PortOpenFailure::Busy { process: Some(process) },
+///             ..
+///         }) => eprintln!("Close {} first", process),
+///         _ => eprintln!("{}", err),
+///     },
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortOpenError {
+    pub port_name: String,
+    pub failure: PortOpenFailure,
+    /// Error message of the operating system
+    pub description: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PortOpenError {
+    /// Classifies the error of opening `port_name` and wraps it into a `std::io::Error`
+    pub(crate) fn from_serial(port_name: &str, err: serialport::Error) -> std::io::Error {
+        let failure = match err.kind {
+            serialport::ErrorKind::Io(std::io::ErrorKind::PermissionDenied) => {
+                PortOpenFailure::PermissionDenied {
+                    group: device_group(port_name),
+                }
+            }
+            // Windows reports ports held by another process as access denied
+            serialport::ErrorKind::NoDevice if err.description.contains("denied") => {
+                PortOpenFailure::Busy { process: None }
+            }
+            serialport::ErrorKind::NoDevice
+            | serialport::ErrorKind::Io(std::io::ErrorKind::NotFound) => PortOpenFailure::NotFound,
+            _ if err.description.contains("busy") => PortOpenFailure::Busy {
+                process: port_owner(port_name),
+            },
+            _ => PortOpenFailure::Other,
+        };
+
+        let kind = match failure {
+            PortOpenFailure::PermissionDenied { .. } => std::io::ErrorKind::PermissionDenied,
+            PortOpenFailure::Busy { .. } => std::io::ErrorKind::ResourceBusy,
+            PortOpenFailure::NotFound => std::io::ErrorKind::NotFound,
+            PortOpenFailure::Other => std::io::ErrorKind::Other,
+        };
+
+        std::io::Error::new(
+            kind,
+            PortOpenError {
+                port_name: port_name.to_string(),
+                failure,
+                description: err.description,
+            },
+        )
+    }
+}
+
+impl std::fmt::Display for PortOpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to open {}: {}", self.port_name, self.description)?;
+
+        match &self.failure {
+            PortOpenFailure::PermissionDenied { group: Some(group) } => write!(
+                f,
+                ". Add your user to the {} group and log in again, for example with `sudo usermod -aG {} $USER`",
+                group, group
+            ),
+            PortOpenFailure::PermissionDenied { group: None } => write!(
+                f,
+                ". Add your user to the group owning the port, usually dialout or uucp, or install a udev rule granting access"
+            ),
+            PortOpenFailure::Busy {
+                process: Some(process),
+            } => write!(f, ". The port is in use by {}", process),
+            PortOpenFailure::Busy { process: None } => write!(
+                f,
+                ". The port is in use by another program, such as a terminal or another loader"
+            ),
+            PortOpenFailure::NotFound => write!(
+                f,
+                ". Check that the Everdrive is connected and the port name is correct, `Everdrive::find_usb_devices` lists connected devices"
+            ),
+            PortOpenFailure::Other => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for PortOpenError {}
+
+/// Returns the name of the group owning the device node at `path`
+#[cfg(all(unix, not(target_arch = "wasm32")))]
+fn device_group(path: &str) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let gid = std::fs::metadata(path).ok()?.gid().to_string();
+    let groups = std::fs::read_to_string("/etc/group").ok()?;
+
+    // Entries are `name:password:gid:members`
+    groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        (fields.nth(1)? == gid).then(|| name.to_string())
+    })
+}
+
+#[cfg(not(any(unix, target_arch = "wasm32")))]
+fn device_group(_path: &str) -> Option<String> {
+    None
+}
+
+/// Returns `name (pid)` of a process which has the device node at `path` open
+#[cfg(target_os = "linux")]
+fn port_owner(path: &str) -> Option<String> {
+    let device = std::fs::canonicalize(path).ok()?;
+
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .find_map(|process| {
+            let pid = process.file_name().to_str()?.parse::<u32>().ok()?;
+
+            // Processes of other users can't be inspected without privileges and are skipped
+            let mut fds = std::fs::read_dir(process.path().join("fd")).ok()?.flatten();
+            fds.find(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == device))?;
+
+            let name = std::fs::read_to_string(process.path().join("comm")).ok()?;
+            Some(format!("{} ({})", name.trim_end(), pid))
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_arch = "wasm32")))]
+fn port_owner(_path: &str) -> Option<String> {
+    None
+}
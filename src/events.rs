@@ -0,0 +1,93 @@
+#[cfg(feature = "edos")]
+use crate::EdCommand;
+use crate::Everdrive;
+use crate::Progress;
+#[cfg(feature = "unf")]
+use crate::UnfRecvPacket;
+
+/// Hooks called by `Everdrive` as operations go over the wire, registered once with `set_events`
+/// so a GUI can reflect progress and errors without wrapping every call. All methods default to
+/// doing nothing.
+///
+/// Hooks run on the thread performing the operation, in the middle of it, so they should return
+/// quickly, such as by forwarding the event over a channel.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{EdCommand, Everdrive, EverdriveEvents};
+///
+/// #[derive(Debug)]
+/// struct Progress {
+///     written: usize,
+/// }
+///
+/// impl EverdriveEvents for Progress {
+///     fn on_chunk_written(&mut self, _addr: u32, len: usize) {
+///         self.written += len;
+///         println!("{} bytes written", self.written);
+///     }
+///
+///     fn on_error(&mut self, err: &std::io::Error) {
+///         eprintln!("error: {}", err);
+///     }
+/// }
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// ed.set_events(Some(Box::new(Progress { written: 0 })));
+/// ed.ed_load_rom(std::fs::read("your_rom.z64").unwrap(), None, None, None).unwrap();
+/// ```
+pub trait EverdriveEvents: Send + std::fmt::Debug {
+    /// Called after an Everdrive OS command frame has been written
+    #[cfg(feature = "edos")]
+    fn on_command_sent(&mut self, _cmd: EdCommand) {}
+
+    /// Called after a chunk of rom data has been written to `addr`
+    #[cfg(feature = "edos")]
+    fn on_chunk_written(&mut self, _addr: u32, _len: usize) {}
+
+    /// Called for every UNF packet received, including packets handled internally such as
+    /// heartbeats
+    #[cfg(feature = "unf")]
+    fn on_packet_received(&mut self, _packet: &UnfRecvPacket) {}
+
+    /// Called when a transfer fails or the device sends invalid data, including timeouts of
+    /// Everdrive OS commands and of UNF packets which stop arriving halfway. Timeouts while waiting
+    /// for the next UNF packet are not reported here, they are the normal outcome of polling and
+    /// are returned to the caller.
+    fn on_error(&mut self, _err: &std::io::Error) {}
+
+    /// Called when a long running operation starts, after every transferred chunk and when it
+    /// finishes, see `Progress`
+    fn on_progress(&mut self, _progress: &Progress) {}
+
+    /// Called before an operation is retried after a transient error, see `set_retry_policy`.
+    /// `attempt` is the number of the upcoming attempt, starting at 2.
+    #[cfg(feature = "edos")]
+    fn on_retry(&mut self, _attempt: u32, _err: &std::io::Error) {}
+}
+
+impl Everdrive {
+    /// Registers the hooks called for operations on the wire, or removes them with `None`.
+    /// See `EverdriveEvents`.
+    pub fn set_events(&mut self, events: Option<Box<dyn EverdriveEvents>>) {
+        self.events = events;
+    }
+
+    /// Calls `hook` on the registered event hooks, if any
+    pub(crate) fn emit_event(&mut self, hook: impl FnOnce(&mut dyn EverdriveEvents)) {
+        if let Some(events) = &mut self.events {
+            hook(events.as_mut());
+        }
+    }
+
+    /// Reports `err` to `EverdriveEvents::on_error` unless it is an interruption, and returns it
+    pub(crate) fn emit_error(&mut self, err: std::io::Error) -> std::io::Error {
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            self.emit_event(|events| events.on_error(&err));
+        }
+
+        err
+    }
+}
@@ -0,0 +1,263 @@
+//! C API for using the crate from C and C++ tools.
+//!
+//! Build a C library with `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`). The
+//! declarations are in `include/libeverdrive.h`, which is regenerated from this module with
+//! `cbindgen --config cbindgen.toml --output include/libeverdrive.h` after changing the API. Devices are used
+//! through an opaque `EverdriveHandle`. Functions return `EVERDRIVE_OK` on success or a negative error code,
+//! the message of the last error of a handle is available through `everdrive_last_error`, and the message of
+//! a failed `everdrive_open` through `everdrive_open_error`.
+
+use crate::unf::{UnfRecvPacket, UnfSendPacket};
+use crate::{Everdrive, UnfDataType};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+
+pub const EVERDRIVE_OK: i32 = 0;
+/// A pointer argument was null or a string was not valid UTF-8
+pub const EVERDRIVE_INVALID_ARGUMENT: i32 = -1;
+pub const EVERDRIVE_IO_ERROR: i32 = -2;
+/// No response or packet arrived within the timeout
+pub const EVERDRIVE_TIMED_OUT: i32 = -3;
+/// The receive buffer is too small, the required size is stored in `len` and the packet is kept for the next call
+pub const EVERDRIVE_BUFFER_TOO_SMALL: i32 = -4;
+
+thread_local! {
+    /// Message of the last failed `everdrive_open` of the thread, there is no handle to store it in
+    static OPEN_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Opaque device handle
+pub struct EverdriveHandle {
+    ed: Everdrive,
+    pending: Option<UnfRecvPacket>,
+    last_error: CString,
+}
+
+impl EverdriveHandle {
+    fn result(&mut self, result: std::io::Result<()>) -> i32 {
+        match result {
+            Ok(()) => EVERDRIVE_OK,
+            Err(err) => {
+                self.last_error = CString::new(err.to_string()).unwrap_or_default();
+
+                if err.kind() == std::io::ErrorKind::TimedOut {
+                    EVERDRIVE_TIMED_OUT
+                } else {
+                    EVERDRIVE_IO_ERROR
+                }
+            }
+        }
+    }
+}
+
+/// Converts a nullable C string argument, `Err` if it is not valid UTF-8
+unsafe fn opt_str<'a>(s: *const c_char) -> Result<Option<&'a str>, ()> {
+    if s.is_null() {
+        return Ok(None);
+    }
+
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| ())
+}
+
+/// Opens the device on `port`, or the first responding device if `port` is null, and stores the handle in `out`.
+///
+/// # Safety
+///
+/// `port` must be null or a valid C string and `out` must be a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_open(
+    port: *const c_char,
+    out: *mut *mut EverdriveHandle,
+) -> i32 {
+    if out.is_null() {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    }
+
+    let ed = match unsafe { opt_str(port) } {
+        Ok(Some(port)) => Everdrive::new(port),
+        Ok(None) => Everdrive::detect(),
+        Err(()) => return EVERDRIVE_INVALID_ARGUMENT,
+    };
+
+    match ed {
+        Ok(ed) => {
+            let handle = Box::new(EverdriveHandle {
+                ed,
+                pending: None,
+                last_error: CString::default(),
+            });
+            unsafe { *out = Box::into_raw(handle) };
+            EVERDRIVE_OK
+        }
+        Err(err) => {
+            OPEN_ERROR.set(CString::new(err.to_string()).unwrap_or_default());
+
+            match err.kind() {
+                std::io::ErrorKind::TimedOut => EVERDRIVE_TIMED_OUT,
+                _ => EVERDRIVE_IO_ERROR,
+            }
+        }
+    }
+}
+
+/// Returns the message of the last failed `everdrive_open` on the calling thread, or an empty string.
+/// Valid until the next `everdrive_open` on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn everdrive_open_error() -> *const c_char {
+    OPEN_ERROR.with_borrow(|err| err.as_ptr())
+}
+
+/// Closes a handle returned by `everdrive_open`. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle from `everdrive_open` which has not been closed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_close(handle: *mut EverdriveHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Returns the message of the last error of `handle`, valid until the next call with the handle
+///
+/// # Safety
+///
+/// `handle` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_last_error(handle: *const EverdriveHandle) -> *const c_char {
+    match unsafe { handle.as_ref() } {
+        Some(handle) => handle.last_error.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Loads a rom image of `len` bytes like `Everdrive::ed_load_rom_auto`
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `data` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_load_rom(
+    handle: *mut EverdriveHandle,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    };
+
+    if data.is_null() {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    }
+
+    let rom = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    let result = handle.ed.ed_load_rom_auto(rom, None).map(|_| ());
+    handle.result(result)
+}
+
+/// Starts the loaded rom, `save_file_name` may be null
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `save_file_name` null or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_app_start(
+    handle: *mut EverdriveHandle,
+    save_file_name: *const c_char,
+) -> i32 {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    };
+
+    let Ok(save_file_name) = (unsafe { opt_str(save_file_name) }) else {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    };
+
+    let result = handle.ed.ed_app_start(save_file_name);
+    handle.result(result)
+}
+
+/// Sends a UNF packet of `datatype` with `len` bytes of `data`
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `data` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_unf_send(
+    handle: *mut EverdriveHandle,
+    datatype: u8,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    };
+
+    if data.is_null() && len > 0 {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    }
+
+    let data = match len {
+        0 => &[][..],
+        _ => unsafe { std::slice::from_raw_parts(data, len) },
+    };
+
+    let result = UnfSendPacket::from_bytes(UnfDataType::from(datatype), data)
+        .and_then(|packet| handle.ed.unf_tx(&packet));
+    handle.result(result)
+}
+
+/// Receives a UNF packet into `buf` of `capacity` bytes, storing its datatype and length.
+/// Returns `EVERDRIVE_TIMED_OUT` if no packet arrived within the timeout.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle, `buf` must point to `capacity` writable bytes and
+/// `datatype` and `len` must be valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn everdrive_unf_receive(
+    handle: *mut EverdriveHandle,
+    datatype: *mut u8,
+    buf: *mut u8,
+    capacity: usize,
+    len: *mut usize,
+) -> i32 {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    };
+
+    if datatype.is_null() || len.is_null() || (buf.is_null() && capacity > 0) {
+        return EVERDRIVE_INVALID_ARGUMENT;
+    }
+
+    let packet = match handle.pending.take() {
+        Some(packet) => packet,
+        None => match handle.ed.unf_rx() {
+            Ok(packet) => packet,
+            Err(err) => return handle.result(Err(err)),
+        },
+    };
+
+    let data = packet.get_data();
+
+    unsafe {
+        *len = data.len();
+        *datatype = packet.get_datatype().into();
+    }
+
+    if data.len() > capacity {
+        handle.pending = Some(packet);
+        return EVERDRIVE_BUFFER_TOO_SMALL;
+    }
+
+    // Even zero length copies need a non-null destination, and `buf` may be null for empty buffers
+    if !data.is_empty() {
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len()) };
+    }
+
+    EVERDRIVE_OK
+}
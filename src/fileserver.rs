@@ -0,0 +1,282 @@
+//! Debug filesystem served to the console over UNF binary packets.
+//!
+//! Requests and responses are `DataTypeBinary` packets starting with the `FS` prefix followed by
+//! an operation byte. All integers are big-endian.
+//!
+//! | Operation   | Request payload                    | Response payload           |
+//! |-------------|------------------------------------|----------------------------|
+//! | `0x01` open | mode `u8`, path bytes              | status `u8`, handle `u8`   |
+//! | `0x02` read | handle `u8`, length `u32`          | status `u8`, data          |
+//! | `0x03` write| handle `u8`, data                  | status `u8`, written `u32` |
+//! | `0x04` close| handle `u8`                        | status `u8`                |
+//! | `0x05` seek | handle `u8`, offset `u32`          | status `u8`                |
+//!
+//! Open modes are `0` read, `1` write (create and truncate), `2` append and `3` read/write.
+//! Responses repeat the `FS` prefix and operation byte of the request.
+//!
+//! The protocol is specific to libeverdrive and not part of UNFLoader. This crate only provides the
+//! host side, console programs build the requests themselves and exchange them with UNFLoader's
+//! `usb_write` and `usb_read`.
+
+use crate::CancellationToken;
+use crate::Everdrive;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+use std::io::{Seek, Write};
+
+const FS_PREFIX: &[u8; 2] = b"FS";
+
+const FS_OP_OPEN: u8 = 0x01;
+const FS_OP_READ: u8 = 0x02;
+const FS_OP_WRITE: u8 = 0x03;
+const FS_OP_CLOSE: u8 = 0x04;
+const FS_OP_SEEK: u8 = 0x05;
+
+const FS_STATUS_OK: u8 = 0x00;
+const FS_STATUS_NOT_FOUND: u8 = 0x01;
+const FS_STATUS_PERMISSION_DENIED: u8 = 0x02;
+const FS_STATUS_INVALID_REQUEST: u8 = 0x03;
+const FS_STATUS_IO_ERROR: u8 = 0x04;
+
+/// Maximum amount of data returned for a single read request
+const FS_MAX_READ: usize = 0x8000;
+
+/// Serves files under a root directory to the console. Paths requested by the
+/// console are always resolved relative to the root and cannot escape it, also not through
+/// symbolic links inside the root.
+#[derive(Debug)]
+pub struct FileServer {
+    root: std::path::PathBuf,
+    files: std::collections::HashMap<u8, std::fs::File>,
+}
+
+fn fs_status(err: &std::io::Error) -> u8 {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => FS_STATUS_NOT_FOUND,
+        std::io::ErrorKind::PermissionDenied => FS_STATUS_PERMISSION_DENIED,
+        std::io::ErrorKind::InvalidInput => FS_STATUS_INVALID_REQUEST,
+        _ => FS_STATUS_IO_ERROR,
+    }
+}
+
+fn path_escapes_root() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        "Path escapes the file server root",
+    )
+}
+
+fn invalid_request() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "Invalid file server request",
+    )
+}
+
+impl FileServer {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            files: std::collections::HashMap::new(),
+        }
+    }
+
+    fn resolve(&self, path: &[u8]) -> std::io::Result<std::path::PathBuf> {
+        let path = std::str::from_utf8(path).map_err(|_| invalid_request())?;
+        let mut resolved = self.root.clone();
+
+        for component in std::path::Path::new(path).components() {
+            match component {
+                std::path::Component::Normal(part) => resolved.push(part),
+                std::path::Component::CurDir => {}
+                // Absolute paths and parent directories could escape the root
+                _ => {
+                    return Err(path_escapes_root());
+                }
+            }
+        }
+
+        // Symbolic links are resolved to check where the path really leads. Files which don't
+        // exist yet are created in a directory which has to.
+        let canonical = match resolved.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(err)
+                if err.kind() == std::io::ErrorKind::NotFound
+                    && resolved.symlink_metadata().is_err() =>
+            {
+                match (resolved.parent(), resolved.file_name()) {
+                    (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+                    _ => return Err(err),
+                }
+            }
+            // Dangling symbolic links could create files anywhere
+            Err(_)
+                if resolved
+                    .symlink_metadata()
+                    .is_ok_and(|metadata| metadata.file_type().is_symlink()) =>
+            {
+                return Err(path_escapes_root());
+            }
+            Err(err) => return Err(err),
+        };
+
+        if !canonical.starts_with(self.root.canonicalize()?) {
+            return Err(path_escapes_root());
+        }
+
+        Ok(canonical)
+    }
+
+    fn file(&mut self, handle: Option<&u8>) -> std::io::Result<&mut std::fs::File> {
+        handle
+            .and_then(|handle| self.files.get_mut(handle))
+            .ok_or_else(invalid_request)
+    }
+
+    fn open(&mut self, args: &[u8]) -> std::io::Result<Vec<u8>> {
+        let (mode, path) = args.split_first().ok_or_else(invalid_request)?;
+        let path = self.resolve(path)?;
+
+        let mut options = std::fs::OpenOptions::new();
+
+        match mode {
+            0 => options.read(true),
+            1 => options.write(true).create(true).truncate(true),
+            2 => options.append(true).create(true),
+            3 => options.read(true).write(true),
+            _ => return Err(invalid_request()),
+        };
+
+        let handle = (0..=u8::MAX)
+            .find(|handle| !self.files.contains_key(handle))
+            .ok_or_else(|| std::io::Error::other("Too many open files"))?;
+
+        self.files.insert(handle, options.open(path)?);
+
+        Ok(vec![handle])
+    }
+
+    fn read(&mut self, args: &[u8]) -> std::io::Result<Vec<u8>> {
+        let len = args
+            .get(1..5)
+            .map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            .ok_or_else(invalid_request)?;
+
+        let file = self.file(args.first())?;
+
+        let mut data = vec![0; len.min(FS_MAX_READ)];
+        let read = crate::read_chunk(file, &mut data)?;
+        data.truncate(read);
+
+        Ok(data)
+    }
+
+    fn write(&mut self, args: &[u8]) -> std::io::Result<Vec<u8>> {
+        let file = self.file(args.first())?;
+        file.write_all(&args[1..])?;
+
+        Ok(((args.len() - 1) as u32).to_be_bytes().to_vec())
+    }
+
+    fn close(&mut self, args: &[u8]) -> std::io::Result<Vec<u8>> {
+        args.first()
+            .and_then(|handle| self.files.remove(handle))
+            .ok_or_else(invalid_request)?;
+
+        Ok(Vec::new())
+    }
+
+    fn seek(&mut self, args: &[u8]) -> std::io::Result<Vec<u8>> {
+        let offset = args
+            .get(1..5)
+            .map(|offset| u32::from_be_bytes(offset.try_into().unwrap()))
+            .ok_or_else(invalid_request)?;
+
+        self.file(args.first())?
+            .seek(std::io::SeekFrom::Start(offset as u64))?;
+
+        Ok(Vec::new())
+    }
+
+    /// Handles a file server request packet and sends the response to the console.
+    /// Returns false without sending anything if the packet is not a file server request.
+    pub fn handle_packet(
+        &mut self,
+        ed: &mut Everdrive,
+        packet: &UnfRecvPacket,
+    ) -> std::io::Result<bool> {
+        let data = packet.get_data();
+
+        if packet.get_datatype() != UnfDataType::DataTypeBinary
+            || data.len() < 3
+            || &data[0..2] != FS_PREFIX
+        {
+            return Ok(false);
+        }
+
+        let op = data[2];
+        let args = &data[3..];
+
+        let result = match op {
+            FS_OP_OPEN => self.open(args),
+            FS_OP_READ => self.read(args),
+            FS_OP_WRITE => self.write(args),
+            FS_OP_CLOSE => self.close(args),
+            FS_OP_SEEK => self.seek(args),
+            _ => Err(invalid_request()),
+        };
+
+        let (status, payload) = match result {
+            Ok(payload) => (FS_STATUS_OK, payload),
+            Err(err) => (fs_status(&err), Vec::new()),
+        };
+
+        let mut response = UnfSendPacket::new(UnfDataType::DataTypeBinary, payload.len() + 4)?;
+        let response_data = response.get_data();
+        response_data[0..2].copy_from_slice(FS_PREFIX);
+        response_data[2] = op;
+        response_data[3] = status;
+        response_data[4..].copy_from_slice(&payload);
+
+        ed.unf_tx(&response)?;
+
+        Ok(true)
+    }
+}
+
+impl Everdrive {
+    /// Serves files under `root` to the console until `stop` is cancelled. Packets which are not
+    /// file server requests are passed to `on_packet`. See the `fileserver` module for the protocol.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.unf_serve_files("./assets", |packet| println!("{:?}", packet), &CancellationToken::new())
+    ///     .unwrap();
+    /// ```
+    pub fn unf_serve_files(
+        &mut self,
+        root: impl Into<std::path::PathBuf>,
+        mut on_packet: impl FnMut(&UnfRecvPacket),
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        let mut server = FileServer::new(root);
+
+        while !stop.is_cancelled() {
+            match self.unf_rx() {
+                Ok(packet) => {
+                    if !server.handle_packet(self, &packet)? {
+                        on_packet(&packet);
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
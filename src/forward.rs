@@ -0,0 +1,137 @@
+use crate::CancellationToken;
+use crate::Everdrive;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+use std::io::{Read, Write};
+
+/// Interval for polling for new clients, client input and console packets
+const FORWARD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Output a client may have pending before it is considered unable to keep up and dropped, room
+/// for several full size screenshot packets
+const FORWARD_BACKLOG_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Connected client with the output its socket has not accepted yet
+struct ForwardClient {
+    stream: std::net::TcpStream,
+    pending: Vec<u8>,
+}
+
+impl ForwardClient {
+    /// Writes pending output until the socket would block, returns false if the client failed
+    fn flush(&mut self) -> bool {
+        let mut written = 0;
+
+        let ok = loop {
+            if written == self.pending.len() {
+                break true;
+            }
+
+            match self.stream.write(&self.pending[written..]) {
+                Ok(0) => break false,
+                Ok(n) => written += n,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break true,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => break false,
+            }
+        };
+
+        self.pending.drain(..written);
+        ok
+    }
+}
+
+/// Re-frames a received packet in the UNF wire format for forwarding
+fn forward_frame(packet: &UnfRecvPacket) -> std::io::Result<Vec<u8>> {
+    let frame = UnfSendPacket::from_bytes(packet.get_datatype(), packet.get_data())?;
+    Ok(frame.as_bytes().to_vec())
+}
+
+impl Everdrive {
+    /// Accepts TCP clients on `addr` and forwards every packet received from the console to all connected
+    /// clients, so several tools or a remote machine can observe one debug session. Runs until `stop` is cancelled.
+    ///
+    /// Packets are forwarded in the UNF wire format (`DMA@` header, data and `CMPH` footer), so clients
+    /// can parse them the same way as the USB stream. If `relay_input` is set, data sent by clients is
+    /// relayed to the console as `DataTypeText` packets, like the input of UNFLoader's debug mode.
+    /// Clients which disconnect or fail are dropped without ending the session, as are clients which
+    /// fall more than 4MB behind.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// // Observe with `nc localhost 6464`
+    /// ed.unf_forward_tcp("127.0.0.1:6464", true, &CancellationToken::new()).unwrap();
+    /// ```
+    pub fn unf_forward_tcp(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        relay_input: bool,
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let mut clients: Vec<ForwardClient> = Vec::new();
+        let mut buf = [0; 1024];
+
+        while !stop.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    clients.push(ForwardClient {
+                        stream,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+
+            let mut input = Vec::new();
+
+            clients.retain_mut(|client| {
+                loop {
+                    match client.stream.read(&mut buf) {
+                        Ok(0) => return false,
+                        Ok(n) => input.extend_from_slice(&buf[..n]),
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return true,
+                        Err(_) => return false,
+                    }
+                }
+            });
+
+            if relay_input && !input.is_empty() {
+                let packet = UnfSendPacket::from_bytes(UnfDataType::DataTypeText, &input)?;
+                self.unf_tx(&packet)?;
+            }
+
+            let mut received = false;
+
+            while let Some(packet) = self.unf_try_rx()? {
+                received = true;
+                let frame = forward_frame(&packet)?;
+
+                for client in &mut clients {
+                    client.pending.extend_from_slice(&frame);
+                }
+            }
+
+            // Clients are non-blocking, output is sent as their sockets accept it and a client
+            // which can't keep up is dropped
+            clients.retain_mut(|client| {
+                client.flush() && client.pending.len() <= FORWARD_BACKLOG_LIMIT
+            });
+
+            if !received {
+                crate::clock::sleep(FORWARD_POLL_INTERVAL);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! Direct FTDI access through the D2XX driver, for tuning the latency timer and USB transfer sizes of
+//! the FT245R bridge of the Everdrive 64.
+//!
+//! The virtual COM port driver buffers received data for up to 16ms before passing it on, which
+//! limits small UNF packets to a few dozen per second. The D2XX driver allows lowering the latency
+//! timer per connection. It has to be installed on the host, on Linux the `ftdi_sio` kernel driver
+//! also has to be unbound from the device, otherwise `Everdrive::new_ftdi` falls back to the serial
+//! port without tuning.
+
+use crate::transport::Transport;
+use crate::usb::FT245R_VID_PID;
+use crate::{Everdrive, UsbDeviceRule};
+use libftd2xx::FtdiCommon;
+
+fn ftdi_error(status: libftd2xx::FtStatus) -> std::io::Error {
+    std::io::Error::other(format!("FTDI error {:?}", status))
+}
+
+/// Returns true for the FT245R bridge of the Everdrive 64, or a bridge describing itself as an
+/// Everdrive. Other FTDI adapters, such as FT232R serial cables sharing the product id, don't match.
+fn is_everdrive(info: &libftd2xx::DeviceInfo) -> bool {
+    let description = info.description.to_lowercase();
+
+    ((info.vendor_id, info.product_id) == FT245R_VID_PID && description.contains("ft245r"))
+        || description.contains("everdrive")
+}
+
+/// Opens the first Everdrive bridge which is not already open
+fn open_everdrive() -> std::io::Result<libftd2xx::Ftdi> {
+    let info = libftd2xx::list_devices()
+        .map_err(ftdi_error)?
+        .into_iter()
+        .find(|info| !info.port_open && is_everdrive(info))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "No Everdrive found among the FTDI devices",
+            )
+        })?;
+
+    match info.serial_number.is_empty() {
+        true => libftd2xx::Ftdi::with_description(&info.description),
+        false => libftd2xx::Ftdi::with_serial_number(&info.serial_number),
+    }
+    .map_err(ftdi_error)
+}
+
+/// Driver settings applied when opening a device with the D2XX driver
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FtdiOptions {
+    /// Time the bridge waits for more data before sending a partial USB packet, 2ms to 255ms.
+    /// Defaults to 2ms.
+    pub latency_timer: std::time::Duration,
+    /// USB IN transfer size in bytes, a multiple of 64 up to 65536. Defaults to 65536.
+    pub transfer_size: u32,
+}
+
+impl Default for FtdiOptions {
+    fn default() -> Self {
+        Self {
+            latency_timer: std::time::Duration::from_millis(2),
+            transfer_size: 0x10000,
+        }
+    }
+}
+
+/// Transport over an FTDI device opened with the D2XX driver
+#[derive(Debug)]
+pub struct FtdiTransport {
+    // D2XX takes the handle mutably even for queries such as the receive queue size
+    device: std::sync::Mutex<libftd2xx::Ftdi>,
+}
+
+impl FtdiTransport {
+    /// Opens the FTDI device with `serial_number`, or the first Everdrive bridge if `None`, applies
+    /// `options` and purges both buffers. Without a serial number only the FT245R bridge of the
+    /// Everdrive 64 is opened, recognized by its USB ids and description, so other FTDI adapters
+    /// are left alone.
+    pub fn open(serial_number: Option<&str>, options: &FtdiOptions) -> std::io::Result<Self> {
+        let mut device = match serial_number {
+            Some(serial_number) => {
+                libftd2xx::Ftdi::with_serial_number(serial_number).map_err(ftdi_error)?
+            }
+            None => open_everdrive()?,
+        };
+
+        device
+            .set_latency_timer(options.latency_timer)
+            .map_err(ftdi_error)?;
+        device
+            .set_usb_parameters(options.transfer_size)
+            .map_err(ftdi_error)?;
+        device.purge_all().map_err(ftdi_error)?;
+
+        Ok(Self {
+            device: std::sync::Mutex::new(device),
+        })
+    }
+
+    fn device(&self) -> std::sync::MutexGuard<'_, libftd2xx::Ftdi> {
+        self.device.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl std::io::Read for FtdiTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // D2XX returns no data instead of an error when the read timeout expires
+        match self.device().read(buf).map_err(ftdi_error)? {
+            0 => Err(std::io::ErrorKind::TimedOut.into()),
+            len => Ok(len),
+        }
+    }
+}
+
+impl std::io::Write for FtdiTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.device().write(buf).map_err(ftdi_error)? {
+            0 => Err(std::io::ErrorKind::TimedOut.into()),
+            len => Ok(len),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for FtdiTransport {
+    fn set_timeout(&mut self, timeout: std::time::Duration) -> std::io::Result<()> {
+        self.device()
+            .set_timeouts(timeout, timeout)
+            .map_err(ftdi_error)
+    }
+
+    fn bytes_to_read(&self) -> std::io::Result<u32> {
+        Ok(self.device().queue_status().map_err(ftdi_error)? as u32)
+    }
+
+    fn clear_input(&mut self) -> std::io::Result<()> {
+        self.device().purge_rx().map_err(ftdi_error)
+    }
+
+    fn clear_output(&mut self) -> std::io::Result<()> {
+        self.device().purge_tx().map_err(ftdi_error)
+    }
+}
+
+impl Everdrive {
+    /// Opens the Everdrive with the FTDI D2XX driver and applies `options`, see the `ftdi` module.
+    /// `serial_number` selects the bridge when several FTDI devices are connected.
+    ///
+    /// Falls back to the serial port of the first matching `UsbDeviceRule::defaults()` device with the
+    /// same serial number if the D2XX driver is not installed or can't open the device.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::ftdi::FtdiOptions;
+    ///
+    /// let mut ed = Everdrive::new_ftdi(None, &FtdiOptions::default()).unwrap();
+    ///
+    /// ed.ed_status().unwrap();
+    /// ```
+    pub fn new_ftdi(serial_number: Option<&str>, options: &FtdiOptions) -> std::io::Result<Self> {
+        let err = match FtdiTransport::open(serial_number, options) {
+            Ok(transport) => return Self::from_transport(transport),
+            Err(err) => err,
+        };
+
+        crate::trace::trace_event!(debug, error = %err, "D2XX open failed, using the serial port");
+
+        let device = Self::find_usb_devices_matching(&UsbDeviceRule::defaults())?
+            .into_iter()
+            .find(|device| {
+                serial_number.is_none() || device.serial_number.as_deref() == serial_number
+            })
+            .ok_or(err)?;
+
+        Self::new(&device.port_name)
+    }
+}
@@ -0,0 +1,359 @@
+//! Controller input injection for automated tests of UNF-aware homebrew.
+//!
+//! Controller states are sent as `DataTypeBinary` packets of 8 bytes. All integers are big-endian.
+//!
+//! | Offset | Size | Field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | `0x00` | 2    | `IN` prefix                                   |
+//! | `0x02` | 1    | controller port, `0` to `3`                   |
+//! | `0x03` | 1    | reserved, `0`                                 |
+//! | `0x04` | 2    | button mask in the layout of the N64 joybus   |
+//! | `0x06` | 1    | analog stick X as a signed byte               |
+//! | `0x07` | 1    | analog stick Y as a signed byte               |
+//!
+//! The console is expected to apply the latest state of a port in place of the physical controller
+//! until it receives a new one.
+//!
+//! Input sequences can be recorded with [`InputRecorder`] and replayed with `play_inputs`. Movies
+//! are stored as text with one frame per line of the time in microseconds since the start, port,
+//! button mask in hex and stick X and Y, separated by spaces. Empty lines and lines starting with
+//! `#` are ignored.
+
+use crate::clock::Stopwatch;
+use crate::unf::{UnfDataType, UnfSendPacket};
+use crate::{CancellationToken, Everdrive};
+
+const IN_PREFIX: &[u8; 2] = b"IN";
+
+/// Number of controller ports on the console
+const IN_PORT_COUNT: u8 = 4;
+
+/// Button mask in the bit layout of the N64 controller status
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::input::Buttons;
+///
+/// let buttons = Buttons::A | Buttons::START;
+///
+/// assert!(buttons.contains(Buttons::A));
+/// assert!(!buttons.contains(Buttons::B));
+/// assert_eq!(buttons.bits(), 0x9000);
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Buttons(u16);
+
+impl Buttons {
+    pub const NONE: Buttons = Buttons(0);
+    pub const A: Buttons = Buttons(0x8000);
+    pub const B: Buttons = Buttons(0x4000);
+    pub const Z: Buttons = Buttons(0x2000);
+    pub const START: Buttons = Buttons(0x1000);
+    pub const D_UP: Buttons = Buttons(0x0800);
+    pub const D_DOWN: Buttons = Buttons(0x0400);
+    pub const D_LEFT: Buttons = Buttons(0x0200);
+    pub const D_RIGHT: Buttons = Buttons(0x0100);
+    pub const L: Buttons = Buttons(0x0020);
+    pub const R: Buttons = Buttons(0x0010);
+    pub const C_UP: Buttons = Buttons(0x0008);
+    pub const C_DOWN: Buttons = Buttons(0x0004);
+    pub const C_LEFT: Buttons = Buttons(0x0002);
+    pub const C_RIGHT: Buttons = Buttons(0x0001);
+
+    pub fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns true if all buttons of `other` are pressed
+    pub fn contains(&self, other: Buttons) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Buttons {
+    type Output = Buttons;
+
+    fn bitor(self, rhs: Buttons) -> Buttons {
+        Buttons(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Buttons {
+    fn bitor_assign(&mut self, rhs: Buttons) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A controller state sent at a point in time
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputFrame {
+    /// Time since the start of the movie
+    pub time: std::time::Duration,
+    pub pad: u8,
+    pub buttons: Buttons,
+    pub stick: (i8, i8),
+}
+
+fn invalid_movie(line: usize) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Invalid input movie frame on line {}", line),
+    )
+}
+
+/// A recorded sequence of controller states
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::input::{Buttons, InputMovie};
+///
+/// let movie = InputMovie::parse("0 0 0000 0 0\n500000 0 8000 40 -10\n").unwrap();
+///
+/// assert_eq!(movie.frames().len(), 2);
+/// assert_eq!(movie.frames()[1].buttons, Buttons::A);
+/// assert_eq!(movie.frames()[1].stick, (40, -10));
+/// assert_eq!(InputMovie::parse(&movie.to_string()).unwrap(), movie);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputMovie {
+    frames: Vec<InputFrame>,
+}
+
+impl InputMovie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> &[InputFrame] {
+        &self.frames
+    }
+
+    /// Appends a frame. Frames must be pushed in order of their time.
+    pub fn push(&mut self, frame: InputFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Parses a movie from its text form
+    pub fn parse(text: &str) -> std::io::Result<Self> {
+        let mut movie = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            let frame = match fields[..] {
+                [time, pad, buttons, x, y] => (|| {
+                    Some(InputFrame {
+                        time: std::time::Duration::from_micros(time.parse().ok()?),
+                        pad: pad.parse().ok()?,
+                        buttons: Buttons(u16::from_str_radix(buttons, 16).ok()?),
+                        stick: (x.parse().ok()?, y.parse().ok()?),
+                    })
+                })(),
+                _ => None,
+            };
+
+            let frame = frame.ok_or_else(|| invalid_movie(index + 1))?;
+
+            if movie
+                .frames
+                .last()
+                .is_some_and(|last| last.time > frame.time)
+            {
+                return Err(invalid_movie(index + 1));
+            }
+
+            movie.frames.push(frame);
+        }
+
+        Ok(movie)
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl std::fmt::Display for InputMovie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for frame in &self.frames {
+            writeln!(
+                f,
+                "{} {} {:04x} {} {}",
+                frame.time.as_micros(),
+                frame.pad,
+                frame.buttons.bits(),
+                frame.stick.0,
+                frame.stick.1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends controller states to the console while recording them with their time into an
+/// [`InputMovie`]
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::Everdrive;
+/// use libeverdrive::input::{Buttons, InputRecorder};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+/// let mut recorder = InputRecorder::new();
+///
+/// recorder.send(&mut ed, 0, Buttons::START, (0, 0)).unwrap();
+/// std::thread::sleep(std::time::Duration::from_millis(100));
+/// recorder.send(&mut ed, 0, Buttons::NONE, (0, 0)).unwrap();
+///
+/// recorder.into_movie().save("intro.movie").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct InputRecorder {
+    start: Stopwatch,
+    movie: InputMovie,
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputRecorder {
+    /// Creates a recorder. Frame times are measured from this call.
+    pub fn new() -> Self {
+        Self {
+            start: Stopwatch::start(),
+            movie: InputMovie::new(),
+        }
+    }
+
+    /// Sends a controller state like `send_controller_state` and records it on success
+    pub fn send(
+        &mut self,
+        ed: &mut Everdrive,
+        pad: u8,
+        buttons: Buttons,
+        stick: (i8, i8),
+    ) -> std::io::Result<()> {
+        ed.send_controller_state(pad, buttons, stick)?;
+
+        self.movie.push(InputFrame {
+            time: self.start.elapsed().unwrap_or_default(),
+            pad,
+            buttons,
+            stick,
+        });
+
+        Ok(())
+    }
+
+    pub fn movie(&self) -> &InputMovie {
+        &self.movie
+    }
+
+    pub fn into_movie(self) -> InputMovie {
+        self.movie
+    }
+}
+
+impl Everdrive {
+    /// Sends the state of controller `pad` (`0` to `3`) to the console, with `stick` as the analog
+    /// stick X and Y position. See the `input` module for the protocol.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::input::Buttons;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// // Hold right and jump for a second, then release everything
+    /// ed.send_controller_state(0, Buttons::A, (80, 0)).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// ed.send_controller_state(0, Buttons::NONE, (0, 0)).unwrap();
+    /// ```
+    pub fn send_controller_state(
+        &mut self,
+        pad: u8,
+        buttons: Buttons,
+        stick: (i8, i8),
+    ) -> std::io::Result<()> {
+        if pad >= IN_PORT_COUNT {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Controller port {} out of range", pad),
+            ));
+        }
+
+        let mut payload = [0; 8];
+        payload[0..2].copy_from_slice(IN_PREFIX);
+        payload[2] = pad;
+        payload[4..6].copy_from_slice(&buttons.bits().to_be_bytes());
+        payload[6] = stick.0 as u8;
+        payload[7] = stick.1 as u8;
+
+        self.unf_tx(&UnfSendPacket::from_bytes(
+            UnfDataType::DataTypeBinary,
+            &payload,
+        )?)
+    }
+
+    /// Replays the frames of `movie`, sending each controller state at its recorded time relative
+    /// to the start of playback. Returns a `Cancelled` error if `stop` is cancelled first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::input::InputMovie;
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let movie = InputMovie::load("intro.movie").unwrap();
+    ///
+    /// ed.play_inputs(&movie, &CancellationToken::new()).unwrap();
+    /// ```
+    pub fn play_inputs(
+        &mut self,
+        movie: &InputMovie,
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        let start = Stopwatch::start();
+
+        for frame in movie.frames() {
+            // Sleep in short steps so cancellation is noticed during long pauses
+            while let Some(wait) = start
+                .elapsed()
+                .and_then(|elapsed| frame.time.checked_sub(elapsed))
+                .filter(|wait| !wait.is_zero())
+            {
+                stop.check()?;
+                crate::clock::sleep(wait.min(std::time::Duration::from_millis(10)));
+            }
+
+            stop.check()?;
+            self.send_controller_state(frame.pad, frame.buttons, frame.stick)?;
+        }
+
+        Ok(())
+    }
+}
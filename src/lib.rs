@@ -1,9 +1,191 @@
+#[cfg(feature = "edos")]
+mod benchmark;
+pub mod byteswap;
+mod cancel;
+#[cfg(feature = "edos")]
+mod capabilities;
+pub mod cheats;
+mod clock;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "unf")]
+pub mod crash;
+#[cfg(feature = "debugger")]
+mod debugger;
+#[cfg(feature = "edos")]
 mod edos;
+pub mod elf;
+mod error;
+mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "unf")]
+pub mod fileserver;
+#[cfg(feature = "unf")]
+mod forward;
+#[cfg(feature = "ftdi")]
+pub mod ftdi;
+#[cfg(feature = "unf")]
+pub mod input;
+#[cfg(feature = "unf")]
+mod log_session;
+#[cfg(feature = "edos")]
+mod memory_map;
+#[cfg(feature = "debugger")]
+mod memory_watch;
+#[cfg(feature = "unf")]
+mod monitor;
+pub mod patch;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+mod reader;
+#[cfg(feature = "unf")]
+pub mod remote;
+#[cfg(feature = "edos")]
+mod retry;
+pub mod rom;
+#[cfg(feature = "romdb")]
+pub mod romdb;
+#[cfg(feature = "unf")]
+mod router;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(all(feature = "edos", feature = "unf"))]
+mod run;
+#[cfg(feature = "edos")]
+mod save_db;
+#[cfg(feature = "unf")]
+pub mod savestate;
+#[cfg(feature = "unf")]
+pub mod screenshot;
+mod session;
+mod stats;
+pub mod symbols;
+mod trace;
+#[cfg(feature = "unf")]
+pub mod transfer;
+pub mod transport;
+#[cfg(feature = "unf")]
 mod unf;
+#[cfg(feature = "edos")]
+mod upload_cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod usb;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(not(target_arch = "wasm32"))]
+mod watcher;
+
+#[cfg(feature = "edos")]
+pub use benchmark::UsbBenchmark;
+pub use cancel::{CancellationToken, Cancelled};
+#[cfg(feature = "edos")]
+pub use capabilities::EdCapabilities;
+#[cfg(feature = "edos")]
+pub use edos::{
+    BootOptions, EdCommand, EdFileName, EdFileNameError, EdFpgaError, EdRtcRegionType, EdSaveType,
+    EdVerifyMismatch, EdVerifyMode, RomLoadOptions, RomLoadReport,
+};
+pub use error::{EverdriveError, PortOpenError, PortOpenFailure};
+pub use events::EverdriveEvents;
+#[cfg(feature = "unf")]
+pub use log_session::{LogEntry, LogLevel, LogSession};
+#[cfg(feature = "edos")]
+pub use memory_map::{MemoryMap, MemoryRegion, RegionKind};
+#[cfg(feature = "debugger")]
+pub use memory_watch::{MemoryChange, MemoryWatcher};
+#[cfg(feature = "unf")]
+pub use monitor::{HeartbeatMonitor, HeartbeatStatus};
+pub use progress::{Progress, ProgressOperation, ProgressPhase};
+pub use reader::PacketReader;
+#[cfg(feature = "edos")]
+pub use retry::RetryPolicy;
+#[cfg(feature = "unf")]
+pub use router::UnfRouter;
+#[cfg(all(feature = "edos", feature = "unf"))]
+pub use run::RunOptions;
+pub use session::{SessionError, SessionState};
+pub use stats::TransferStats;
+#[cfg(feature = "unf")]
+pub use transfer::UnfReassembler;
+#[cfg(feature = "unf")]
+pub use unf::{UnfDataType, UnfHandlers, UnfHeartbeat, UnfRecvPacket, UnfSendPacket, UnfStartup};
+#[cfg(feature = "edos")]
+pub use upload_cache::UploadCache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use usb::{UsbDevice, UsbDeviceRule};
+#[cfg(not(target_arch = "wasm32"))]
+pub use watcher::{EverdriveWatcher, WatcherEvent};
+
+use transport::Transport;
+
+/// Time without received data after which `reset_session` considers the input drained
+const SESSION_QUIET_TIME: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Maximum time `reset_session` spends draining input, a running rom may never stop sending
+const SESSION_DRAIN_LIMIT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Writes are split into slices of this many seconds of data when a write rate limit is set
+const WRITE_THROTTLE_SLICES_PER_SEC: u32 = 20;
+
+/// Interval for polling the receive buffer while draining input
+const SESSION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(5);
+
+/// Reads from `reader` until `buf` is full or the end of the stream is reached
+pub(crate) fn read_chunk<R: std::io::Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut len = 0;
+
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(len)
+}
 
 #[derive(Debug)]
 pub struct Everdrive {
-    port: Box<dyn serialport::SerialPort>,
+    port: Box<dyn Transport>,
+    #[cfg(feature = "unf")]
+    unf_heartbeat: Option<unf::UnfHeartbeat>,
+    #[cfg(feature = "unf")]
+    unf_consume_heartbeats: bool,
+    #[cfg(feature = "unf")]
+    unf_resync_limit: usize,
+    cancel: Option<CancellationToken>,
+    #[cfg(feature = "edos")]
+    retry_policy: RetryPolicy,
+    stats: TransferStats,
+    session: SessionState,
+    timeout: std::time::Duration,
+    write_rate_limit: Option<u32>,
+    #[cfg(feature = "unf")]
+    heartbeat_tracker: Option<monitor::HeartbeatTracker>,
+    #[cfg(feature = "edos")]
+    upload_cache: Option<UploadCache>,
+    #[cfg(feature = "edos")]
+    rom_padding: bool,
+    #[cfg(feature = "edos")]
+    rom_trimming: bool,
+    #[cfg(feature = "edos")]
+    memory_map: MemoryMap,
+    #[cfg(feature = "edos")]
+    console_tv_standard: Option<rom::TvStandard>,
+    #[cfg(feature = "edos")]
+    tv_standard_patching: bool,
+    #[cfg(feature = "edos")]
+    capabilities: EdCapabilities,
+    events: Option<Box<dyn EverdriveEvents>>,
+    #[cfg(any(feature = "edos", feature = "unf"))]
+    progress: Option<Progress>,
 }
 
 impl Everdrive {
@@ -12,50 +194,298 @@ impl Everdrive {
     ///
     /// `timeout` is configured for the serial port for future reads and writes
     ///
+    /// On Windows, `port_name` is a COM port name such as `COM3` or `COM12`, with or without the
+    /// `\\.\` prefix.
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// use libeverdrive::Everdrive;
     ///
-    /// let usb_ports = Everdrive::find_usb_devices();
+    /// let usb_ports = Everdrive::find_usb_devices().unwrap();
     /// assert!(!usb_ports.is_empty());
     ///
     /// let mut ed = Everdrive::new(&usb_ports[0]).unwrap();
     ///
     /// assert!(ed.ed_status().is_ok());
     ///  ```
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new(port_name: &str) -> std::io::Result<Self> {
-        let port = match serialport::new(port_name, 115_200).open() {
-            Ok(port) => port,
-            Err(err) => {
-                return Err(err.into());
-            }
-        };
+        trace::trace_event!(debug, port_name, "opening Everdrive port");
 
-        let mut ed = Self { port };
+        let port = transport::open_serial(port_name)?;
+
+        Self::from_transport(port)
+    }
+
+    /// Creates a new Everdrive instance communicating over `transport` instead of a serial port,
+    /// such as a `RecordingTransport` or a `ReplayTransport`.
+    pub fn from_transport(transport: impl Transport + 'static) -> std::io::Result<Self> {
+        let mut ed = Self {
+            port: Box::new(transport),
+            #[cfg(feature = "unf")]
+            unf_heartbeat: None,
+            #[cfg(feature = "unf")]
+            unf_consume_heartbeats: false,
+            #[cfg(feature = "unf")]
+            unf_resync_limit: 0,
+            cancel: None,
+            #[cfg(feature = "edos")]
+            retry_policy: RetryPolicy::none(),
+            stats: TransferStats::default(),
+            session: SessionState::Unknown,
+            timeout: std::time::Duration::ZERO,
+            write_rate_limit: None,
+            #[cfg(feature = "unf")]
+            heartbeat_tracker: None,
+            #[cfg(feature = "edos")]
+            upload_cache: None,
+            #[cfg(feature = "edos")]
+            rom_padding: true,
+            #[cfg(feature = "edos")]
+            rom_trimming: false,
+            #[cfg(feature = "edos")]
+            memory_map: MemoryMap::everdrive64(),
+            #[cfg(feature = "edos")]
+            console_tv_standard: None,
+            #[cfg(feature = "edos")]
+            tv_standard_patching: false,
+            #[cfg(feature = "edos")]
+            capabilities: EdCapabilities::everdrive64(),
+            events: None,
+            #[cfg(any(feature = "edos", feature = "unf"))]
+            progress: None,
+        };
         ed.set_timeout(std::time::Duration::from_millis(100))?;
         Ok(ed)
     }
 
     pub fn set_timeout(&mut self, timeout: std::time::Duration) -> std::io::Result<()> {
-        match self.port.set_timeout(timeout) {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                return Err(err.into());
+        self.port.set_timeout(timeout)?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Returns the timeout currently configured for reads and writes
+    pub fn timeout(&self) -> std::time::Duration {
+        self.timeout
+    }
+
+    /// Limits the rate data is written to the device to `bytes_per_sec`, or removes the limit with
+    /// `None`, which is the default. Some USB hubs drop data at full speed, a limit trades upload
+    /// speed for reliability.
+    ///
+    /// Writes are split into slices of 1/20th of the rate and paced to keep the average rate below
+    /// the limit. Rate limiting is not available on `wasm32-unknown-unknown`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.set_write_rate_limit(Some(256 * 1024));
+    /// ```
+    pub fn set_write_rate_limit(&mut self, bytes_per_sec: Option<u32>) {
+        self.write_rate_limit = bytes_per_sec.filter(|rate| *rate > 0);
+    }
+
+    /// Returns the write rate limit in bytes per second, if any
+    pub fn write_rate_limit(&self) -> Option<u32> {
+        self.write_rate_limit
+    }
+
+    /// Runs `f` with `timeout` configured for reads and writes and restores the previous timeout afterwards,
+    /// also when `f` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::time::Duration;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// // Wait up to 10 seconds for the rom to send its first packet
+    /// let packet = ed.with_timeout(Duration::from_secs(10), |ed| ed.unf_rx()).unwrap();
+    /// ```
+    pub fn with_timeout<T>(
+        &mut self,
+        timeout: std::time::Duration,
+        f: impl FnOnce(&mut Self) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let previous = self.timeout;
+        self.set_timeout(timeout)?;
+
+        let result = f(self);
+        let restored = self.set_timeout(previous);
+
+        // An error from `f` is more relevant than a failure to restore the timeout
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    /// Sets a token which cancels long running transfers such as `ed_rom_write` and `ed_load_rom`.
+    /// Transfers check the token between chunks and return a `Cancelled` error once it has been cancelled.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancel = token;
+    }
+
+    /// Returns a `Cancelled` error if the cancellation token has been cancelled
+    #[cfg(any(feature = "edos", feature = "unf"))]
+    pub(crate) fn check_cancelled(&self) -> std::io::Result<()> {
+        match &self.cancel {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the connection to a known state, for example after a previous process crashed in the middle
+    /// of a transfer. Pending output is discarded, input is drained until the device has been quiet for 50ms
+    /// (for at most one second), recorded UNF and session state is forgotten and the status handshake is run again.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.reset_session().unwrap();
+    /// ```
+    pub fn reset_session(&mut self) -> std::io::Result<()> {
+        self.port.clear_output()?;
+
+        let drain_start = clock::Stopwatch::start();
+        let mut quiet_since = drain_start;
+
+        // Without a clock the input is only drained once
+        while quiet_since
+            .elapsed()
+            .is_some_and(|t| t < SESSION_QUIET_TIME)
+            && drain_start
+                .elapsed()
+                .is_some_and(|t| t < SESSION_DRAIN_LIMIT)
+        {
+            if self.port.bytes_to_read()? > 0 {
+                self.port.clear_input()?;
+                quiet_since = clock::Stopwatch::start();
             }
+
+            clock::sleep(SESSION_POLL_INTERVAL);
         }
+
+        self.port.clear_input()?;
+
+        #[cfg(feature = "unf")]
+        {
+            self.unf_heartbeat = None;
+        }
+
+        self.session = SessionState::Unknown;
+
+        trace::trace_event!(debug, "session reset");
+
+        #[cfg(feature = "edos")]
+        self.ed_status()?;
+
+        Ok(())
     }
 
     pub fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
-        self.port.write_all(buf)
+        trace::trace_event!(trace, len = buf.len(), data = %trace::HexDump(buf), "serial write");
+
+        let start = clock::Stopwatch::start();
+        let result = match self.write_rate_limit {
+            Some(rate) => self.write_throttled(buf, rate, start),
+            None => self.port.write_all(buf),
+        };
+
+        if let Err(err) = result {
+            return Err(self.emit_error(err));
+        }
+
+        self.stats.bytes_written += buf.len() as u64;
+        self.stats.write_time += start.elapsed().unwrap_or_default();
+        Ok(())
+    }
+
+    /// Writes `buf` in slices, sleeping after each slice until the time `rate` allows for the data
+    /// written since `start` has passed
+    fn write_throttled(
+        &mut self,
+        buf: &[u8],
+        rate: u32,
+        start: clock::Stopwatch,
+    ) -> std::io::Result<()> {
+        let slice_size = (rate / WRITE_THROTTLE_SLICES_PER_SEC).max(64) as usize;
+        let mut written = 0;
+
+        for slice in buf.chunks(slice_size) {
+            self.port.write_all(slice)?;
+            written += slice.len();
+
+            let due = std::time::Duration::from_secs_f64(written as f64 / rate as f64);
+
+            if let Some(elapsed) = start.elapsed()
+                && elapsed < due
+            {
+                clock::sleep(due - elapsed);
+            }
+        }
+
+        Ok(())
     }
 
     pub fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
-        self.port.read_exact(buf)
+        self.read_exact_reporting(buf, true)
+    }
+
+    /// Like `read_exact`, for waiting on data which may not arrive such as the next UNF packet.
+    /// Timeouts are the normal outcome of waiting and are not reported to `on_error`.
+    #[cfg(feature = "unf")]
+    pub(crate) fn read_exact_polling(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.read_exact_reporting(buf, false)
+    }
+
+    fn read_exact_reporting(&mut self, buf: &mut [u8], timeouts: bool) -> std::io::Result<()> {
+        let start = clock::Stopwatch::start();
+        if let Err(err) = self.port.read_exact(buf) {
+            if !timeouts
+                && matches!(
+                    err.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                )
+            {
+                return Err(err);
+            }
+
+            return Err(self.emit_error(err));
+        }
+
+        trace::trace_event!(trace, len = buf.len(), data = %trace::HexDump(buf), "serial read");
+
+        self.stats.bytes_read += buf.len() as u64;
+        self.stats.read_time += start.elapsed().unwrap_or_default();
+        Ok(())
     }
 
     pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.port.read(buf)
+        let start = clock::Stopwatch::start();
+        let len = self.port.read(buf).map_err(|err| self.emit_error(err))?;
+
+        trace::trace_event!(trace, len, data = %trace::HexDump(&buf[..len]), "serial read");
+
+        self.stats.bytes_read += len as u64;
+        self.stats.read_time += start.elapsed().unwrap_or_default();
+        Ok(len)
+    }
+
+    /// Returns the number of bytes received by the serial port which have not been read yet
+    pub fn bytes_to_read(&self) -> std::io::Result<u32> {
+        self.port.bytes_to_read()
     }
 
     pub fn read_word_be(&mut self) -> std::io::Result<u32> {
@@ -71,31 +501,59 @@ impl Everdrive {
     }
 
     /// Find available USB ports with everdrive devices and returns a list of port names
-    /// matching Everdrive VID and PID. The port name can be used to create a new Everdrive instance.
+    /// matching the default `UsbDeviceRule`s. The port name can be used to create a new Everdrive instance.
+    ///
+    /// Returns an empty list if no devices are connected, and `EverdriveError::Enumeration`
+    /// if the serial ports of the system can't be listed.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use libeverdrive::Everdrive;
     ///
-    /// let usb_ports = Everdrive::find_usb_devices();
+    /// let usb_ports = Everdrive::find_usb_devices().unwrap();
     ///
     /// println!("Found devices: {:?}", usb_ports);
     /// ```
-    pub fn find_usb_devices() -> Vec<String> {
-        let ports = serialport::available_ports().expect("No available USB ports found");
-
-        let ed_device_ports = ports.iter().filter_map(|p| match &p.port_type {
-            serialport::SerialPortType::UsbPort(info) => {
-                if info.vid == 0x0403 && info.pid == 0x6001 {
-                    Some(p.port_name.clone())
-                } else {
-                    None
-                }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn find_usb_devices() -> Result<Vec<String>, EverdriveError> {
+        let devices = Self::find_usb_devices_matching(&UsbDeviceRule::defaults())?;
+
+        Ok(devices.into_iter().map(|device| device.port_name).collect())
+    }
+
+    /// Probes the USB ports found by `find_usb_devices` with the status handshake and returns the first
+    /// device which responds, so applications don't have to pick a port themselves. Ports which fail to
+    /// open or don't answer the handshake, such as other FTDI devices, are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::detect().unwrap();
+    ///
+    /// ed.ed_status().unwrap();
+    /// ```
+    #[cfg(all(feature = "edos", not(target_arch = "wasm32")))]
+    pub fn detect() -> std::io::Result<Self> {
+        let ports = Self::find_usb_devices()?;
+
+        for port_name in ports {
+            let Ok(mut ed) = Self::new(&port_name) else {
+                continue;
+            };
+
+            if ed.ed_status().is_ok() {
+                return Ok(ed);
             }
-            _ => None,
-        });
 
-        ed_device_ports.collect()
+            trace::trace_event!(debug, port_name, "port did not answer the handshake");
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "No responding Everdrive device found",
+        ))
     }
 }
@@ -0,0 +1,194 @@
+use crate::clock::Stopwatch;
+use crate::unf::UnfHandlers;
+
+/// Severity parsed from a `[LEVEL]` prefix at the start of a log line
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix.to_ascii_uppercase().as_str() {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" | "WARNING" => Some(LogLevel::Warn),
+            "ERROR" | "ERR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Single line of console output
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    /// Time since the start of the session
+    pub elapsed: std::time::Duration,
+    pub time: std::time::SystemTime,
+    /// Level from a `[LEVEL]` prefix, which is removed from `message`
+    pub level: Option<LogLevel>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{:>10.3}] ", self.elapsed.as_secs_f64())?;
+
+        match self.level {
+            Some(level) => write!(f, "{:<5} ", format!("{:?}", level).to_uppercase())?,
+            None => write!(f, "{:<5} ", "")?,
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Collects console text output into timestamped log lines, optionally streaming them to a writer.
+///
+/// Text packets are split into lines, partial lines are buffered until the rest of the line arrives.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{CancellationToken, Everdrive, LogLevel, LogSession};
+/// use std::fs::File;
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// let mut session = LogSession::new().with_writer(File::create("console.log").unwrap());
+///
+/// ed.unf_listen(session.handlers(), &CancellationToken::new()).unwrap();
+/// session.finish().unwrap();
+///
+/// let errors = session.entries().iter().filter(|e| e.level == Some(LogLevel::Error)).count();
+/// println!("{} errors", errors);
+/// ```
+pub struct LogSession<'a> {
+    start: Stopwatch,
+    entries: Vec<LogEntry>,
+    partial: String,
+    writer: Option<Box<dyn std::io::Write + 'a>>,
+    write_error: Option<std::io::Error>,
+}
+
+impl Default for LogSession<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> LogSession<'a> {
+    pub fn new() -> Self {
+        Self {
+            start: Stopwatch::start(),
+            entries: Vec::new(),
+            partial: String::new(),
+            writer: None,
+            write_error: None,
+        }
+    }
+
+    /// Writes every log line to `writer` as it is completed
+    pub fn with_writer(mut self, writer: impl std::io::Write + 'a) -> Self {
+        self.writer = Some(Box::new(writer));
+        self
+    }
+
+    /// Adds console text to the session
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::{LogLevel, LogSession};
+    ///
+    /// let mut session = LogSession::new();
+    /// session.push_text("[ERROR] out of ");
+    /// session.push_text("memory\nboot ok\n");
+    ///
+    /// assert_eq!(session.entries()[0].level, Some(LogLevel::Error));
+    /// assert_eq!(session.entries()[0].message, "out of memory");
+    /// assert_eq!(session.entries()[1].level, None);
+    /// ```
+    pub fn push_text(&mut self, text: &str) {
+        self.partial.push_str(text);
+
+        while let Some(end) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=end).collect();
+            self.push_line(line.trim_end_matches(['\r', '\n']));
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        let (level, message) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .and_then(|(prefix, message)| {
+                Some((LogLevel::from_prefix(prefix)?, message.trim_start()))
+            })
+            .map_or((None, line), |(level, message)| (Some(level), message));
+
+        let entry = LogEntry {
+            elapsed: self.start.elapsed().unwrap_or_default(),
+            time: std::time::SystemTime::now(),
+            level,
+            message: message.to_string(),
+        };
+
+        if let Some(writer) = self.writer.as_mut()
+            && self.write_error.is_none()
+            && let Err(err) = writeln!(writer, "{}", entry)
+        {
+            // Keep collecting entries, the error is reported by `finish`
+            self.write_error = Some(err);
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// Returns handlers for `unf_listen` which add text packets to the session
+    pub fn handlers(&mut self) -> UnfHandlers<'_> {
+        UnfHandlers::new().on_text(|text| self.push_text(text))
+    }
+
+    /// Returns the log lines collected so far
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Removes and returns the log lines collected so far
+    pub fn take_entries(&mut self) -> Vec<LogEntry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Completes a trailing partial line, flushes the writer and returns the first write error, if any
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.push_line(&line);
+        }
+
+        if let Some(err) = self.write_error.take() {
+            return Err(err);
+        }
+
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for LogSession<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogSession")
+            .field("start", &self.start)
+            .field("entries", &self.entries)
+            .field("partial", &self.partial)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,192 @@
+use crate::Everdrive;
+use crate::edos::{EdCommand, ROM_BASE_ADDR, ROM_BASE_ADDR_EMU};
+use crate::rom::RomKind;
+
+/// Purpose of a region of the cartridge address space
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    /// SDRAM holding the rom of N64 programs
+    Rom,
+    /// Part of the SDRAM holding roms for the emulators of the Everdrive menu
+    Emulator,
+    /// Save memory, managed by the Everdrive OS
+    Save,
+}
+
+/// A region of the cartridge address space
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub kind: RegionKind,
+    pub start: u32,
+    pub size: u32,
+    /// Whether the rom commands may write to the region
+    pub writable: bool,
+}
+
+impl MemoryRegion {
+    pub fn new(name: impl Into<String>, kind: RegionKind, start: u32, size: u32) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            start,
+            size,
+            writable: kind != RegionKind::Save,
+        }
+    }
+
+    /// Returns true if `len` bytes at `addr` lie within the region
+    pub fn contains(&self, addr: u32, len: u32) -> bool {
+        let end = self.start as u64 + self.size as u64;
+        addr >= self.start && addr as u64 + len as u64 <= end
+    }
+}
+
+/// Layout of the cartridge address space of a cart model: where roms are loaded and which regions
+/// the rom commands may access. Rom reads, writes and fills outside of the regions return an
+/// `InvalidInput` error before anything is sent.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::{MemoryMap, MemoryRegion, RegionKind};
+///
+/// let map = MemoryMap::everdrive64();
+///
+/// assert_eq!(map.rom_base(), 0x10000000);
+/// assert!(map.check_write(0x13FFFE00, 512).is_ok());
+/// assert!(map.check_write(0x13FFFE00, 1024).is_err());
+///
+/// // A hypothetical revision with 128MB of SDRAM
+/// let map = MemoryMap::new()
+///     .with_region(MemoryRegion::new("sdram", RegionKind::Rom, 0x10000000, 0x8000000))
+///     .with_region(MemoryRegion::new("emulator", RegionKind::Emulator, 0x10200000, 0x7E00000));
+///
+/// assert!(map.check_write(0x17FFFE00, 512).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryMap {
+    regions: Vec<MemoryRegion>,
+}
+
+impl MemoryMap {
+    /// Creates an empty map, regions are added with `with_region`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Memory map of the Everdrive 64 X-series and V3 with 64MB of SDRAM. Used by default.
+    pub fn everdrive64() -> Self {
+        const SDRAM_SIZE: u32 = 0x4000000;
+
+        Self::new()
+            .with_region(MemoryRegion::new(
+                "sdram",
+                RegionKind::Rom,
+                ROM_BASE_ADDR,
+                SDRAM_SIZE,
+            ))
+            .with_region(MemoryRegion::new(
+                "emulator",
+                RegionKind::Emulator,
+                ROM_BASE_ADDR_EMU,
+                ROM_BASE_ADDR + SDRAM_SIZE - ROM_BASE_ADDR_EMU,
+            ))
+            .with_region(MemoryRegion::new(
+                "sram",
+                RegionKind::Save,
+                0x08000000,
+                0x20000,
+            ))
+    }
+
+    pub fn with_region(mut self, region: MemoryRegion) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// Returns the first region of `kind`
+    pub fn region(&self, kind: RegionKind) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.kind == kind)
+    }
+
+    /// Address N64 roms are loaded to, the start of the first `Rom` region or `ROM_BASE_ADDR`
+    pub fn rom_base(&self) -> u32 {
+        self.region(RegionKind::Rom)
+            .map_or(ROM_BASE_ADDR, |region| region.start)
+    }
+
+    /// Address emulator roms are loaded to, the start of the first `Emulator` region or
+    /// `ROM_BASE_ADDR_EMU`
+    pub fn emulator_base(&self) -> u32 {
+        self.region(RegionKind::Emulator)
+            .map_or(ROM_BASE_ADDR_EMU, |region| region.start)
+    }
+
+    /// Address a rom of `kind` is loaded to by default. Emulator roms are loaded behind the emulator,
+    /// which the Everdrive menu places at the start of the rom space.
+    pub fn base_address(&self, kind: RomKind) -> u32 {
+        match kind.is_emulated() {
+            true => self.emulator_base(),
+            false => self.rom_base(),
+        }
+    }
+
+    fn check(&self, addr: u32, len: u32, write: bool) -> std::io::Result<()> {
+        if self
+            .regions
+            .iter()
+            .any(|region| (region.writable || !write) && region.contains(addr, len))
+        {
+            return Ok(());
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "{} of {:#X} bytes at {:#010X} is outside the {}regions of the memory map",
+                if write { "Write" } else { "Read" },
+                len,
+                addr,
+                if write { "writable " } else { "" }
+            ),
+        ))
+    }
+
+    /// Returns an `InvalidInput` error unless `len` bytes at `addr` lie within a writable region
+    pub fn check_write(&self, addr: u32, len: u32) -> std::io::Result<()> {
+        self.check(addr, len, true)
+    }
+
+    /// Returns an `InvalidInput` error unless `len` bytes at `addr` lie within a region
+    pub fn check_read(&self, addr: u32, len: u32) -> std::io::Result<()> {
+        self.check(addr, len, false)
+    }
+}
+
+impl Everdrive {
+    /// Sets the memory map used for default load addresses and for validating rom commands.
+    /// Defaults to `MemoryMap::everdrive64()`.
+    pub fn set_memory_map(&mut self, map: MemoryMap) {
+        self.memory_map = map;
+    }
+
+    pub fn memory_map(&self) -> &MemoryMap {
+        &self.memory_map
+    }
+
+    /// Validates the address range of rom commands against the memory map
+    pub(crate) fn memory_map_check(&self, cmd: &EdCommand) -> std::io::Result<()> {
+        match *cmd {
+            EdCommand::RomWrite(addr, size) | EdCommand::RomFill(addr, size, _) => {
+                self.memory_map.check_write(addr, size)
+            }
+            EdCommand::RomRead(addr, size) => self.memory_map.check_read(addr, size),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{CancellationToken, Everdrive};
+
+/// A change of a watched memory region between two polls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub addr: u32,
+    /// Contents at the previous poll, or `None` on the first poll
+    pub old: Option<Vec<u8>>,
+    pub new: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Watch {
+    addr: u32,
+    len: usize,
+    last: Option<Vec<u8>>,
+}
+
+/// Polls registered regions of console memory and reports their changes, like the RAM watch of an
+/// emulator. Memory is read with `ed_ram_read`, which requires a GDB stub on the console.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{CancellationToken, Everdrive, MemoryWatcher};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// let watcher = MemoryWatcher::new(std::time::Duration::from_millis(100))
+///     .watch(0x8033B21D, 1)
+///     .watch(0x8033B218, 2);
+///
+/// ed.watch_memory(
+///     watcher,
+///     |change| println!("{:08X}: {:02X?} -> {:02X?}", change.addr, change.old, change.new),
+///     &CancellationToken::new(),
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MemoryWatcher {
+    watches: Vec<Watch>,
+    interval: std::time::Duration,
+}
+
+impl MemoryWatcher {
+    /// Creates a watcher polling every `interval`
+    pub fn new(interval: std::time::Duration) -> Self {
+        Self {
+            watches: Vec::new(),
+            interval,
+        }
+    }
+
+    /// Registers `len` bytes at `addr` to be watched
+    pub fn watch(mut self, addr: u32, len: usize) -> Self {
+        self.watches.push(Watch {
+            addr,
+            len,
+            last: None,
+        });
+        self
+    }
+
+    /// Reads all watched regions once and returns those which changed since the previous poll.
+    /// Every region is reported on the first poll.
+    pub fn poll(&mut self, ed: &mut Everdrive) -> std::io::Result<Vec<MemoryChange>> {
+        let mut changes = Vec::new();
+
+        for watch in &mut self.watches {
+            let data = ed.ed_ram_read(watch.addr, watch.len)?;
+
+            if watch.last.as_ref() != Some(&data) {
+                changes.push(MemoryChange {
+                    addr: watch.addr,
+                    old: watch.last.replace(data.clone()),
+                    new: data,
+                });
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+impl Everdrive {
+    /// Polls the regions of `watcher` at its interval and calls `on_change` for every change until
+    /// `stop` is cancelled or reading fails
+    pub fn watch_memory(
+        &mut self,
+        mut watcher: MemoryWatcher,
+        mut on_change: impl FnMut(&MemoryChange),
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        while !stop.is_cancelled() {
+            for change in watcher.poll(self)? {
+                on_change(&change);
+            }
+
+            crate::clock::sleep(watcher.interval);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use crate::Everdrive;
+use crate::clock::Stopwatch;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Liveness of the console as seen by a `HeartbeatMonitor`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HeartbeatStatus {
+    /// No heartbeat has been received yet
+    Waiting,
+    /// The last heartbeat was received within the timeout
+    Alive,
+    /// No heartbeat has been received for the given time, longer than the timeout
+    Stalled(std::time::Duration),
+}
+
+#[derive(Debug, Default)]
+struct HeartbeatState {
+    last: Option<Stopwatch>,
+    stalled: bool,
+}
+
+/// Shared handle through which `Everdrive` reports received heartbeats to a monitor
+#[derive(Debug, Clone)]
+pub(crate) struct HeartbeatTracker(Arc<Mutex<HeartbeatState>>);
+
+impl HeartbeatTracker {
+    pub(crate) fn beat(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.last = Some(Stopwatch::start());
+        state.stalled = false;
+    }
+}
+
+/// Watches the interval between UNF heartbeats in a background thread and calls a handler when the
+/// console stops sending them, to detect crashes and hangs which produce no output.
+///
+/// Heartbeats are recorded while packets are received through the `Everdrive` the monitor is attached to,
+/// so a receive loop such as `unf_listen` has to keep running. The monitor only starts checking after the
+/// first heartbeat, since roms without heartbeat support never send one.
+///
+/// The background thread is stopped when the monitor is dropped.
+#[derive(Debug)]
+pub struct HeartbeatMonitor {
+    tracker: HeartbeatTracker,
+    timeout: std::time::Duration,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl HeartbeatMonitor {
+    /// Starts a monitor which calls `on_stall` with the time since the last heartbeat once no heartbeat
+    /// has been received for `timeout`. The handler is called again for every later stall after heartbeats
+    /// resume.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive, HeartbeatMonitor, UnfHandlers};
+    /// use std::time::Duration;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let stop = CancellationToken::new();
+    /// let monitor_stop = stop.clone();
+    ///
+    /// let monitor = HeartbeatMonitor::new(Duration::from_secs(5), move |elapsed| {
+    ///     eprintln!("Console stopped responding {:?} ago", elapsed);
+    ///     monitor_stop.cancel();
+    /// });
+    ///
+    /// ed.unf_set_heartbeat_monitor(Some(&monitor));
+    /// ed.unf_listen(UnfHandlers::new().on_text(|text| print!("{}", text)), &stop).unwrap();
+    /// ```
+    pub fn new(
+        timeout: std::time::Duration,
+        mut on_stall: impl FnMut(std::time::Duration) + Send + 'static,
+    ) -> Self {
+        let tracker = HeartbeatTracker(Arc::new(Mutex::new(HeartbeatState::default())));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let poll_interval = (timeout / 4).max(std::time::Duration::from_millis(10));
+
+        let thread_tracker = tracker.clone();
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                let stalled_for = {
+                    let mut state = thread_tracker.0.lock().unwrap();
+
+                    match state.last.map(|last| last.elapsed().unwrap_or_default()) {
+                        Some(elapsed) if elapsed > timeout && !state.stalled => {
+                            state.stalled = true;
+                            Some(elapsed)
+                        }
+                        _ => None,
+                    }
+                };
+
+                // Called without holding the lock, so the handler may query the monitor
+                if let Some(elapsed) = stalled_for {
+                    on_stall(elapsed);
+                }
+
+                std::thread::sleep(poll_interval);
+            }
+        });
+
+        Self {
+            tracker,
+            timeout,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the current liveness of the console
+    pub fn status(&self) -> HeartbeatStatus {
+        let state = self.tracker.0.lock().unwrap();
+
+        match state.last.map(|last| last.elapsed().unwrap_or_default()) {
+            None => HeartbeatStatus::Waiting,
+            Some(elapsed) if elapsed > self.timeout => HeartbeatStatus::Stalled(elapsed),
+            Some(_) => HeartbeatStatus::Alive,
+        }
+    }
+}
+
+impl Drop for HeartbeatMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Everdrive {
+    /// Attaches a heartbeat monitor, which is notified of every UNF heartbeat received from now on.
+    /// Pass `None` to detach it.
+    pub fn unf_set_heartbeat_monitor(&mut self, monitor: Option<&HeartbeatMonitor>) {
+        self.heartbeat_tracker = monitor.map(|monitor| monitor.tracker.clone());
+    }
+}
@@ -0,0 +1,343 @@
+//! Rom patch formats, applied in memory before a rom is uploaded.
+//!
+//! Patches are applied to the rom in big-endian (z64) byte order, which romhacks are distributed for.
+
+#[cfg(feature = "edos")]
+use crate::Everdrive;
+
+const IPS_MAGIC: &[u8; 5] = b"PATCH";
+const IPS_EOF: &[u8; 3] = b"EOF";
+
+const BPS_MAGIC: &[u8; 4] = b"BPS1";
+const BPS_FOOTER_SIZE: usize = 12;
+
+/// Largest target size accepted from a BPS patch, the 64MB of the largest cartridge
+const BPS_MAX_TARGET_SIZE: usize = 0x4000000;
+
+/// Checksum of a BPS patch which did not match
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BpsChecksum {
+    /// The rom the patch was applied to is not the rom the patch was made for
+    Source,
+    /// The patched rom does not match, the patch is corrupt
+    Target,
+    /// The patch file itself is corrupt
+    Patch,
+}
+
+/// Error payload returned by `apply_bps` when a checksum declared in the patch does not match.
+/// Available through `std::io::Error::get_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BpsCrcMismatch {
+    pub checksum: BpsChecksum,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl std::fmt::Display for BpsCrcMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BPS {:?} CRC32 mismatch, expected {:08X} got {:08X}",
+            self.checksum, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for BpsCrcMismatch {}
+
+fn invalid_patch(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads `len` bytes at `*pos` of a patch and advances the position
+fn take<'a>(patch: &'a [u8], pos: &mut usize, len: usize) -> std::io::Result<&'a [u8]> {
+    let bytes = patch
+        .get(*pos..*pos + len)
+        .ok_or_else(|| invalid_patch("Patch is truncated"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+fn be_uint(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as usize)
+}
+
+/// Applies an IPS patch to `rom`. Records past the end of the rom extend it, with any gap filled
+/// with zeros, and a truncation offset after the `EOF` marker shrinks the rom to that size.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::patch;
+///
+/// let mut rom = vec![0; 8];
+/// let ips = b"PATCH\x00\x00\x02\x00\x02\xAB\xCD\x00\x00\x0A\x00\x00\x00\x03\xEEEOF";
+///
+/// patch::apply_ips(&mut rom, ips).unwrap();
+///
+/// assert_eq!(rom, [0, 0, 0xAB, 0xCD, 0, 0, 0, 0, 0, 0, 0xEE, 0xEE, 0xEE]);
+/// ```
+pub fn apply_ips(rom: &mut Vec<u8>, patch: &[u8]) -> std::io::Result<()> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(invalid_patch("Not an IPS patch"));
+    }
+
+    let mut pos = IPS_MAGIC.len();
+
+    loop {
+        let offset = take(patch, &mut pos, 3)?;
+
+        if offset == IPS_EOF {
+            break;
+        }
+
+        let offset = be_uint(offset);
+        let size = be_uint(take(patch, &mut pos, 2)?);
+
+        // Size 0 marks a run-length encoded record
+        let (len, data) = if size == 0 {
+            let count = be_uint(take(patch, &mut pos, 2)?);
+            let value = take(patch, &mut pos, 1)?;
+            (count, std::iter::repeat_n(value[0], count).collect())
+        } else {
+            (size, take(patch, &mut pos, size)?.to_vec())
+        };
+
+        if rom.len() < offset + len {
+            rom.resize(offset + len, 0);
+        }
+
+        rom[offset..offset + len].copy_from_slice(&data);
+    }
+
+    if let Some(truncate) = patch.get(pos..pos + 3) {
+        rom.truncate(be_uint(truncate));
+    }
+
+    Ok(())
+}
+
+/// Reads a BPS variable length number at `*pos` and advances the position
+fn bps_number(patch: &[u8], pos: &mut usize) -> std::io::Result<usize> {
+    let mut value = 0usize;
+    let mut shift = 1usize;
+
+    let overflow = || invalid_patch("BPS number overflows");
+
+    loop {
+        let byte = take(patch, pos, 1)?[0] as usize;
+        value = (byte & 0x7F)
+            .checked_mul(shift)
+            .and_then(|digit| value.checked_add(digit))
+            .ok_or_else(overflow)?;
+
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+
+        shift = shift.checked_mul(1 << 7).ok_or_else(overflow)?;
+        value = value.checked_add(shift).ok_or_else(overflow)?;
+    }
+}
+
+/// Reads a signed BPS copy offset and applies it to `offset`
+fn bps_relative(patch: &[u8], pos: &mut usize, offset: usize) -> std::io::Result<usize> {
+    let value = bps_number(patch, pos)?;
+    let delta = value >> 1;
+
+    if value & 1 != 0 {
+        offset.checked_sub(delta)
+    } else {
+        offset.checked_add(delta)
+    }
+    .ok_or_else(|| invalid_patch("BPS copy offset out of range"))
+}
+
+fn bps_check(checksum: BpsChecksum, expected: &[u8], data: &[u8]) -> std::io::Result<()> {
+    let expected = u32::from_le_bytes(expected.try_into().unwrap());
+    let actual = crate::rom::crc32(data);
+
+    if expected != actual {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            BpsCrcMismatch {
+                checksum,
+                expected,
+                actual,
+            },
+        ));
+    }
+
+    Ok(())
+}
+
+/// Applies a BPS patch to `source` and returns the patched rom.
+///
+/// The source, target and patch CRC32s declared in the patch are verified. A mismatch is returned as an
+/// `InvalidData` error carrying a `BpsCrcMismatch`, a source mismatch usually means the patch was made for
+/// a different revision or byte order of the rom.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::patch::{self, BpsChecksum, BpsCrcMismatch};
+/// use std::fs;
+///
+/// let rom_data = fs::read("your_rom.z64").unwrap();
+/// let bps = fs::read("romhack.bps").unwrap();
+///
+/// match patch::apply_bps(&rom_data, &bps) {
+///     Ok(patched) => fs::write("romhack.z64", patched).unwrap(),
+///     Err(err) => match err.get_ref().and_then(|e| e.downcast_ref::<BpsCrcMismatch>()) {
+///         Some(mismatch) if mismatch.checksum == BpsChecksum::Source => eprintln!("Wrong rom for this patch"),
+///         _ => eprintln!("Patching failed: {:?}", err),
+///     },
+/// }
+/// ```
+pub fn apply_bps(source: &[u8], patch: &[u8]) -> std::io::Result<Vec<u8>> {
+    if !patch.starts_with(BPS_MAGIC) || patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(invalid_patch("Not a BPS patch"));
+    }
+
+    let footer = &patch[patch.len() - BPS_FOOTER_SIZE..];
+    let actions_end = patch.len() - BPS_FOOTER_SIZE;
+
+    bps_check(
+        BpsChecksum::Patch,
+        &footer[8..12],
+        &patch[..patch.len() - 4],
+    )?;
+    bps_check(BpsChecksum::Source, &footer[0..4], source)?;
+
+    let mut pos = BPS_MAGIC.len();
+    let source_size = bps_number(patch, &mut pos)?;
+    let target_size = bps_number(patch, &mut pos)?;
+    let metadata_size = bps_number(patch, &mut pos)?;
+    take(patch, &mut pos, metadata_size)?;
+
+    if source_size != source.len() {
+        return Err(invalid_patch("BPS source size does not match the rom"));
+    }
+
+    // A short patch can copy its own output up to any declared size, so it must fit a cartridge
+    if target_size > BPS_MAX_TARGET_SIZE {
+        return Err(invalid_patch(
+            "BPS target size is larger than a cartridge rom",
+        ));
+    }
+
+    // The declared size is only a hint until the actions have produced it
+    let mut target = Vec::with_capacity(target_size.min(source.len() + patch.len()));
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+
+    while pos < actions_end {
+        let action = bps_number(patch, &mut pos)?;
+        let len = (action >> 2) + 1;
+        let out = target.len();
+
+        if out.checked_add(len).is_none_or(|end| end > target_size) {
+            return Err(invalid_patch("BPS patch writes past the target size"));
+        }
+
+        match action & 3 {
+            // SourceRead
+            0 => target.extend_from_slice(
+                source
+                    .get(out..out + len)
+                    .ok_or_else(|| invalid_patch("BPS read past the end of the source"))?,
+            ),
+            // TargetRead
+            1 => target.extend_from_slice(take(patch, &mut pos, len)?),
+            // SourceCopy
+            2 => {
+                source_offset = bps_relative(patch, &mut pos, source_offset)?;
+                target.extend_from_slice(
+                    source
+                        .get(source_offset..source_offset.saturating_add(len))
+                        .ok_or_else(|| invalid_patch("BPS copy past the end of the source"))?,
+                );
+                source_offset += len;
+            }
+            // TargetCopy, byte by byte since the copy may overlap its own output
+            _ => {
+                target_offset = bps_relative(patch, &mut pos, target_offset)?;
+
+                for _ in 0..len {
+                    let byte = *target
+                        .get(target_offset)
+                        .ok_or_else(|| invalid_patch("BPS copy past the end of the target"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(invalid_patch(
+            "BPS patch does not produce the declared target size",
+        ));
+    }
+
+    bps_check(BpsChecksum::Target, &footer[4..8], &target)?;
+
+    Ok(target)
+}
+
+/// Applies an IPS or BPS patch to `rom`, detecting the format from the patch header
+pub fn apply_patch(rom: &mut Vec<u8>, patch: &[u8]) -> std::io::Result<()> {
+    if patch.starts_with(BPS_MAGIC) {
+        *rom = apply_bps(rom, patch)?;
+        Ok(())
+    } else {
+        apply_ips(rom, patch)
+    }
+}
+
+#[cfg(feature = "edos")]
+impl Everdrive {
+    /// Applies an IPS or BPS patch to a rom in memory and loads the patched rom like `ed_load_rom_auto`.
+    ///
+    /// The rom is converted to big-endian byte order before patching, so patches apply
+    /// regardless of the byte order the rom is stored in. Patched roms which are not a whole number
+    /// of 512 byte blocks are padded as configured with `set_rom_padding`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    /// let patch = fs::read("translation.bps").unwrap();
+    ///
+    /// ed.ed_load_rom_patched(rom_data, &patch, None).unwrap();
+    /// ed.ed_app_start(Some("your_rom.z64")).unwrap();
+    /// ```
+    pub fn ed_load_rom_patched(
+        &mut self,
+        rom_file: impl Into<Vec<u8>>,
+        patch: &[u8],
+        base_address: Option<u32>,
+    ) -> std::io::Result<crate::RomLoadReport> {
+        let mut rom_file = rom_file.into();
+
+        if rom_file.len() < 0x40 {
+            return Err(invalid_patch("Rom file is too small"));
+        }
+
+        let header_word_be =
+            u32::from_be_bytes([rom_file[0], rom_file[1], rom_file[2], rom_file[3]]);
+        crate::rom::rom_swap_to_native(header_word_be, &mut rom_file);
+
+        apply_patch(&mut rom_file, patch)?;
+
+        self.ed_load_rom_auto(rom_file, base_address)
+    }
+}
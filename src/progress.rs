@@ -0,0 +1,127 @@
+#[cfg(any(feature = "edos", feature = "unf"))]
+use crate::Everdrive;
+
+/// Long running operation a `Progress` event belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProgressOperation {
+    /// Rom upload by `ed_load_rom` and the other rom loading functions
+    RomUpload,
+    /// FPGA configuration by `ed_fpga_init`
+    FpgaInit,
+    /// Host file sent to the console by `unf_send_file`
+    FileTransfer,
+    /// Save state downloaded from the console by `state_save`
+    SaveStateBackup,
+    /// Save state uploaded to the console by `state_load`
+    SaveStateRestore,
+}
+
+/// Stage of an operation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProgressPhase {
+    Started,
+    /// Sent after every transferred chunk
+    Transferring,
+    /// The operation completed successfully, `bytes_done` equals `bytes_total` if it is known.
+    /// Failed operations end without this phase and return their error instead.
+    Finished,
+}
+
+/// Progress of a long running operation, passed to `EverdriveEvents::on_progress` so one progress
+/// bar implementation works for every operation. SD card transfers are not available over USB, see
+/// the limitations in the readme.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Everdrive, EverdriveEvents, Progress, ProgressPhase};
+///
+/// #[derive(Debug)]
+/// struct ProgressBar;
+///
+/// impl EverdriveEvents for ProgressBar {
+///     fn on_progress(&mut self, progress: &Progress) {
+///         match (progress.phase, progress.fraction()) {
+///             (ProgressPhase::Finished, _) => println!("{:?} done", progress.operation),
+///             (_, Some(fraction)) => println!("{:?} {:.0}%", progress.operation, fraction * 100.0),
+///             (_, None) => println!("{:?} {} bytes", progress.operation, progress.bytes_done),
+///         }
+///     }
+/// }
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// ed.set_events(Some(Box::new(ProgressBar)));
+/// ed.ed_load_rom(std::fs::read("your_rom.z64").unwrap(), None, None, None).unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Progress {
+    pub operation: ProgressOperation,
+    pub phase: ProgressPhase,
+    pub bytes_done: u64,
+    /// Total size of the transfer, `None` if it is not known up front such as for streamed roms
+    pub bytes_total: Option<u64>,
+}
+
+impl Progress {
+    /// Completed fraction from 0.0 to 1.0, or `None` if the total size is not known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.bytes_total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes_done as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "edos", feature = "unf"))]
+impl Everdrive {
+    /// Runs `op` as `operation`, sending the `Started` and `Finished` events around it. Operations
+    /// started inside `op` report their chunks as part of the outer operation.
+    pub(crate) fn with_progress<T>(
+        &mut self,
+        operation: ProgressOperation,
+        bytes_total: Option<u64>,
+        op: impl FnOnce(&mut Self) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        if self.progress.is_some() {
+            return op(self);
+        }
+
+        self.progress = Some(Progress {
+            operation,
+            phase: ProgressPhase::Started,
+            bytes_done: 0,
+            bytes_total,
+        });
+        self.progress_emit();
+
+        let result = op(self);
+
+        if let Some(mut progress) = self.progress.take()
+            && result.is_ok()
+        {
+            progress.phase = ProgressPhase::Finished;
+            // Blocks skipped by differential uploads count as done
+            progress.bytes_done = progress.bytes_total.unwrap_or(progress.bytes_done);
+            self.emit_event(|events| events.on_progress(&progress));
+        }
+
+        result
+    }
+
+    /// Adds `len` transferred bytes to the running operation, if any
+    pub(crate) fn progress_advance(&mut self, len: usize) {
+        if let Some(progress) = &mut self.progress {
+            progress.phase = ProgressPhase::Transferring;
+            progress.bytes_done += len as u64;
+            self.progress_emit();
+        }
+    }
+
+    fn progress_emit(&mut self) {
+        if let Some(progress) = self.progress {
+            self.emit_event(|events| events.on_progress(&progress));
+        }
+    }
+}
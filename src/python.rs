@@ -0,0 +1,227 @@
+//! Python bindings, exposed as the `pyeverdrive` module.
+//!
+//! Build the extension with maturin or `cargo rustc --release --features python,pyo3/extension-module
+//! --crate-type cdylib` and rename the library to `pyeverdrive.so` (`pyeverdrive.pyd` on Windows).
+//! IO errors are raised as `TimeoutError`, `FileNotFoundError` or `OSError` with the error message.
+//! Cancelled transfers raise `pyeverdrive.CancelledError`, commands the session can't serve raise
+//! `pyeverdrive.SessionError` and invalid data raises `pyeverdrive.InvalidDataError`, a `ValueError`.
+//!
+//! `load_rom` and `run_rom` return the load report as a dict with `kind`, `base_address`, `size`,
+//! `crc32` and `save_type`, and call the optional `progress` callable with the bytes uploaded so far
+//! and the total size.
+//!
+//! ```python
+//! import pyeverdrive
+//!
+//! ed = pyeverdrive.Everdrive()
+//! report = ed.run_rom("your_rom.z64", progress=lambda done, total: print(done, "/", total))
+//! print(report["kind"], hex(report["crc32"]))
+//!
+//! while True:
+//!     packet = ed.unf_receive()
+//!     if packet is not None and packet[0] == pyeverdrive.DATATYPE_TEXT:
+//!         print(packet[1].decode(), end="")
+//! ```
+
+use crate::unf::UnfSendPacket;
+use crate::{
+    Cancelled, Everdrive, EverdriveEvents, Progress, RomLoadReport, RunOptions, UnfDataType,
+};
+use pyo3::exceptions::{PyException, PyFileNotFoundError, PyOSError, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+pyo3::create_exception!(
+    pyeverdrive,
+    CancelledError,
+    PyException,
+    "The transfer was cancelled"
+);
+pyo3::create_exception!(
+    pyeverdrive,
+    SessionError,
+    PyException,
+    "The command is not available in the current session state"
+);
+pyo3::create_exception!(
+    pyeverdrive,
+    InvalidDataError,
+    PyValueError,
+    "The rom, patch or received data is invalid"
+);
+
+fn py_err(err: std::io::Error) -> PyErr {
+    if Cancelled::is(&err) {
+        return CancelledError::new_err(err.to_string());
+    }
+
+    if err.get_ref().is_some_and(|e| e.is::<crate::SessionError>()) {
+        return SessionError::new_err(err.to_string());
+    }
+
+    match err.kind() {
+        std::io::ErrorKind::TimedOut => PyTimeoutError::new_err(err.to_string()),
+        std::io::ErrorKind::NotFound => PyFileNotFoundError::new_err(err.to_string()),
+        std::io::ErrorKind::InvalidData => InvalidDataError::new_err(err.to_string()),
+        _ => PyOSError::new_err(err.to_string()),
+    }
+}
+
+/// Forwards progress events to a Python callable, errors it raises are reported as unraisable
+#[derive(Debug)]
+struct PyProgress(Py<PyAny>);
+
+impl EverdriveEvents for PyProgress {
+    fn on_progress(&mut self, progress: &Progress) {
+        Python::with_gil(|py| {
+            if let Err(err) = self
+                .0
+                .call1(py, (progress.bytes_done, progress.bytes_total))
+            {
+                err.write_unraisable(py, Some(self.0.bind(py)));
+            }
+        });
+    }
+}
+
+fn report_dict<'py>(py: Python<'py>, report: &RomLoadReport) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("kind", format!("{:?}", report.kind))?;
+    dict.set_item("base_address", report.base_address)?;
+    dict.set_item("size", report.size)?;
+    dict.set_item("crc32", report.crc32)?;
+    dict.set_item("save_type", report.save_type.map(|st| st.to_string()))?;
+    Ok(dict)
+}
+
+// The port is not `Sync`, so instances are bound to the Python thread which created them
+#[pyclass(name = "Everdrive", unsendable)]
+struct PyEverdrive {
+    ed: Everdrive,
+}
+
+#[pymethods]
+impl PyEverdrive {
+    /// Opens the device on `port`, or the first responding device if no port is given
+    #[new]
+    #[pyo3(signature = (port = None))]
+    fn new(port: Option<&str>) -> PyResult<Self> {
+        let ed = match port {
+            Some(port) => Everdrive::new(port),
+            None => Everdrive::detect(),
+        };
+
+        Ok(Self {
+            ed: ed.map_err(py_err)?,
+        })
+    }
+
+    /// Returns the port names of connected devices
+    #[staticmethod]
+    fn find_usb_devices() -> PyResult<Vec<String>> {
+        Everdrive::find_usb_devices().map_err(|err| py_err(err.into()))
+    }
+
+    /// Sets the read and write timeout in seconds
+    fn set_timeout(&mut self, seconds: f64) -> PyResult<()> {
+        self.ed
+            .set_timeout(std::time::Duration::from_secs_f64(seconds))
+            .map_err(py_err)
+    }
+
+    fn status(&mut self) -> PyResult<()> {
+        self.ed.ed_status().map_err(py_err)
+    }
+
+    /// Loads a rom image from bytes, looking up the save type from the built-in game database.
+    /// Returns the load report as a dict.
+    #[pyo3(signature = (rom, base_address = None, progress = None))]
+    fn load_rom<'py>(
+        &mut self,
+        py: Python<'py>,
+        rom: Vec<u8>,
+        base_address: Option<u32>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let report = self.with_progress(progress, |ed| ed.ed_load_rom_auto(rom, base_address))?;
+        report_dict(py, &report)
+    }
+
+    #[pyo3(signature = (save_file_name = None))]
+    fn app_start(&mut self, save_file_name: Option<&str>) -> PyResult<()> {
+        self.ed.ed_app_start(save_file_name).map_err(py_err)
+    }
+
+    /// Loads the rom file at `path` and starts it. Returns the load report as a dict.
+    #[pyo3(signature = (path, progress = None))]
+    fn run_rom<'py>(
+        &mut self,
+        py: Python<'py>,
+        path: &str,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let report = self.with_progress(progress, |ed| {
+            ed.load_and_start(std::path::Path::new(path), &RunOptions::default())
+        })?;
+        report_dict(py, &report)
+    }
+
+    /// Sends a UNF packet of `datatype`
+    fn unf_send(&mut self, datatype: u8, data: &[u8]) -> PyResult<()> {
+        let packet =
+            UnfSendPacket::from_bytes(UnfDataType::from(datatype), data).map_err(py_err)?;
+        self.ed.unf_tx(&packet).map_err(py_err)
+    }
+
+    /// Receives a UNF packet as a `(datatype, data)` tuple, or `None` if none arrived within the timeout
+    fn unf_receive<'py>(&mut self, py: Python<'py>) -> PyResult<Option<(u8, Bound<'py, PyBytes>)>> {
+        match self.ed.unf_rx() {
+            Ok(packet) => Ok(Some((
+                packet.get_datatype().into(),
+                PyBytes::new(py, packet.get_data()),
+            ))),
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => Ok(None),
+            Err(err) => Err(py_err(err)),
+        }
+    }
+}
+
+impl PyEverdrive {
+    /// Runs `f` with `progress` receiving the progress events, if set
+    fn with_progress<T>(
+        &mut self,
+        progress: Option<Py<PyAny>>,
+        f: impl FnOnce(&mut Everdrive) -> std::io::Result<T>,
+    ) -> PyResult<T> {
+        let Some(progress) = progress else {
+            return f(&mut self.ed).map_err(py_err);
+        };
+
+        let previous = self.ed.events.replace(Box::new(PyProgress(progress)));
+        let result = f(&mut self.ed);
+        self.ed.events = previous;
+
+        result.map_err(py_err)
+    }
+}
+
+#[pymodule]
+fn pyeverdrive(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEverdrive>()?;
+    m.add("CancelledError", m.py().get_type::<CancelledError>())?;
+    m.add("SessionError", m.py().get_type::<SessionError>())?;
+    m.add("InvalidDataError", m.py().get_type::<InvalidDataError>())?;
+
+    m.add("DATATYPE_TEXT", u8::from(UnfDataType::DataTypeText))?;
+    m.add("DATATYPE_BINARY", u8::from(UnfDataType::DataTypeBinary))?;
+    m.add(
+        "DATATYPE_SCREENSHOT",
+        u8::from(UnfDataType::DataTypeScreenshot),
+    )?;
+    m.add(
+        "DATATYPE_HEARTBEAT",
+        u8::from(UnfDataType::DataTypeHeartbeat),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+/// Bounds-checked big endian reader over a byte buffer, for parsing UNF and EDOS frames
+/// from captures or packet payloads.
+///
+/// Every read returns an `UnexpectedEof` error instead of panicking when the buffer is too short.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::PacketReader;
+///
+/// let payload = [0x12, 0x34, 0x00, 0x02, b'h', b'i'];
+/// let mut reader = PacketReader::new(&payload);
+///
+/// assert_eq!(reader.consume_half().unwrap(), 0x1234);
+/// assert_eq!(reader.consume_str().unwrap(), "hi");
+/// assert_eq!(reader.remaining(), 0);
+/// assert!(reader.consume_byte().is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketReader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PacketReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    /// Reads the next `n` bytes
+    pub fn consume_bytes(&mut self, n: usize) -> std::io::Result<&'a [u8]> {
+        let bytes = self
+            .offset
+            .checked_add(n)
+            .and_then(|end| self.buf.get(self.offset..end))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Packet too short, needed {} bytes at offset {} of {}",
+                        n,
+                        self.offset,
+                        self.buf.len()
+                    ),
+                )
+            })?;
+
+        self.offset += n;
+        Ok(bytes)
+    }
+
+    pub fn consume_byte(&mut self) -> std::io::Result<u8> {
+        Ok(self.consume_bytes(1)?[0])
+    }
+
+    pub fn consume_half(&mut self) -> std::io::Result<u16> {
+        Ok(u16::from_be_bytes(
+            self.consume_bytes(2)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn consume_word(&mut self) -> std::io::Result<u32> {
+        Ok(u32::from_be_bytes(
+            self.consume_bytes(4)?.try_into().unwrap(),
+        ))
+    }
+
+    /// Reads a UTF-8 string with a `u16` length prefix
+    pub fn consume_str(&mut self) -> std::io::Result<&'a str> {
+        let len = self.consume_half()? as usize;
+
+        std::str::from_utf8(self.consume_bytes(len)?)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn skip(&mut self, n: usize) -> std::io::Result<()> {
+        self.consume_bytes(n).map(|_| ())
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.offset
+    }
+}
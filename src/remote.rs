@@ -0,0 +1,384 @@
+//! Remote procedure calls between host and console over UNF binary packets.
+//!
+//! Calls and replies are `DataTypeBinary` packets starting with the `RP` prefix followed by a
+//! message kind byte and a `u16` message ID. All integers are big-endian.
+//!
+//! | Kind          | Payload after the message ID               |
+//! |---------------|--------------------------------------------|
+//! | `0x01` call   | method name length `u8`, method name, args |
+//! | `0x02` reply  | result                                     |
+//! | `0x03` error  | UTF-8 error message                        |
+//!
+//! Replies and errors repeat the message ID of the call they answer. Either side may send calls,
+//! the IDs of both sides are independent. Arguments and results are opaque bytes, [`PayloadWriter`]
+//! and [`PacketReader`] encode and decode them with big-endian integers and `u16` length prefixed
+//! strings.
+//!
+//! The console side is `include/everdrive_remote.h`, a header only C library on top of UNFLoader's
+//! `usb.h`. Its `rp_write_call`, `rp_write_reply` and `rp_write_error` send messages with
+//! `usb_write`, `rp_read_message` and `rp_read_reply` receive them with `usb_read`, and
+//! `rp_writer`/`rp_reader` mirror [`PayloadWriter`] and [`PacketReader`].
+//!
+//! Malformed messages are logged and dropped, and calls among them are answered with an error
+//! reply, so a bad packet doesn't end `serve` or `call`.
+
+use crate::PacketReader;
+use crate::clock::Stopwatch;
+use crate::trace::trace_event;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+use crate::{CancellationToken, Everdrive};
+
+const RP_PREFIX: &[u8; 2] = b"RP";
+
+const RP_KIND_CALL: u8 = 0x01;
+const RP_KIND_REPLY: u8 = 0x02;
+const RP_KIND_ERROR: u8 = 0x03;
+
+type MethodHandler<'a> = Box<dyn FnMut(&[u8]) -> std::io::Result<Vec<u8>> + 'a>;
+type PacketHandler<'a> = Box<dyn FnMut(&UnfRecvPacket) + 'a>;
+
+/// Builds call arguments and results in the encoding read by [`PacketReader`]
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::PacketReader;
+/// use libeverdrive::remote::PayloadWriter;
+///
+/// let payload = PayloadWriter::new().u32(0x80001000).str("player").into_bytes();
+/// let mut reader = PacketReader::new(&payload);
+///
+/// assert_eq!(reader.consume_word().unwrap(), 0x80001000);
+/// assert_eq!(reader.consume_str().unwrap(), "player");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct PayloadWriter {
+    data: Vec<u8>,
+}
+
+impl PayloadWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(mut self, value: u8) -> Self {
+        self.data.push(value);
+        self
+    }
+
+    pub fn u16(mut self, value: u16) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u32(mut self, value: u32) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    /// Appends raw bytes without a length prefix
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.data.extend_from_slice(value);
+        self
+    }
+
+    /// Appends a string with a `u16` length prefix. Longer strings are truncated to `u16::MAX` bytes.
+    pub fn str(mut self, value: &str) -> Self {
+        let bytes = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+        self.data
+            .extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// A decoded `RP` message
+enum RemoteMessage<'p> {
+    Call(u16, &'p str, &'p [u8]),
+    Reply(u16, &'p [u8]),
+    Error(u16, &'p [u8]),
+}
+
+/// An `RP` message which could not be decoded, with the ID of the call it belongs to if known
+struct MalformedMessage {
+    call_id: Option<u16>,
+}
+
+fn call_timed_out(method: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        format!("No reply to remote call {}", method),
+    )
+}
+
+fn parse_message(packet: &UnfRecvPacket) -> Option<Result<RemoteMessage<'_>, MalformedMessage>> {
+    let data = packet.get_data();
+
+    if packet.get_datatype() != UnfDataType::DataTypeBinary || !data.starts_with(RP_PREFIX) {
+        return None;
+    }
+
+    let mut reader = PacketReader::new(&data[2..]);
+
+    let (Ok(kind), Ok(id)) = (reader.consume_byte(), reader.consume_half()) else {
+        return Some(Err(MalformedMessage { call_id: None }));
+    };
+
+    let message = match kind {
+        RP_KIND_CALL => (|| {
+            let len = reader.consume_byte().ok()? as usize;
+            let method = std::str::from_utf8(reader.consume_bytes(len).ok()?).ok()?;
+            let args = reader.consume_bytes(reader.remaining()).ok()?;
+            Some(RemoteMessage::Call(id, method, args))
+        })()
+        .ok_or(MalformedMessage { call_id: Some(id) }),
+        RP_KIND_REPLY => Ok(RemoteMessage::Reply(id, &data[5..])),
+        RP_KIND_ERROR => Ok(RemoteMessage::Error(id, &data[5..])),
+        _ => Err(MalformedMessage { call_id: None }),
+    };
+
+    Some(message)
+}
+
+fn build_message(
+    kind: u8,
+    id: u16,
+    header: &[u8],
+    payload: &[u8],
+) -> std::io::Result<UnfSendPacket> {
+    let mut packet = UnfSendPacket::new(
+        UnfDataType::DataTypeBinary,
+        5 + header.len() + payload.len(),
+    )?;
+
+    let data = packet.get_data();
+    data[0..2].copy_from_slice(RP_PREFIX);
+    data[2] = kind;
+    data[3..5].copy_from_slice(&id.to_be_bytes());
+    data[5..5 + header.len()].copy_from_slice(header);
+    data[5 + header.len()..].copy_from_slice(payload);
+
+    Ok(packet)
+}
+
+/// Host side of the remote call protocol. Answers calls from the console with registered method
+/// handlers and sends calls to the console.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::remote::{PayloadWriter, RemoteEndpoint};
+/// use libeverdrive::{Everdrive, PacketReader};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// let mut endpoint = RemoteEndpoint::new()
+///     .on_method("host_time", |_args| {
+///         let now = std::time::SystemTime::now()
+///             .duration_since(std::time::UNIX_EPOCH)
+///             .unwrap();
+///         Ok(PayloadWriter::new().u32(now.as_secs() as u32).into_bytes())
+///     })
+///     .on_packet(|packet| println!("{:?}", packet));
+///
+/// let result = endpoint
+///     .call(&mut ed, "get_lives", &PayloadWriter::new().u8(0).into_bytes())
+///     .unwrap();
+///
+/// println!("Lives: {}", PacketReader::new(&result).consume_byte().unwrap());
+/// ```
+pub struct RemoteEndpoint<'a> {
+    methods: std::collections::HashMap<String, MethodHandler<'a>>,
+    packet: Option<PacketHandler<'a>>,
+    next_id: u16,
+    call_timeout: std::time::Duration,
+}
+
+impl Default for RemoteEndpoint<'_> {
+    fn default() -> Self {
+        Self {
+            methods: std::collections::HashMap::new(),
+            packet: None,
+            next_id: 0,
+            call_timeout: std::time::Duration::from_secs(1),
+        }
+    }
+}
+
+impl std::fmt::Debug for RemoteEndpoint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteEndpoint")
+            .field("methods", &self.methods.keys().collect::<Vec<_>>())
+            .field("next_id", &self.next_id)
+            .field("call_timeout", &self.call_timeout)
+            .finish()
+    }
+}
+
+impl<'a> RemoteEndpoint<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for calls to `method` from the console. An error returned by the
+    /// handler is sent to the console as an error reply.
+    pub fn on_method(
+        mut self,
+        method: impl Into<String>,
+        handler: impl FnMut(&[u8]) -> std::io::Result<Vec<u8>> + 'a,
+    ) -> Self {
+        self.methods.insert(method.into(), Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for packets which are not remote call messages
+    pub fn on_packet(mut self, handler: impl FnMut(&UnfRecvPacket) + 'a) -> Self {
+        self.packet = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets how long `call` waits for the console to reply. Defaults to 1 second.
+    pub fn call_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.call_timeout = timeout;
+        self
+    }
+
+    /// Handles a call from the console and sends the reply. Returns false without sending anything
+    /// if the packet is not a remote call message. Malformed messages are dropped, with an error
+    /// reply if they are a call.
+    pub fn handle_packet(
+        &mut self,
+        ed: &mut Everdrive,
+        packet: &UnfRecvPacket,
+    ) -> std::io::Result<bool> {
+        match parse_message(packet) {
+            None => Ok(false),
+            Some(Ok(RemoteMessage::Call(id, method, args))) => {
+                let reply = match self.methods.get_mut(method) {
+                    Some(handler) => handler(args),
+                    None => Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("Unknown method {}", method),
+                    )),
+                };
+
+                let response = match reply {
+                    Ok(result) => build_message(RP_KIND_REPLY, id, &[], &result)?,
+                    Err(err) => build_message(RP_KIND_ERROR, id, &[], err.to_string().as_bytes())?,
+                };
+
+                ed.unf_tx(&response)?;
+                Ok(true)
+            }
+            // Replies to calls which already timed out are dropped
+            Some(Ok(_)) => Ok(true),
+            Some(Err(MalformedMessage { call_id })) => {
+                trace_event!(warn, ?call_id, "malformed remote call message dropped");
+
+                if let Some(id) = call_id {
+                    ed.unf_tx(&build_message(
+                        RP_KIND_ERROR,
+                        id,
+                        &[],
+                        b"Malformed remote call",
+                    )?)?;
+                }
+
+                Ok(true)
+            }
+        }
+    }
+
+    /// Handles remote call messages and passes other packets to the `on_packet` handler
+    fn dispatch(&mut self, ed: &mut Everdrive, packet: &UnfRecvPacket) -> std::io::Result<()> {
+        if !self.handle_packet(ed, packet)?
+            && let Some(handler) = self.packet.as_mut()
+        {
+            handler(packet);
+        }
+
+        Ok(())
+    }
+
+    /// Calls `method` on the console and waits for its result. Calls from the console and other
+    /// packets received while waiting are handled as in `handle_packet`.
+    ///
+    /// Errors reported by the console are returned as `ErrorKind::Other` with the console's
+    /// message. Returns a `TimedOut` error if no reply arrives within the call timeout.
+    pub fn call(
+        &mut self,
+        ed: &mut Everdrive,
+        method: &str,
+        args: &[u8],
+    ) -> std::io::Result<Vec<u8>> {
+        let name = method.as_bytes();
+
+        if name.len() > u8::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Method name must be at most 255 bytes",
+            ));
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let mut header = vec![name.len() as u8];
+        header.extend_from_slice(name);
+
+        ed.unf_tx(&build_message(RP_KIND_CALL, id, &header, args)?)?;
+
+        let start = Stopwatch::start();
+
+        loop {
+            match ed.unf_rx() {
+                Ok(packet) => match parse_message(&packet) {
+                    Some(Ok(RemoteMessage::Reply(reply_id, result))) if reply_id == id => {
+                        return Ok(result.to_vec());
+                    }
+                    Some(Ok(RemoteMessage::Error(reply_id, message))) if reply_id == id => {
+                        return Err(std::io::Error::other(
+                            String::from_utf8_lossy(message).into_owned(),
+                        ));
+                    }
+                    _ => {
+                        self.dispatch(ed, &packet)?;
+                    }
+                },
+                // Without a clock the first port timeout ends the wait
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => {
+                    if start.elapsed().is_none() {
+                        return Err(call_timed_out(method));
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+
+            if start
+                .elapsed()
+                .is_some_and(|elapsed| elapsed >= self.call_timeout)
+            {
+                return Err(call_timed_out(method));
+            }
+        }
+    }
+
+    /// Answers calls from the console until `stop` is cancelled
+    pub fn serve(&mut self, ed: &mut Everdrive, stop: &CancellationToken) -> std::io::Result<()> {
+        while !stop.is_cancelled() {
+            match ed.unf_rx() {
+                Ok(packet) => {
+                    self.dispatch(ed, &packet)?;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
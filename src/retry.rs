@@ -0,0 +1,106 @@
+use crate::Everdrive;
+
+/// Retry policy for command transmit/receive pairs which fail with transient serial errors,
+/// such as spurious timeouts. Retries wait for an exponentially increasing backoff time.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Everdrive, RetryPolicy};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// ed.set_retry_policy(RetryPolicy {
+///     max_attempts: 5,
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one. 1 disables retrying.
+    pub max_attempts: u32,
+    /// Backoff before the first retry
+    pub initial_backoff: std::time::Duration,
+    /// Upper limit for the backoff, which doubles after every retry
+    pub max_backoff: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: std::time::Duration::from_millis(50),
+            max_backoff: std::time::Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy which never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    fn is_transient(err: &std::io::Error) -> bool {
+        matches!(
+            err.kind(),
+            std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::WouldBlock
+        )
+    }
+}
+
+impl Everdrive {
+    /// Sets the retry policy for commands. Defaults to `RetryPolicy::none()`.
+    ///
+    /// Retried operations are the status handshake, rom reads and rom write chunks. Rom reads and
+    /// writes are only retried while none of their data has been transferred, as the cart would
+    /// otherwise still be sending or consuming data of the failed attempt.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Runs `op`, retrying it according to the retry policy if it fails with a transient error.
+    /// Pending input is discarded before each retry so that a late response is not mistaken for the next one.
+    pub(crate) fn with_retry<T>(
+        &mut self,
+        op: impl FnMut(&mut Self) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        self.with_retry_if(op, || true)
+    }
+
+    /// Like `with_retry`, but a failed attempt is only retried while `retryable` returns true.
+    /// Used by commands which transfer data, which must not be repeated once data has moved.
+    pub(crate) fn with_retry_if<T>(
+        &mut self,
+        mut op: impl FnMut(&mut Self) -> std::io::Result<T>,
+        retryable: impl Fn() -> bool,
+    ) -> std::io::Result<T> {
+        let mut backoff = self.retry_policy.initial_backoff;
+        let mut attempt = 1;
+
+        loop {
+            match op(self) {
+                Err(err)
+                    if RetryPolicy::is_transient(&err)
+                        && retryable()
+                        && attempt < self.retry_policy.max_attempts =>
+                {
+                    self.check_cancelled()?;
+
+                    crate::clock::sleep(backoff);
+                    backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+                    attempt += 1;
+
+                    self.emit_event(|events| events.on_retry(attempt, &err));
+                    self.port.clear_input()?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,700 @@
+//! Utilities for working with N64 rom images without a device connection.
+//!
+//! All functions expect the rom to be in big-endian (z64) byte order.
+
+#[cfg(feature = "edos")]
+use crate::EdSaveType;
+
+pub use crate::byteswap::ByteOrder;
+
+const CRC_START: usize = 0x1000;
+const CRC_LENGTH: usize = 0x100000;
+
+/// Size of the rom image covered by the checksums, smaller roms are padded to it
+pub(crate) const CRC_AREA_SIZE: usize = CRC_START + CRC_LENGTH;
+
+const CRC1_OFFSET: usize = 0x10;
+const CRC2_OFFSET: usize = 0x14;
+
+#[cfg(feature = "edos")]
+const GAME_ID_OFFSET: usize = 0x3C;
+
+const IPL3_START: usize = 0x40;
+const IPL3_END: usize = 0x1000;
+
+/// CIC lockout chip variants, which determine the seed and algorithm for the rom checksums
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CicType {
+    Cic6101,
+    Cic7102,
+    Cic6102,
+    Cic6103,
+    Cic6105,
+    Cic6106,
+}
+
+impl CicType {
+    fn crc_seed(&self) -> u32 {
+        match self {
+            CicType::Cic6101 | CicType::Cic7102 | CicType::Cic6102 => 0xF8CA4DDC,
+            CicType::Cic6103 => 0xA3886759,
+            CicType::Cic6105 => 0xDF26F436,
+            CicType::Cic6106 => 0x1FEA617A,
+        }
+    }
+}
+
+pub const HEADER_SIZE: usize = 0x40;
+
+/// Start of the Nintendo logo in Game Boy cartridge headers
+const GB_LOGO_OFFSET: usize = 0x104;
+const GB_LOGO_START: &[u8] = &[0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B];
+
+const NES_MAGIC: &[u8] = b"NES\x1A";
+
+const SMS_MAGIC: &[u8] = b"TMR SEGA";
+const SMS_HEADER_OFFSETS: &[usize] = &[0x7FF0, 0x3FF0, 0x1FF0];
+
+/// Internal header locations of LoROM and HiROM SNES roms
+const SNES_HEADER_OFFSETS: &[usize] = &[0x7FC0, 0xFFC0];
+/// Size of the header some SNES copier devices prepend to rom dumps
+const SNES_COPIER_HEADER_SIZE: usize = 0x200;
+
+/// Kind of rom image, either a native N64 rom or a rom for one of the emulators of the Everdrive menu
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RomKind {
+    N64(ByteOrder),
+    /// iNES rom
+    Nes,
+    Snes,
+    GameBoy,
+    MasterSystem,
+}
+
+impl RomKind {
+    /// Detects the kind of a rom image from its header. Returns `None` if the format is not recognized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::rom::{ByteOrder, RomKind};
+    ///
+    /// assert_eq!(RomKind::detect(&[0x80, 0x37, 0x12, 0x40]), Some(RomKind::N64(ByteOrder::BigEndian)));
+    /// assert_eq!(RomKind::detect(b"NES\x1A\x02\x01"), Some(RomKind::Nes));
+    /// assert_eq!(RomKind::detect(&[0; 0x40]), None);
+    /// ```
+    pub fn detect(rom: &[u8]) -> Option<Self> {
+        if let Some(word) = rom.get(0..4) {
+            let header_word_be = u32::from_be_bytes(word.try_into().unwrap());
+
+            if let Some(order) = ByteOrder::from_header_word(header_word_be) {
+                return Some(RomKind::N64(order));
+            }
+        }
+
+        if rom.starts_with(NES_MAGIC) {
+            return Some(RomKind::Nes);
+        }
+
+        if rom.get(GB_LOGO_OFFSET..GB_LOGO_OFFSET + GB_LOGO_START.len()) == Some(GB_LOGO_START) {
+            return Some(RomKind::GameBoy);
+        }
+
+        if SMS_HEADER_OFFSETS
+            .iter()
+            .any(|offset| rom.get(*offset..offset + SMS_MAGIC.len()) == Some(SMS_MAGIC))
+        {
+            return Some(RomKind::MasterSystem);
+        }
+
+        let snes = &rom[snes_copier_header_size(rom)..];
+
+        if SNES_HEADER_OFFSETS
+            .iter()
+            .any(|offset| is_snes_header(snes, *offset))
+        {
+            return Some(RomKind::Snes);
+        }
+
+        None
+    }
+
+    /// Returns true for roms which run in one of the emulators of the Everdrive menu
+    pub fn is_emulated(&self) -> bool {
+        !matches!(self, RomKind::N64(_))
+    }
+
+    /// Prepares an emulator rom for loading: SNES copier headers are removed and the image is padded
+    /// with zeros to a whole number of 512 byte blocks. N64 roms are returned unchanged.
+    pub fn emulator_image<'a>(&self, rom: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        let rom = match self {
+            RomKind::N64(_) => return std::borrow::Cow::Borrowed(rom),
+            RomKind::Snes => &rom[snes_copier_header_size(rom)..],
+            _ => rom,
+        };
+
+        if rom.len().is_multiple_of(512) {
+            std::borrow::Cow::Borrowed(rom)
+        } else {
+            let mut image = rom.to_vec();
+            image.resize(rom.len().next_multiple_of(512), 0);
+            std::borrow::Cow::Owned(image)
+        }
+    }
+}
+
+fn snes_copier_header_size(rom: &[u8]) -> usize {
+    if rom.len() % 0x400 == SNES_COPIER_HEADER_SIZE {
+        SNES_COPIER_HEADER_SIZE
+    } else {
+        0
+    }
+}
+
+/// Checks for a SNES internal header at `offset` by its map mode and checksum complement
+fn is_snes_header(rom: &[u8], offset: usize) -> bool {
+    let Some(header) = rom.get(offset..offset + 0x20) else {
+        return false;
+    };
+
+    let map_mode = header[0x15];
+    let complement = u16::from_le_bytes([header[0x1C], header[0x1D]]);
+    let checksum = u16::from_le_bytes([header[0x1E], header[0x1F]]);
+
+    map_mode & 0xE0 == 0x20 && complement ^ checksum == 0xFFFF
+}
+
+/// Video standard of a N64 console or rom
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TvStandard {
+    /// North America, Japan and most of Asia
+    Ntsc,
+    /// Europe and Australia
+    Pal,
+    /// Brazil
+    Mpal,
+}
+
+impl TvStandard {
+    /// Returns the video standard of the consoles sold in the region with the given region
+    /// (destination) code, or `None` for unknown codes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::rom::TvStandard;
+    ///
+    /// assert_eq!(TvStandard::from_region(b'E'), Some(TvStandard::Ntsc));
+    /// assert_eq!(TvStandard::from_region(b'P'), Some(TvStandard::Pal));
+    /// assert_eq!(TvStandard::from_region(b'B'), Some(TvStandard::Mpal));
+    /// assert_eq!(TvStandard::from_region(b'?'), None);
+    /// ```
+    pub fn from_region(region: u8) -> Option<Self> {
+        match region {
+            b'A' | b'E' | b'G' | b'J' | b'K' | b'N' => Some(TvStandard::Ntsc),
+            b'D' | b'F' | b'H' | b'I' | b'L' | b'P' | b'S' | b'U' | b'W' | b'X' | b'Y' | b'Z' => {
+                Some(TvStandard::Pal)
+            }
+            b'B' => Some(TvStandard::Mpal),
+            _ => None,
+        }
+    }
+
+    /// Region code used when patching a rom for this video standard: b'E' for NTSC, b'P' for PAL
+    /// and b'B' for MPAL
+    pub fn default_region(&self) -> u8 {
+        match self {
+            TvStandard::Ntsc => b'E',
+            TvStandard::Pal => b'P',
+            TvStandard::Mpal => b'B',
+        }
+    }
+}
+
+/// Parsed N64 rom header from the first 0x40 bytes of a rom image
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RomHeader {
+    /// Byte order the rom image is stored in. The other fields are always parsed in native order.
+    pub byte_order: ByteOrder,
+    pub clock_rate: u32,
+    pub entry_point: u32,
+    pub crc1: u32,
+    pub crc2: u32,
+    /// Internal name of the rom with trailing padding removed
+    pub title: String,
+    /// Four character game code, such as "NSME": category, two character game id and region code
+    pub game_code: String,
+    /// Region (destination) code character, such as b'E' for North America
+    pub region: u8,
+    pub version: u8,
+}
+
+impl RomHeader {
+    /// Parses the header of a rom image in any byte order. Only the first 0x40 bytes of `rom` are used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::rom::{ByteOrder, RomHeader};
+    ///
+    /// let mut rom_data = vec![0; 0x40];
+    /// rom_data[0..4].copy_from_slice(&[0x80, 0x37, 0x12, 0x40]);
+    /// rom_data[0x20..0x2C].copy_from_slice(b"SUPER MARIO ");
+    /// rom_data[0x3B..0x3F].copy_from_slice(b"NSME");
+    ///
+    /// let header = RomHeader::parse(&rom_data).unwrap();
+    ///
+    /// assert_eq!(header.byte_order, ByteOrder::BigEndian);
+    /// assert_eq!(header.title, "SUPER MARIO");
+    /// assert_eq!(header.game_code, "NSME");
+    /// assert_eq!(header.region, b'E');
+    /// ```
+    pub fn parse(rom: &[u8]) -> std::io::Result<Self> {
+        if rom.len() < HEADER_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Rom file is too small",
+            ));
+        }
+
+        let header_word_be = u32::from_be_bytes([rom[0], rom[1], rom[2], rom[3]]);
+
+        let byte_order = ByteOrder::from_header_word(header_word_be).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unrecognized rom header {:08X}", header_word_be),
+            )
+        })?;
+
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(&rom[..HEADER_SIZE]);
+
+        crate::byteswap::to_big_endian_in_place(&mut header, byte_order);
+
+        let title = String::from_utf8_lossy(&header[0x20..0x34])
+            .trim_end_matches(['\0', ' '])
+            .to_string();
+
+        Ok(Self {
+            byte_order,
+            clock_rate: rom_word(&header, 0x04),
+            entry_point: rom_word(&header, 0x08),
+            crc1: rom_word(&header, CRC1_OFFSET),
+            crc2: rom_word(&header, CRC2_OFFSET),
+            title,
+            game_code: String::from_utf8_lossy(&header[0x3B..0x3F]).to_string(),
+            region: header[0x3E],
+            version: header[0x3F],
+        })
+    }
+
+    /// Video standard of the region the rom was released for, `None` for unknown region codes
+    pub fn tv_standard(&self) -> Option<TvStandard> {
+        TvStandard::from_region(self.region)
+    }
+}
+
+/// Length of the internal name in the rom header
+const TITLE_LENGTH: usize = 20;
+const TITLE_OFFSET: usize = 0x20;
+const GAME_CODE_OFFSET: usize = 0x3B;
+const REGION_OFFSET: usize = 0x3E;
+
+impl RomHeader {
+    /// Applies `edit` to the header of `rom` in big-endian byte order, keeping the byte order of the rom
+    fn edit(rom: &mut [u8], edit: impl FnOnce(&mut [u8; HEADER_SIZE])) -> std::io::Result<()> {
+        let byte_order = Self::parse(rom)?.byte_order;
+
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(&rom[..HEADER_SIZE]);
+
+        crate::byteswap::to_big_endian_in_place(&mut header, byte_order);
+        edit(&mut header);
+        // Swapping is its own inverse, this restores the byte order of the rom
+        crate::byteswap::to_big_endian_in_place(&mut header, byte_order);
+
+        rom[..HEADER_SIZE].copy_from_slice(&header);
+        Ok(())
+    }
+
+    /// Sets the internal name in the header of `rom`, in any byte order. The name must be ASCII of
+    /// at most 20 characters and is padded with spaces.
+    ///
+    /// The header is not covered by the rom checksums, so they stay valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::rom::RomHeader;
+    ///
+    /// let mut rom_data = vec![0; 0x40];
+    /// rom_data[0..4].copy_from_slice(&[0x37, 0x80, 0x40, 0x12]);
+    ///
+    /// RomHeader::set_title(&mut rom_data, "MY HOMEBREW").unwrap();
+    /// RomHeader::set_game_code(&mut rom_data, "NMHE").unwrap();
+    /// RomHeader::set_region(&mut rom_data, b'P').unwrap();
+    ///
+    /// let header = RomHeader::parse(&rom_data).unwrap();
+    ///
+    /// assert_eq!(header.title, "MY HOMEBREW");
+    /// assert_eq!(header.game_code, "NMHP");
+    /// assert_eq!(header.region, b'P');
+    /// ```
+    pub fn set_title(rom: &mut [u8], title: &str) -> std::io::Result<()> {
+        if !title.is_ascii() || title.len() > TITLE_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Title must be ASCII of at most 20 characters",
+            ));
+        }
+
+        Self::edit(rom, |header| {
+            let field = &mut header[TITLE_OFFSET..TITLE_OFFSET + TITLE_LENGTH];
+            field.fill(b' ');
+            field[..title.len()].copy_from_slice(title.as_bytes());
+        })
+    }
+
+    /// Sets the four character game code, such as "NSME", in the header of `rom`, in any byte order.
+    /// The last character is the region code.
+    pub fn set_game_code(rom: &mut [u8], game_code: &str) -> std::io::Result<()> {
+        if !game_code.is_ascii() || game_code.len() != 4 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Game code must be four ASCII characters",
+            ));
+        }
+
+        Self::edit(rom, |header| {
+            header[GAME_CODE_OFFSET..GAME_CODE_OFFSET + 4].copy_from_slice(game_code.as_bytes());
+        })
+    }
+
+    /// Sets the region (destination) code, such as b'E' for North America, in the header of `rom`,
+    /// in any byte order
+    pub fn set_region(rom: &mut [u8], region: u8) -> std::io::Result<()> {
+        if !region.is_ascii_graphic() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Region must be a printable ASCII character",
+            ));
+        }
+
+        Self::edit(rom, |header| header[REGION_OFFSET] = region)
+    }
+}
+
+/// Returns the video standard of `rom` if it differs from the `console` the rom is going to run
+/// on, or `None` if they match or the region of the rom is not known. Games made for the other
+/// video standard often run with wrong timing or no picture on a mismatching console.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::rom::{self, TvStandard};
+/// use std::fs;
+///
+/// let mut rom_data = fs::read("your_rom.z64").unwrap();
+///
+/// if let Some(standard) = rom::tv_standard_mismatch(&rom_data, TvStandard::Pal) {
+///     eprintln!("Rom is made for {:?} consoles, patching it", standard);
+///     rom::patch_tv_standard(&mut rom_data, TvStandard::Pal).unwrap();
+/// }
+/// ```
+pub fn tv_standard_mismatch(rom: &[u8], console: TvStandard) -> Option<TvStandard> {
+    RomHeader::parse(rom)
+        .ok()?
+        .tv_standard()
+        .filter(|standard| *standard != console)
+}
+
+/// Sets the region code of `rom`, in any byte order, to the default region of `console` if the
+/// video standard of the rom differs. Returns true if the rom was patched.
+///
+/// Only the region code is changed, which is what the Everdrive menu and other tools reading the
+/// header display. The video standard a game runs in comes from the console (`osTvType`) and its
+/// own code, not from the header, so this doesn't make a game run on a mismatching console.
+pub fn patch_tv_standard(rom: &mut [u8], console: TvStandard) -> std::io::Result<bool> {
+    if tv_standard_mismatch(rom, console).is_none() {
+        return Ok(false);
+    }
+
+    RomHeader::set_region(rom, console.default_region())?;
+    Ok(true)
+}
+
+/// Information about a rom image gathered without a device connection
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RomInfo {
+    /// Detected CIC variant, or `None` if the IPL3 bootcode was not recognized
+    pub cic: Option<CicType>,
+    /// CRC32 of the IPL3 bootcode region used for CIC detection
+    pub ipl3_crc32: u32,
+    /// Region (destination) code from the header, or `None` if the header could not be parsed
+    pub region: Option<u8>,
+    /// Video standard of the region, `None` for unknown region codes
+    pub tv_standard: Option<TvStandard>,
+}
+
+impl RomInfo {
+    /// Gathers information about a rom image.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::rom::RomInfo;
+    /// use std::fs;
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    /// let info = RomInfo::new(&rom_data);
+    ///
+    /// if info.cic.is_none() {
+    ///     eprintln!("Unknown CIC, bootcode CRC32 {:08X}", info.ipl3_crc32);
+    /// }
+    /// ```
+    pub fn new(rom: &[u8]) -> Self {
+        let ipl3_crc32 = ipl3_crc32(rom);
+        let region = RomHeader::parse(rom).ok().map(|header| header.region);
+
+        Self {
+            cic: cic_from_ipl3_crc32(ipl3_crc32),
+            ipl3_crc32,
+            region,
+            tv_standard: region.and_then(TvStandard::from_region),
+        }
+    }
+}
+
+/// Calculates the standard (IEEE) CRC32 of `data`
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn ipl3_crc32(rom: &[u8]) -> u32 {
+    let end = rom.len().min(IPL3_END);
+    let start = IPL3_START.min(end);
+
+    crc32(&rom[start..end])
+}
+
+fn cic_from_ipl3_crc32(crc: u32) -> Option<CicType> {
+    match crc {
+        0x6170A4A1 => Some(CicType::Cic6101),
+        0x009E9EA3 => Some(CicType::Cic7102),
+        0x90BB6CB5 => Some(CicType::Cic6102),
+        0x0B050EE0 => Some(CicType::Cic6103),
+        0x98BC2C86 => Some(CicType::Cic6105),
+        0xACC8580A => Some(CicType::Cic6106),
+        _ => None,
+    }
+}
+
+/// Detects the CIC variant of the rom by hashing its IPL3 bootcode.
+/// Returns `None` if the bootcode does not match any known CIC.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::rom::{self, CicType};
+/// use std::fs;
+///
+/// let mut rom_data = fs::read("your_rom.z64").unwrap();
+///
+/// let cic = rom::detect_cic(&rom_data).unwrap_or(CicType::Cic6102);
+/// rom::fix_crc(&mut rom_data, cic);
+/// ```
+pub fn detect_cic(rom: &[u8]) -> Option<CicType> {
+    cic_from_ipl3_crc32(ipl3_crc32(rom))
+}
+
+/// Reads a big-endian word from the rom, treating bytes past the end of the rom as zero
+/// the same way the cart sees them after the CRC area has been filled.
+fn rom_word(rom: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+
+    for (i, byte) in word.iter_mut().enumerate() {
+        *byte = rom.get(offset + i).copied().unwrap_or(0);
+    }
+
+    u32::from_be_bytes(word)
+}
+
+/// Calculates the CRC1 and CRC2 checksums of the rom for the given CIC variant.
+///
+/// The checksums cover 1MB of the rom starting at offset 0x1000, so changes to the
+/// header (such as the save type bytes) do not affect them.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::rom::{self, CicType};
+///
+/// let rom_data = vec![0; 0x101000];
+/// let (crc1, crc2) = rom::calculate_crc(&rom_data, CicType::Cic6102);
+///
+/// assert_eq!(crc1, 0xF8CA4DDC);
+/// assert_eq!(crc2, 0x303A4DDC);
+/// ```
+pub fn calculate_crc(rom: &[u8], cic: CicType) -> (u32, u32) {
+    // reference https://github.com/parasyte/n64crc/blob/master/n64crc.c
+    let seed = cic.crc_seed();
+
+    let (mut t1, mut t2, mut t3, mut t4, mut t5, mut t6) = (seed, seed, seed, seed, seed, seed);
+
+    for i in (CRC_START..CRC_START + CRC_LENGTH).step_by(4) {
+        let d = rom_word(rom, i);
+
+        if t6.wrapping_add(d) < t6 {
+            t4 = t4.wrapping_add(1);
+        }
+
+        t6 = t6.wrapping_add(d);
+        t3 ^= d;
+
+        let r = d.rotate_left(d & 0x1F);
+        t5 = t5.wrapping_add(r);
+
+        if t2 > d {
+            t2 ^= r;
+        } else {
+            t2 ^= t6 ^ d;
+        }
+
+        if cic == CicType::Cic6105 {
+            t1 = t1.wrapping_add(rom_word(rom, 0x0750 + (i & 0xFF)) ^ d);
+        } else {
+            t1 = t1.wrapping_add(t5 ^ d);
+        }
+    }
+
+    match cic {
+        CicType::Cic6103 => ((t6 ^ t4).wrapping_add(t3), (t5 ^ t2).wrapping_add(t1)),
+        CicType::Cic6106 => (
+            t6.wrapping_mul(t4).wrapping_add(t3),
+            t5.wrapping_mul(t2).wrapping_add(t1),
+        ),
+        _ => (t6 ^ t4 ^ t3, t5 ^ t2 ^ t1),
+    }
+}
+
+/// Returns the CRC1 and CRC2 checksums stored in the rom header
+pub fn header_crc(rom: &[u8]) -> (u32, u32) {
+    (rom_word(rom, CRC1_OFFSET), rom_word(rom, CRC2_OFFSET))
+}
+
+/// Recalculates the rom checksums for the given CIC variant and writes them into the rom header.
+/// Returns true if the checksums in the header were changed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::Everdrive;
+/// use libeverdrive::rom::{self, CicType};
+/// use std::fs;
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// let mut rom_data = fs::read("your_rom.z64").unwrap();
+/// rom::fix_crc(&mut rom_data, CicType::Cic6102);
+///
+/// ed.ed_load_rom(rom_data, None, None, None).unwrap();
+/// ```
+pub fn fix_crc(rom: &mut [u8], cic: CicType) -> bool {
+    let (crc1, crc2) = calculate_crc(rom, cic);
+
+    if header_crc(rom) == (crc1, crc2) {
+        return false;
+    }
+
+    rom[CRC1_OFFSET..CRC1_OFFSET + 4].copy_from_slice(&crc1.to_be_bytes());
+    rom[CRC2_OFFSET..CRC2_OFFSET + 4].copy_from_slice(&crc2.to_be_bytes());
+
+    true
+}
+
+/// Looks up the save type of the rom from the built-in game database, using the game id in the rom header,
+/// and its region and version for games whose save type differs between releases.
+/// Returns the save type and whether the game requires the RTC, or `None` if the game is not in the database.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::EdSaveType;
+/// use libeverdrive::rom;
+///
+/// let mut rom_data = vec![0; 0x40];
+/// rom_data[0x3B..0x3F].copy_from_slice(b"NZSE");
+///
+/// assert_eq!(rom::lookup_save_type(&rom_data), Some((EdSaveType::FlashRam, false)));
+///
+/// rom_data[0x3B..0x40].copy_from_slice(b"NK4J\x00");
+/// assert_eq!(rom::lookup_save_type(&rom_data), Some((EdSaveType::Sram, false)));
+///
+/// rom_data[0x3F] = 0x02;
+/// assert_eq!(rom::lookup_save_type(&rom_data), Some((EdSaveType::Eeprom4k, false)));
+/// ```
+#[cfg(feature = "edos")]
+pub fn lookup_save_type(rom: &[u8]) -> Option<(EdSaveType, bool)> {
+    let key = rom.get(GAME_ID_OFFSET..GAME_ID_OFFSET + 4)?;
+
+    crate::save_db::lookup(key)
+}
+
+/// Swaps `data` to big-endian byte order based on the rom header word.
+/// Returns false if the byte order was not recognized, in which case `data` is left untouched.
+pub(crate) fn rom_swap_to_native(header_word_be: u32, data: &mut [u8]) -> bool {
+    match ByteOrder::from_header_word(header_word_be) {
+        Some(order) => {
+            crate::byteswap::to_big_endian_in_place(data, order);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the size of `rom` without the trailing 0xFF or 0x00 padding of an overdump. Returns the
+/// full size if the rom does not end in padding.
+///
+/// Overdumps are roms dumped with a larger size than the game, such as 64MB dumps of 12MB games.
+/// Note that some games end in padding which is part of the checksummed area, so the size should
+/// not be reduced below the checksummed area.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::rom;
+///
+/// let mut rom_data = vec![0xFF; 0x4000];
+/// rom_data[..0x1234].fill(0x55);
+///
+/// assert_eq!(rom::content_size(&rom_data), 0x1234);
+/// assert_eq!(rom::content_size(&[0x55; 0x100]), 0x100);
+/// ```
+pub fn content_size(rom: &[u8]) -> usize {
+    let Some(&padding) = rom.last().filter(|byte| **byte == 0xFF || **byte == 0x00) else {
+        return rom.len();
+    };
+
+    rom.iter()
+        .rposition(|byte| *byte != padding)
+        .map_or(0, |last| last + 1)
+}
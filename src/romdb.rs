@@ -0,0 +1,203 @@
+//! Rom identification against No-Intro style DAT files.
+//!
+//! DAT files in the Logiqx XML format, as published by No-Intro, are parsed into a [`RomDatabase`].
+//! Roms are matched by size, CRC32 and MD5 of their big-endian (z64) image, the byte order No-Intro
+//! uses for N64 roms. No database is bundled, DAT files have to be downloaded separately.
+
+use md5::Digest;
+
+/// Checksums of a rom image in big-endian byte order
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RomHashes {
+    pub size: usize,
+    pub crc32: u32,
+    pub md5: [u8; 16],
+}
+
+impl RomHashes {
+    /// Computes the checksums of a rom in any byte order. Roms which are not recognized as N64 roms
+    /// are hashed as is.
+    pub fn compute(rom: &[u8]) -> Self {
+        let order = match crate::rom::RomKind::detect(rom) {
+            Some(crate::rom::RomKind::N64(order)) => order,
+            _ => crate::rom::ByteOrder::BigEndian,
+        };
+
+        let mut rom = rom.to_vec();
+        crate::byteswap::to_big_endian_in_place(&mut rom, order);
+
+        Self {
+            size: rom.len(),
+            crc32: crate::rom::crc32(&rom),
+            md5: md5::Md5::digest(&rom).into(),
+        }
+    }
+}
+
+/// A rom entry of a DAT file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatEntry {
+    /// Full name of the entry, such as "Super Mario 64 (USA)"
+    pub name: String,
+    /// Name without the parenthesized tags
+    pub title: String,
+    /// Region tag, such as "USA" or "Europe"
+    pub region: Option<String>,
+    /// Revision tag, such as "Rev 1"
+    pub revision: Option<String>,
+    pub size: usize,
+    pub crc32: u32,
+    pub md5: Option<[u8; 16]>,
+    /// The DAT marks the entry as a known bad dump
+    pub bad_dump: bool,
+}
+
+impl DatEntry {
+    fn matches(&self, hashes: &RomHashes) -> bool {
+        self.size == hashes.size
+            && self.crc32 == hashes.crc32
+            && self.md5.is_none_or(|md5| md5 == hashes.md5)
+    }
+}
+
+/// Returns the value of `attr` in the XML tag `tag`
+fn xml_attr(tag: &str, attr: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", attr);
+    let start = tag.find(&pattern)? + pattern.len();
+    let len = tag[start..].find('"')?;
+
+    Some(
+        tag[start..start + len]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn parse_md5(hex: &str) -> Option<[u8; 16]> {
+    if hex.len() != 32 {
+        return None;
+    }
+
+    let mut md5 = [0; 16];
+
+    for (i, byte) in md5.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    Some(md5)
+}
+
+/// Splits a No-Intro name into its title, region and revision
+fn parse_name(name: &str) -> (String, Option<String>, Option<String>) {
+    let title = name.split(" (").next().unwrap_or(name).trim().to_string();
+
+    let tags: Vec<&str> = name
+        .split('(')
+        .skip(1)
+        .filter_map(|tag| tag.split(')').next())
+        .collect();
+
+    let region = tags.first().map(|tag| tag.to_string());
+    let revision = tags
+        .iter()
+        .find(|tag| tag.starts_with("Rev "))
+        .map(|tag| tag.to_string());
+
+    (title, region, revision)
+}
+
+/// Rom entries of a DAT file
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::romdb::RomDatabase;
+///
+/// let dat = r#"<datafile>
+///     <game name="Example Game (USA) (Rev 1)">
+///         <rom name="Example Game (USA) (Rev 1).z64" size="4" crc="e311ab71"/>
+///     </game>
+/// </datafile>"#;
+///
+/// let db = RomDatabase::parse(dat).unwrap();
+/// let entry = db.identify(&[0x80, 0x37, 0x12, 0x40]).unwrap();
+///
+/// assert_eq!(entry.title, "Example Game");
+/// assert_eq!(entry.region.as_deref(), Some("USA"));
+/// assert_eq!(entry.revision.as_deref(), Some("Rev 1"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    entries: Vec<DatEntry>,
+}
+
+impl RomDatabase {
+    /// Parses a DAT file in the Logiqx XML format
+    pub fn parse(xml: &str) -> std::io::Result<Self> {
+        let mut entries = Vec::new();
+
+        for game in xml.split("<game ").skip(1) {
+            let game = game.split("</game>").next().unwrap_or(game);
+            let game_tag = game.split('>').next().unwrap_or(game);
+            let game_name = xml_attr(&format!(" {}", game_tag), "name");
+
+            for rom in game.split("<rom ").skip(1) {
+                let tag = format!(" {}", rom.split('>').next().unwrap_or(rom));
+
+                let (Some(size), Some(crc)) = (xml_attr(&tag, "size"), xml_attr(&tag, "crc"))
+                else {
+                    continue;
+                };
+
+                let (Ok(size), Ok(crc32)) = (size.parse(), u32::from_str_radix(&crc, 16)) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid rom entry in DAT file: {}", tag.trim()),
+                    ));
+                };
+
+                let name = game_name
+                    .clone()
+                    .or_else(|| xml_attr(&tag, "name"))
+                    .unwrap_or_default();
+                let (title, region, revision) = parse_name(&name);
+
+                entries.push(DatEntry {
+                    bad_dump: xml_attr(&tag, "status").as_deref() == Some("baddump")
+                        || name.contains("[b]"),
+                    name,
+                    title,
+                    region,
+                    revision,
+                    size,
+                    crc32,
+                    md5: xml_attr(&tag, "md5").and_then(|md5| parse_md5(&md5)),
+                });
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn entries(&self) -> &[DatEntry] {
+        &self.entries
+    }
+
+    /// Finds the entry matching `hashes`
+    pub fn lookup(&self, hashes: &RomHashes) -> Option<&DatEntry> {
+        self.entries.iter().find(|entry| entry.matches(hashes))
+    }
+
+    /// Hashes a rom in any byte order and finds its entry. Returns `None` for unknown roms, which
+    /// may be homebrew, hacks or bad dumps not listed in the DAT file.
+    pub fn identify(&self, rom: &[u8]) -> Option<&DatEntry> {
+        self.lookup(&RomHashes::compute(rom))
+    }
+}
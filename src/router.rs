@@ -0,0 +1,108 @@
+use crate::unf::{UnfDataType, UnfRecvPacket};
+use crate::{CancellationToken, Everdrive};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Demultiplexes a single UNF receive loop to any number of consumers over channels.
+///
+/// Consumers subscribe to the datatypes they are interested in and receive their own copy of every
+/// matching packet, so they can run on separate threads. Subscriptions are dropped once their
+/// receiver is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{CancellationToken, Everdrive, UnfDataType, UnfRouter};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+/// let mut router = UnfRouter::new();
+///
+/// let text = router.subscribe(UnfDataType::DataTypeText);
+/// let screenshots = router.subscribe(UnfDataType::DataTypeScreenshot);
+///
+/// std::thread::spawn(move || {
+///     for packet in text {
+///         print!("{}", packet.as_text().unwrap_or_default());
+///     }
+/// });
+///
+/// std::thread::spawn(move || {
+///     for packet in screenshots {
+///         println!("Screenshot packet of {} bytes", packet.get_data().len());
+///     }
+/// });
+///
+/// ed.unf_route(&mut router, &CancellationToken::new()).unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct UnfRouter {
+    subscribers: Vec<(Option<UnfDataType>, Sender<UnfRecvPacket>)>,
+}
+
+impl UnfRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to packets of `datatype`
+    pub fn subscribe(&mut self, datatype: UnfDataType) -> Receiver<UnfRecvPacket> {
+        self.add_subscriber(Some(datatype))
+    }
+
+    /// Subscribes to all packets regardless of their datatype
+    pub fn subscribe_all(&mut self) -> Receiver<UnfRecvPacket> {
+        self.add_subscriber(None)
+    }
+
+    fn add_subscriber(&mut self, datatype: Option<UnfDataType>) -> Receiver<UnfRecvPacket> {
+        let (sender, receiver) = channel();
+        self.subscribers.push((datatype, sender));
+        receiver
+    }
+
+    /// Sends `packet` to every subscriber interested in its datatype and returns how many
+    /// subscribers received it
+    pub fn dispatch(&mut self, packet: &UnfRecvPacket) -> usize {
+        let mut delivered = 0;
+
+        self.subscribers.retain(|(datatype, sender)| {
+            if datatype.is_some_and(|datatype| datatype != packet.get_datatype()) {
+                return true;
+            }
+
+            // A failed send means the receiver was dropped
+            let connected = sender.send(packet.clone()).is_ok();
+            delivered += connected as usize;
+            connected
+        });
+
+        delivered
+    }
+
+    /// Returns the number of active subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+impl Everdrive {
+    /// Runs a blocking receive loop, dispatching incoming packets to the subscribers of `router`
+    /// until `stop` is cancelled or receiving fails. Serial read timeouts while waiting for a packet are
+    /// not treated as errors.
+    pub fn unf_route(
+        &mut self,
+        router: &mut UnfRouter,
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        while !stop.is_cancelled() {
+            match self.unf_rx() {
+                Ok(packet) => {
+                    router.dispatch(&packet);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! JSON-RPC 2.0 server for driving the cart from other languages and tools.
+//!
+//! Requests and responses are single-line JSON objects separated by newlines. Supported methods:
+//!
+//! | Method            | Params                                    | Result                              |
+//! |-------------------|-------------------------------------------|-------------------------------------|
+//! | `status`          |                                           | `null`                              |
+//! | `load_rom`        | `path`, optional `base_address`           | `null`                              |
+//! | `app_start`       | optional `save_file_name`                 | `null`                              |
+//! | `run_rom`         | `path`                                    | `null`                              |
+//! | `send_text`       | `text`                                    | `null`                              |
+//! | `read_logs`       |                                           | text received since the last call   |
+//! | `take_screenshot` |                                           | latest screenshot, or `null`        |
+//! | `reset_session`   |                                           | `null`                              |
+//!
+//! Requests without an `id` are notifications and get no response. Screenshots are returned as an
+//! object with `width`, `height` and `rgba`, the decoded 8-bit RGBA pixels in row order as hex.
+//!
+//! `load_rom` and `run_rom` reset the session first if a program is running, which requires the
+//! console to be reset to the menu beforehand.
+//!
+//! Rom paths are relative to the rom directory passed to `rpc_serve`, paths leading outside of it
+//! are rejected so clients can't read other files of the host.
+//!
+//! Console packets are received between requests, so logs and screenshots are collected while
+//! clients are idle. Invalid packets are logged and skipped.
+
+use crate::screenshot::{Screenshot, ScreenshotDecoder};
+use crate::trace::trace_event;
+use crate::unf::{UnfDataType, UnfSendPacket};
+use crate::{CancellationToken, Everdrive, RunOptions, SessionState};
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+
+const RPC_PARSE_ERROR: i64 = -32700;
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+const RPC_INVALID_PARAMS: i64 = -32602;
+const RPC_SERVER_ERROR: i64 = -32000;
+
+/// Interval for polling for clients and console packets
+const RPC_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Console output collected between requests
+struct RpcState {
+    /// Canonical directory rom paths are resolved in
+    rom_dir: std::path::PathBuf,
+    logs: String,
+    screenshot: Option<Screenshot>,
+    /// Pairs screenshot headers with the framebuffers following them
+    decoder: ScreenshotDecoder,
+}
+
+struct RpcError(i64, String);
+
+impl From<std::io::Error> for RpcError {
+    fn from(err: std::io::Error) -> Self {
+        RpcError(RPC_SERVER_ERROR, err.to_string())
+    }
+}
+
+fn param_str<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params.get(name).and_then(Value::as_str).ok_or_else(|| {
+        RpcError(
+            RPC_INVALID_PARAMS,
+            format!("Missing string parameter {}", name),
+        )
+    })
+}
+
+/// Resolves a rom path requested by a client within the rom directory
+fn rom_path(state: &RpcState, params: &Value) -> Result<std::path::PathBuf, RpcError> {
+    let path = state
+        .rom_dir
+        .join(param_str(params, "path")?)
+        .canonicalize()?;
+
+    if !path.starts_with(&state.rom_dir) {
+        return Err(RpcError(
+            RPC_INVALID_PARAMS,
+            "Path is outside of the rom directory".to_string(),
+        ));
+    }
+
+    Ok(path)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Everdrive {
+    /// Serves JSON-RPC requests from TCP clients on `addr` until `stop` is cancelled. Clients are served
+    /// one request at a time in the order they arrive and can only load roms from `rom_dir`. See the
+    /// `rpc` module for the available methods.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// // echo '{"jsonrpc":"2.0","id":1,"method":"run_rom","params":{"path":"rom.z64"}}' | nc localhost 6465
+    /// ed.rpc_serve("127.0.0.1:6465", "./roms", &CancellationToken::new()).unwrap();
+    /// ```
+    pub fn rpc_serve(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        rom_dir: impl AsRef<std::path::Path>,
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        let mut state = RpcState {
+            rom_dir: rom_dir.as_ref().canonicalize()?,
+            logs: String::new(),
+            screenshot: None,
+            decoder: ScreenshotDecoder::new(),
+        };
+
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        // Clients with the part of a request line received so far. Lines are decoded once complete,
+        // since a character may be split between reads.
+        let mut clients: Vec<(std::io::BufReader<std::net::TcpStream>, Vec<u8>)> = Vec::new();
+
+        while !stop.is_cancelled() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    clients.push((std::io::BufReader::new(stream), Vec::new()));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+
+            let mut requests = Vec::new();
+
+            clients.retain_mut(|(client, line)| {
+                loop {
+                    match client.read_until(b'\n', line) {
+                        Ok(0) => return false,
+                        Ok(_) if line.ends_with(b"\n") => {
+                            requests.push((client.get_ref().try_clone(), std::mem::take(line)));
+                        }
+                        Ok(_) => return true,
+                        // Partial lines are kept until the rest of the request arrives
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return true,
+                        Err(_) => return false,
+                    }
+                }
+            });
+
+            for (client, request) in requests {
+                let Some(response) = self.rpc_handle(&request, &mut state) else {
+                    continue;
+                };
+
+                if let Ok(mut client) = client {
+                    client.set_nonblocking(false)?;
+                    let _ = writeln!(client, "{}", response);
+                    client.set_nonblocking(true)?;
+                }
+            }
+
+            let mut received = false;
+
+            // The console only sends packets once a program is running
+            while !matches!(self.session, SessionState::Menu | SessionState::RomLoaded) {
+                let packet = match self.unf_try_rx() {
+                    Ok(Some(packet)) => packet,
+                    Ok(None) => break,
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            std::io::ErrorKind::InvalidData
+                                | std::io::ErrorKind::UnexpectedEof
+                                | std::io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        trace_event!(warn, %err, "invalid UNF packet skipped");
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                received = true;
+
+                match packet.get_datatype() {
+                    UnfDataType::DataTypeText => state
+                        .logs
+                        .push_str(&String::from_utf8_lossy(packet.get_data())),
+                    _ => match state.decoder.push(&packet) {
+                        Some(Ok(screenshot)) => state.screenshot = Some(screenshot),
+                        Some(Err(_err)) => {
+                            trace_event!(warn, error = %_err, "invalid screenshot skipped");
+                        }
+                        None => {}
+                    },
+                }
+            }
+
+            if !received {
+                crate::clock::sleep(RPC_POLL_INTERVAL);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a request line, returns `None` for notifications
+    fn rpc_handle(&mut self, request: &[u8], state: &mut RpcState) -> Option<Value> {
+        let request: Value = match serde_json::from_slice(request) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": RPC_PARSE_ERROR, "message": err.to_string() },
+                }));
+            }
+        };
+
+        let id = request.get("id").cloned();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+
+        let result = self.rpc_call(method, &params, state);
+        let id = id?;
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(RpcError(code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        })
+    }
+
+    fn rpc_call(
+        &mut self,
+        method: &str,
+        params: &Value,
+        state: &mut RpcState,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "status" => self.ed_status()?,
+            "load_rom" => {
+                let rom = std::fs::read(rom_path(state, params)?)?;
+                self.session_reenter_menu("load_rom")?;
+                let base_address = params
+                    .get("base_address")
+                    .and_then(Value::as_u64)
+                    .map(|addr| addr as u32);
+
+                self.ed_load_rom_auto(rom, base_address)?;
+            }
+            "app_start" => {
+                let save_file_name = params.get("save_file_name").and_then(Value::as_str);
+                self.ed_app_start(save_file_name)?;
+            }
+            "run_rom" => {
+                let path = rom_path(state, params)?;
+                self.session_reenter_menu("run_rom")?;
+                self.load_and_start(&path, &RunOptions::default())?;
+            }
+            "send_text" => {
+                let text = param_str(params, "text")?;
+                let packet = UnfSendPacket::from_str(text)?;
+                self.unf_tx(&packet)?;
+            }
+            "reset_session" => self.reset_session()?,
+            "read_logs" => return Ok(Value::String(std::mem::take(&mut state.logs))),
+            "take_screenshot" => {
+                return Ok(state.screenshot.as_ref().map_or(Value::Null, |screenshot| {
+                    json!({
+                        "width": screenshot.width,
+                        "height": screenshot.height,
+                        "rgba": to_hex(&screenshot.rgba),
+                    })
+                }));
+            }
+            _ => {
+                return Err(RpcError(
+                    RPC_METHOD_NOT_FOUND,
+                    format!("Unknown method {}", method),
+                ));
+            }
+        }
+
+        Ok(Value::Null)
+    }
+}
@@ -0,0 +1,87 @@
+use crate::CancellationToken;
+use crate::Everdrive;
+use crate::RomLoadReport;
+use crate::edos::{EdFileName, EdRtcRegionType, EdSaveType};
+use crate::unf::UnfHandlers;
+
+/// Options for `Everdrive::run_rom`
+#[derive(Default)]
+pub struct RunOptions<'a> {
+    /// Base address to load the rom into, defaults to the rom base of the memory map
+    pub base_address: Option<u32>,
+    /// Save type of the rom. If not set, the save type is looked up from the built-in game database.
+    pub save_type: Option<EdSaveType>,
+    /// RTC and region settings, only used when `save_type` is set
+    pub rtc_region_type: Option<EdRtcRegionType>,
+    /// Name of the save file on the SD card, defaults to the file name of the rom converted with
+    /// `EdFileName::sanitized`
+    pub save_file_name: Option<String>,
+    /// If set, a `unf_listen` loop is started with these handlers once the rom is running
+    pub listen: Option<UnfHandlers<'a>>,
+    /// Stop token for the listen loop
+    pub stop: CancellationToken,
+}
+
+impl Everdrive {
+    /// Reads a rom file from `path`, loads it onto the cart and starts it.
+    ///
+    /// Byte order is detected and converted automatically. If `options.listen` is set, the call
+    /// blocks in a `unf_listen` loop after starting the rom until `options.stop` is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, RunOptions, UnfHandlers};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.run_rom(
+    ///     "your_rom.z64",
+    ///     RunOptions {
+    ///         listen: Some(UnfHandlers::new().on_text(|text| print!("{}", text))),
+    ///         ..Default::default()
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn run_rom(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        options: RunOptions,
+    ) -> std::io::Result<()> {
+        self.load_and_start(path.as_ref(), &options)?;
+
+        if let Some(handlers) = options.listen {
+            self.unf_listen(handlers, &options.stop)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the rom from `path` with the load settings of `options` and starts it
+    pub(crate) fn load_and_start(
+        &mut self,
+        path: &std::path::Path,
+        options: &RunOptions,
+    ) -> std::io::Result<RomLoadReport> {
+        let rom_data = std::fs::read(path)?;
+
+        let report = match options.save_type {
+            Some(save_type) => self.ed_load_rom(
+                rom_data,
+                options.base_address,
+                Some(save_type),
+                options.rtc_region_type,
+            )?,
+            None => self.ed_load_rom_auto(rom_data, options.base_address)?,
+        };
+
+        let save_file_name = options.save_file_name.clone().or_else(|| {
+            path.file_name()
+                .map(|name| EdFileName::sanitized(&name.to_string_lossy()).to_string())
+        });
+
+        self.ed_app_start(save_file_name.as_deref())?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,161 @@
+use crate::edos::EdSaveType;
+
+/// Save type and RTC requirements of commercial games, keyed by the header bytes from offset
+/// 0x3C: the two character game id, optionally followed by the region code and the version for
+/// games whose save type differs between releases. The id alone covers all other regions.
+pub(crate) const SAVE_DB: &[(&[u8], EdSaveType, bool)] = &[
+    (b"A2", EdSaveType::Sram, false), // Virtual Pro Wrestling 2: Oudou Keishou
+    (b"AB", EdSaveType::Eeprom4k, false), // Air Boarder 64
+    (b"AD", EdSaveType::Eeprom4k, false), // Worms Armageddon
+    (b"AF", EdSaveType::FlashRam, true), // Animal Forest
+    (b"AG", EdSaveType::Eeprom4k, false), // AeroGauge
+    (b"AL", EdSaveType::Sram, false), // Super Smash Bros.
+    (b"AY", EdSaveType::Sram, false), // Aidyn Chronicles: The First Mage
+    (b"B6", EdSaveType::Eeprom4k, false), // Super B-Daman: Battle Phoenix 64
+    (b"B7", EdSaveType::Eeprom16k, false), // Banjo-Tooie
+    (b"BC", EdSaveType::Eeprom4k, false), // Blast Corps
+    (b"BD", EdSaveType::Eeprom4k, false), // Bomberman Hero
+    (b"BH", EdSaveType::Eeprom4k, false), // Body Harvest
+    (b"BK", EdSaveType::Eeprom4k, false), // Banjo-Kazooie
+    (b"BM", EdSaveType::Eeprom4k, false), // Bomberman 64
+    (b"BN", EdSaveType::Eeprom4k, false), // Bakuretsu Muteki Bangaioh
+    (b"BV", EdSaveType::Eeprom4k, false), // Bomberman 64: The Second Attack!
+    (b"CC", EdSaveType::FlashRam, false), // Command & Conquer
+    (b"CG", EdSaveType::Eeprom4k, false), // Choro Q 64 II
+    (b"CH", EdSaveType::Eeprom4k, false), // Chopper Attack
+    (b"CK", EdSaveType::FlashRam, false), // NBA Courtside 2
+    (b"CR", EdSaveType::Eeprom4k, false), // Penny Racers
+    (b"CT", EdSaveType::Eeprom4k, false), // Chameleon Twist
+    (b"CU", EdSaveType::Eeprom4k, false), // Cruis'n USA
+    (b"CW", EdSaveType::Eeprom16k, false), // Cruis'n World
+    (b"CX", EdSaveType::Eeprom4k, false), // Custom Robo
+    (b"CZ", EdSaveType::Eeprom16k, false), // Dance Dance Revolution: Disney Dancing Museum
+    (b"D2", EdSaveType::Eeprom16k, false), // Doraemon 2: Nobita to Hikari no Shinden
+    (b"D6", EdSaveType::Eeprom16k, false), // Densha de Go! 64
+    (b"DA", EdSaveType::FlashRam, false), // Derby Stallion 64
+    (b"DKJ", EdSaveType::Eeprom4k, false), // Space Dynamites, Dark Rift has no save elsewhere
+    (b"DO", EdSaveType::Eeprom16k, false), // Donkey Kong 64
+    (b"DP", EdSaveType::FlashRam, false), // Dinosaur Planet
+    (b"DQ", EdSaveType::Eeprom4k, false), // Donald Duck: Goin' Quackers
+    (b"DR", EdSaveType::Eeprom4k, false), // Doraemon: Nobita to 3tsu no Seireiseki
+    (b"DU", EdSaveType::Eeprom4k, false), // Duck Dodgers Starring Daffy Duck
+    (b"DY", EdSaveType::Eeprom4k, false), // Diddy Kong Racing
+    (b"DZ", EdSaveType::Sram768k, false), // Dezaemon 3D
+    (b"EA", EdSaveType::Eeprom4k, false), // PGA European Tour
+    (b"EP", EdSaveType::Eeprom16k, false), // Star Wars Episode I: Racer
+    (b"ER", EdSaveType::Eeprom4k, false), // Aero Fighters Assault
+    (b"EV", EdSaveType::Eeprom16k, false), // Neon Genesis Evangelion
+    (b"FG", EdSaveType::Eeprom4k, false), // Fighter Destiny 2
+    (b"FH", EdSaveType::Eeprom4k, false), // Bass Hunter 64
+    (b"FU", EdSaveType::Eeprom16k, false), // Conker's Bad Fur Day
+    (b"FW", EdSaveType::Eeprom4k, false), // F-1 World Grand Prix
+    (b"FX", EdSaveType::Eeprom4k, false), // Star Fox 64 / Lylat Wars
+    (b"FY", EdSaveType::Eeprom4k, false), // Kakutou Denshou: F-Cup Maniax
+    (b"FZ", EdSaveType::Sram, false), // F-Zero X
+    (b"GE", EdSaveType::Eeprom4k, false), // GoldenEye 007
+    (b"GL", EdSaveType::Eeprom4k, false), // Getter Love!!
+    (b"GU", EdSaveType::Eeprom4k, false), // Sin and Punishment
+    (b"GV", EdSaveType::Eeprom4k, false), // Glover
+    (b"HA", EdSaveType::Eeprom4k, false), // Bomberman 64 (Japan)
+    (b"HF", EdSaveType::Eeprom4k, false), // 64 Hanafuda: Tenshi no Yakusoku
+    (b"HP", EdSaveType::Eeprom4k, false), // Heiwa Pachinko World 64
+    (b"IB", EdSaveType::Sram, false), // Itoi Shigesato no Bass Tsuri No. 1
+    (b"IC", EdSaveType::Eeprom4k, false), // Indy Racing 2000
+    (b"IJ", EdSaveType::Eeprom4k, false), // Indiana Jones and the Infernal Machine
+    (b"IR", EdSaveType::Eeprom4k, false), // Utchan Nanchan no Hono no Challenger: Denryuu Ira Ira Bou
+    (b"JF", EdSaveType::FlashRam, false), // Jet Force Gemini
+    (b"JM", EdSaveType::Eeprom4k, false), // Earthworm Jim 3D
+    (b"K2", EdSaveType::Eeprom4k, false), // Snowboard Kids 2
+    (b"K4", EdSaveType::Eeprom4k, false), // Kirby 64: The Crystal Shards
+    (b"K4J\x00", EdSaveType::Sram, false), // Hoshi no Kirby 64 1.0, later versions use EEPROM
+    (b"K4J\x01", EdSaveType::Sram, false), // Hoshi no Kirby 64 1.1
+    (b"KA", EdSaveType::Eeprom4k, false), // Fighters Destiny
+    (b"KG", EdSaveType::Sram, false),     // Major League Baseball Featuring Ken Griffey Jr.
+    (b"KI", EdSaveType::Eeprom4k, false), // Killer Instinct Gold
+    (b"KJ", EdSaveType::FlashRam, false), // Ken Griffey Jr.'s Slugfest
+    (b"KT", EdSaveType::Eeprom4k, false), // Mario Kart 64
+    (b"LB", EdSaveType::Eeprom4k, false), // Mario Party
+    (b"LL", EdSaveType::Eeprom4k, false), // Last Legion UX
+    (b"LR", EdSaveType::Eeprom4k, false), // Lode Runner 3-D
+    (b"M6", EdSaveType::FlashRam, false), // Mega Man 64
+    (b"M8", EdSaveType::Eeprom16k, false), // Mario Tennis
+    (b"MF", EdSaveType::Sram, false),     // Mario Golf
+    (b"MG", EdSaveType::Eeprom4k, false), // Monaco Grand Prix
+    (b"MI", EdSaveType::Eeprom4k, false), // Mission: Impossible
+    (b"ML", EdSaveType::Eeprom4k, false), // Mickey's Speedway USA
+    (b"MO", EdSaveType::Eeprom4k, false), // Monopoly
+    (b"MQ", EdSaveType::FlashRam, false), // Paper Mario
+    (b"MR", EdSaveType::Eeprom4k, false), // Multi-Racing Championship
+    (b"MS", EdSaveType::Eeprom4k, false), // Morita Shougi 64
+    (b"MU", EdSaveType::Eeprom4k, false), // Big Mountain 2000
+    (b"MV", EdSaveType::Eeprom16k, false), // Mario Party 3
+    (b"MW", EdSaveType::Eeprom4k, false), // Mario Party 2
+    (b"MX", EdSaveType::Eeprom16k, false), // Excitebike 64
+    (b"MZ", EdSaveType::Eeprom4k, false), // Zool: Majou Tsukai Densetsu
+    (b"N6", EdSaveType::Eeprom4k, false), // Dr. Mario 64
+    (b"NA", EdSaveType::Eeprom4k, false), // Star Wars Episode I: Battle for Naboo
+    (b"OB", EdSaveType::Sram, false),     // Ogre Battle 64
+    (b"OS", EdSaveType::Eeprom4k, false), // 64 Oozumou
+    (b"P3", EdSaveType::FlashRam, false), // Pokemon Stadium 2
+    (b"PD", EdSaveType::Eeprom16k, false), // Perfect Dark
+    (b"PF", EdSaveType::FlashRam, false), // Pokemon Snap
+    (b"PG", EdSaveType::Eeprom4k, false), // Hey You, Pikachu!
+    (b"PH", EdSaveType::FlashRam, false), // Pokemon Snap Station
+    (b"PN", EdSaveType::FlashRam, false), // Pokemon Puzzle League
+    (b"PO", EdSaveType::FlashRam, false), // Pokemon Stadium
+    (b"PT", EdSaveType::Eeprom4k, false), // Puyo Puyon Party
+    (b"PW", EdSaveType::Eeprom4k, false), // Pilotwings 64
+    (b"PY", EdSaveType::Eeprom4k, false), // Puyo Puyo Sun 64
+    (b"RC", EdSaveType::Eeprom4k, false), // Top Gear Overdrive
+    (b"RE", EdSaveType::Sram, false),     // Resident Evil 2
+    (b"RI", EdSaveType::Sram, false),     // The New Tetris
+    (b"RS", EdSaveType::Eeprom4k, false), // Star Wars: Rogue Squadron
+    (b"RZ", EdSaveType::Eeprom16k, false), // Ridge Racer 64
+    (b"S6", EdSaveType::Eeprom4k, false), // Star Wars: Shadows of the Empire
+    (b"SA", EdSaveType::Eeprom4k, false), // Sonic Wings Assault
+    (b"SC", EdSaveType::Eeprom4k, false), // Starshot: Space Circus Fever
+    (b"SI", EdSaveType::FlashRam, false), // Fushigi no Dungeon: Fuurai no Shiren 2
+    (b"SM", EdSaveType::Eeprom4k, false), // Super Mario 64
+    (b"SN", EdSaveType::Eeprom4k, false), // Snow Speeder
+    (b"SQ", EdSaveType::FlashRam, false), // StarCraft 64
+    (b"SS", EdSaveType::Eeprom4k, false), // Super Robot Spirits
+    (b"SU", EdSaveType::Eeprom4k, false), // Rocket: Robot on Wheels
+    (b"SV", EdSaveType::Eeprom4k, false), // Space Station Silicon Valley
+    (b"T9", EdSaveType::FlashRam, false), // Tigger's Honey Hunt
+    (b"TB", EdSaveType::Eeprom4k, false), // Transformers: Beast Wars Metals 64
+    (b"TC", EdSaveType::Eeprom4k, false), // 64 Trump Collection
+    (b"TE", EdSaveType::Sram, false),     // 1080 Snowboarding
+    (b"TJ", EdSaveType::Eeprom4k, false), // Tom and Jerry in Fists of Furry
+    (b"TM", EdSaveType::Eeprom4k, false), // Mischief Makers
+    (b"TN", EdSaveType::Eeprom4k, false), // All Star Tennis '99
+    (b"TP", EdSaveType::Eeprom4k, false), // Tetrisphere
+    (b"TRJ", EdSaveType::Eeprom4k, false), // Top Gear Rally (Japan)
+    (b"TRP", EdSaveType::Eeprom4k, false), // Top Gear Rally (Europe), the US release has no save
+    (b"TW", EdSaveType::Eeprom4k, false), // 64 de Hakken!! Tamagotchi
+    (b"TX", EdSaveType::Eeprom4k, false), // Taz Express
+    (b"VL", EdSaveType::Eeprom4k, false), // V-Rally Edition 99
+    (b"VP", EdSaveType::Sram, false),     // Virtual Pro Wrestling 64
+    (b"VY", EdSaveType::Eeprom4k, false), // V-Rally Edition 99 (Japan)
+    (b"W4", EdSaveType::FlashRam, false), // WWF No Mercy
+    (b"WC", EdSaveType::Eeprom4k, false), // Wild Choppers
+    (b"WL", EdSaveType::Eeprom4k, false), // Waialae Country Club: True Golf Classics
+    (b"WQ", EdSaveType::Eeprom4k, false), // Rally Challenge 2000
+    (b"WR", EdSaveType::Eeprom4k, false), // Wave Race 64
+    (b"WX", EdSaveType::Sram, false),     // WWF WrestleMania 2000
+    (b"XO", EdSaveType::Eeprom4k, false), // Cruis'n Exotica
+    (b"YK", EdSaveType::Eeprom4k, false), // Yakouchuu II: Satsujin Kouro
+    (b"YS", EdSaveType::Eeprom16k, false), // Yoshi's Story
+    (b"YW", EdSaveType::Sram, false),     // Harvest Moon 64
+    (b"ZL", EdSaveType::Sram, false),     // The Legend of Zelda: Ocarina of Time
+    (b"ZS", EdSaveType::FlashRam, false), // The Legend of Zelda: Majora's Mask
+];
+
+/// Looks up `key`, the game id followed by the region code and version, preferring the most
+/// specific entry
+pub(crate) fn lookup(key: &[u8]) -> Option<(EdSaveType, bool)> {
+    SAVE_DB
+        .iter()
+        .filter(|(entry, _, _)| key.starts_with(entry))
+        .max_by_key(|(entry, _, _)| entry.len())
+        .map(|(_, save_type, rtc)| (*save_type, *rtc))
+}
@@ -0,0 +1,201 @@
+//! Save-state transfers for programs and emulator cores which expose their state over UNF.
+//!
+//! Requests and replies are `DataTypeBinary` packets starting with the `SS` prefix followed by an
+//! operation byte. All integers are big-endian.
+//!
+//! | Operation     | Direction       | Payload after the operation byte           |
+//! |---------------|-----------------|--------------------------------------------|
+//! | `0x01` save   | host to console | slot `u8`                                  |
+//! | `0x02` state  | console to host | slot `u8`, CRC32 `u32`, state data         |
+//! | `0x03` load   | host to console | slot `u8`, CRC32 `u32`, state data         |
+//! | `0x04` status | console to host | slot `u8`, status `u8`, `0` on success     |
+//! | `0x05` resume | host to console | slot `u8`                                  |
+//!
+//! The console answers save with a state packet and load and resume with a status packet. The
+//! CRC32 covers the state data, so corrupted states are rejected on both sides.
+
+use crate::Everdrive;
+use crate::PacketReader;
+use crate::ProgressOperation;
+use crate::unf::{UnfDataType, UnfSendPacket};
+
+const SS_PREFIX: &[u8; 2] = b"SS";
+
+const SS_OP_SAVE: u8 = 0x01;
+const SS_OP_STATE: u8 = 0x02;
+const SS_OP_LOAD: u8 = 0x03;
+const SS_OP_STATUS: u8 = 0x04;
+const SS_OP_RESUME: u8 = 0x05;
+
+/// Magic of save-state files, followed by the slot, CRC32 and state data
+const SS_FILE_MAGIC: &[u8; 8] = b"EDSTATE1";
+
+fn state_crc_mismatch() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "Save-state data does not match its CRC32",
+    )
+}
+
+/// A save-state blob downloaded from the console
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::Everdrive;
+/// use libeverdrive::savestate::SaveState;
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// // Share the state of slot 0 with another tester
+/// ed.state_save(0).unwrap().save("crash_repro.state").unwrap();
+///
+/// // On the other machine
+/// ed.state_load(&SaveState::load("crash_repro.state").unwrap()).unwrap();
+/// ed.state_resume(0).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveState {
+    pub slot: u8,
+    pub data: Vec<u8>,
+}
+
+impl SaveState {
+    pub fn new(slot: u8, data: Vec<u8>) -> Self {
+        Self { slot, data }
+    }
+
+    /// CRC32 of the state data
+    pub fn crc32(&self) -> u32 {
+        crate::rom::crc32(&self.data)
+    }
+
+    /// Writes the state to a file with its CRC32
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let mut file = SS_FILE_MAGIC.to_vec();
+        file.push(self.slot);
+        file.extend_from_slice(&self.crc32().to_be_bytes());
+        file.extend_from_slice(&self.data);
+
+        std::fs::write(path, file)
+    }
+
+    /// Reads a state written by `save`, returning an `InvalidData` error if it is corrupted
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::read(path)?;
+        let mut reader = PacketReader::new(&file);
+
+        if reader.consume_bytes(SS_FILE_MAGIC.len())? != SS_FILE_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not a save-state file",
+            ));
+        }
+
+        let slot = reader.consume_byte()?;
+        let crc = reader.consume_word()?;
+        let state = Self::new(slot, reader.consume_bytes(reader.remaining())?.to_vec());
+
+        if state.crc32() != crc {
+            return Err(state_crc_mismatch());
+        }
+
+        Ok(state)
+    }
+}
+
+impl Everdrive {
+    fn state_tx(&mut self, op: u8, slot: u8, data: Option<&[u8]>) -> std::io::Result<()> {
+        let data_len = data.map_or(0, |data| 4 + data.len());
+        let mut packet = UnfSendPacket::new(UnfDataType::DataTypeBinary, 4 + data_len)?;
+
+        let payload = packet.get_data();
+        payload[0..2].copy_from_slice(SS_PREFIX);
+        payload[2] = op;
+        payload[3] = slot;
+
+        if let Some(data) = data {
+            payload[4..8].copy_from_slice(&crate::rom::crc32(data).to_be_bytes());
+            payload[8..].copy_from_slice(data);
+        }
+
+        self.unf_tx(&packet)
+    }
+
+    /// Waits for a save-state reply of `op` for `slot` and returns its payload after the slot.
+    /// Other packets received while waiting are dropped.
+    fn state_rx(&mut self, op: u8, slot: u8) -> std::io::Result<Vec<u8>> {
+        loop {
+            let packet = self.unf_rx()?;
+            let data = packet.get_data();
+
+            if packet.get_datatype() == UnfDataType::DataTypeBinary
+                && data.len() >= 4
+                && data[0..2] == *SS_PREFIX
+                && data[2] == op
+                && data[3] == slot
+            {
+                return Ok(data[4..].to_vec());
+            }
+        }
+    }
+
+    fn state_status(&mut self, slot: u8) -> std::io::Result<()> {
+        let reply = self.state_rx(SS_OP_STATUS, slot)?;
+
+        match reply.first() {
+            Some(0) => Ok(()),
+            Some(status) => Err(std::io::Error::other(format!(
+                "Console rejected the save-state request with status {:#04X}",
+                status
+            ))),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid save-state status reply",
+            )),
+        }
+    }
+
+    /// Asks the console to save its state into `slot` and downloads it. Returns an `InvalidData`
+    /// error if the state does not match its CRC32. Other packets received while waiting are dropped.
+    /// See the `savestate` module for the protocol.
+    pub fn state_save(&mut self, slot: u8) -> std::io::Result<SaveState> {
+        let reply = self.with_progress(ProgressOperation::SaveStateBackup, None, |ed| {
+            ed.state_tx(SS_OP_SAVE, slot, None)?;
+
+            let reply = ed.state_rx(SS_OP_STATE, slot)?;
+            ed.progress_advance(reply.len());
+            Ok(reply)
+        })?;
+
+        let mut reader = PacketReader::new(&reply);
+
+        let crc = reader.consume_word()?;
+        let state = SaveState::new(slot, reader.consume_bytes(reader.remaining())?.to_vec());
+
+        if state.crc32() != crc {
+            return Err(state_crc_mismatch());
+        }
+
+        Ok(state)
+    }
+
+    /// Uploads `state` into its slot on the console, without resuming it
+    pub fn state_load(&mut self, state: &SaveState) -> std::io::Result<()> {
+        self.with_progress(
+            ProgressOperation::SaveStateRestore,
+            Some(state.data.len() as u64),
+            |ed| {
+                ed.state_tx(SS_OP_LOAD, state.slot, Some(&state.data))?;
+                ed.progress_advance(state.data.len());
+                ed.state_status(state.slot)
+            },
+        )
+    }
+
+    /// Asks the console to resume execution from the state in `slot`
+    pub fn state_resume(&mut self, slot: u8) -> std::io::Result<()> {
+        self.state_tx(SS_OP_RESUME, slot, None)?;
+        self.state_status(slot)
+    }
+}
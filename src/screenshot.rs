@@ -0,0 +1,314 @@
+//! Decoding of framebuffer screenshots sent by the console.
+//!
+//! A screenshot is sent as a `DataTypeHeader` packet followed by a `DataTypeScreenshot` packet with the
+//! framebuffer contents. The header holds four big-endian words: the `DataTypeScreenshot` datatype, bytes
+//! per pixel (2 for RGBA5551 or 4 for RGBA8888 framebuffers), width and height.
+
+use crate::unf::{UnfDataType, UnfRecvPacket};
+
+/// Decoded screenshot in 8-bit RGBA
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels in row order, 4 bytes per pixel. Alpha is always opaque, since the framebuffer alpha
+    /// holds coverage values rather than transparency.
+    pub rgba: Vec<u8>,
+}
+
+fn invalid_screenshot(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Expands a 5-bit color channel to 8 bits
+fn expand5(value: u16) -> u8 {
+    let value = (value & 0x1F) as u8;
+    (value << 3) | (value >> 2)
+}
+
+impl Screenshot {
+    /// Decodes a framebuffer with `depth` bytes per pixel
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::screenshot::Screenshot;
+    ///
+    /// // One white and one red RGBA5551 pixel
+    /// let screenshot = Screenshot::decode(2, 2, 1, &[0xFF, 0xFF, 0xF8, 0x01]).unwrap();
+    ///
+    /// assert_eq!(screenshot.rgba, [255, 255, 255, 255, 255, 0, 0, 255]);
+    /// ```
+    pub fn decode(depth: u32, width: u32, height: u32, data: &[u8]) -> std::io::Result<Self> {
+        // The dimensions come from the console, sizes which overflow can't match the data
+        let too_large = || invalid_screenshot("Screenshot dimensions are too large");
+        let pixels = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or_else(too_large)?;
+        let size = pixels.checked_mul(depth as usize).ok_or_else(too_large)?;
+        pixels.checked_mul(4).ok_or_else(too_large)?;
+
+        if data.len() < size {
+            return Err(invalid_screenshot(
+                "Screenshot data is smaller than the framebuffer",
+            ));
+        }
+
+        let rgba = match depth {
+            2 => data
+                .chunks_exact(2)
+                .take(pixels)
+                .flat_map(|pixel| {
+                    let pixel = u16::from_be_bytes([pixel[0], pixel[1]]);
+                    [
+                        expand5(pixel >> 11),
+                        expand5(pixel >> 6),
+                        expand5(pixel >> 1),
+                        0xFF,
+                    ]
+                })
+                .collect(),
+            4 => data
+                .chunks_exact(4)
+                .take(pixels)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0xFF])
+                .collect(),
+            _ => return Err(invalid_screenshot("Unsupported screenshot depth")),
+        };
+
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// Writes the screenshot to a PNG file
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        image::save_buffer(
+            path,
+            &self.rgba,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(std::io::Error::other)
+    }
+}
+
+/// Pairs screenshot header packets with the framebuffer packets following them
+#[derive(Debug, Default)]
+pub struct ScreenshotDecoder {
+    header: Option<(u32, u32, u32)>,
+}
+
+impl ScreenshotDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a received packet to the decoder. Returns the decoded screenshot when `packet` completes one,
+    /// `None` for packets which are not part of a screenshot or if no header has been received.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::screenshot::ScreenshotDecoder;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let mut decoder = ScreenshotDecoder::new();
+    ///
+    /// loop {
+    ///     if let Some(screenshot) = decoder.push(&ed.unf_rx().unwrap()) {
+    ///         let screenshot = screenshot.unwrap();
+    ///         println!("{}x{} screenshot", screenshot.width, screenshot.height);
+    ///     }
+    /// }
+    /// ```
+    pub fn push(&mut self, packet: &UnfRecvPacket) -> Option<std::io::Result<Screenshot>> {
+        let data = packet.get_data();
+
+        match packet.get_datatype() {
+            UnfDataType::DataTypeHeader if data.len() >= 16 => {
+                let word =
+                    |i: usize| u32::from_be_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+
+                if word(0) == u8::from(UnfDataType::DataTypeScreenshot) as u32 {
+                    self.header = Some((word(1), word(2), word(3)));
+                }
+
+                None
+            }
+            UnfDataType::DataTypeScreenshot => {
+                let (depth, width, height) = self.header.take()?;
+                Some(Screenshot::decode(depth, width, height, data))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl crate::Everdrive {
+    /// Receives `count` screenshots and saves them as numbered PNG files (`frame_0000.png`, ...) in `dir`,
+    /// returning their paths. Stops early with the frames saved so far when `stop` is cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let frames = ed.unf_capture_screenshots("captures", 10, &CancellationToken::new()).unwrap();
+    /// println!("Saved {} frames", frames.len());
+    /// ```
+    pub fn unf_capture_screenshots(
+        &mut self,
+        dir: impl AsRef<std::path::Path>,
+        count: usize,
+        stop: &crate::CancellationToken,
+    ) -> std::io::Result<Vec<std::path::PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut decoder = ScreenshotDecoder::new();
+        let mut frames = Vec::new();
+
+        while frames.len() < count && !stop.is_cancelled() {
+            let packet = match self.unf_rx() {
+                Ok(packet) => packet,
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(err) => return Err(err),
+            };
+
+            if let Some(screenshot) = decoder.push(&packet) {
+                let path = dir.join(format!("frame_{:04}.png", frames.len()));
+                screenshot?.save_png(&path)?;
+                frames.push(path);
+            }
+        }
+
+        Ok(frames)
+    }
+}
+
+/// Streams decoded screenshots from a background thread, for live previews of the console output.
+///
+/// The stream takes ownership of the `Everdrive` while it runs, `stop` returns it. Frames are buffered
+/// up to a fixed capacity; when the consumer falls behind, new frames are dropped and counted instead
+/// of delaying the receive loop. Packets other than screenshots are discarded.
+#[derive(Debug)]
+pub struct FrameStream {
+    frames: std::sync::mpsc::Receiver<Screenshot>,
+    dropped: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    stop: crate::CancellationToken,
+    thread: Option<std::thread::JoinHandle<std::io::Result<crate::Everdrive>>>,
+}
+
+impl FrameStream {
+    /// Starts receiving screenshots from `ed`, buffering up to `capacity` decoded frames
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use libeverdrive::screenshot::FrameStream;
+    ///
+    /// let ed = Everdrive::new("COM3").unwrap();
+    /// let stream = FrameStream::start(ed, 4);
+    ///
+    /// for frame in stream.iter().take(100) {
+    ///     println!("{}x{} frame, {} dropped", frame.width, frame.height, stream.dropped_frames());
+    /// }
+    ///
+    /// let ed = stream.stop().unwrap();
+    /// ```
+    pub fn start(mut ed: crate::Everdrive, capacity: usize) -> Self {
+        let (tx, frames) = std::sync::mpsc::sync_channel(capacity);
+        let dropped = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let stop = crate::CancellationToken::new();
+
+        let thread_dropped = dropped.clone();
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let mut decoder = ScreenshotDecoder::new();
+
+            while !thread_stop.is_cancelled() {
+                let packet = match ed.unf_rx() {
+                    Ok(packet) => packet,
+                    Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                    Err(err) => return Err(err),
+                };
+
+                match decoder.push(&packet) {
+                    Some(Ok(frame)) => match tx.try_send(frame) {
+                        Ok(()) => {}
+                        Err(std::sync::mpsc::TrySendError::Full(_)) => {
+                            thread_dropped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                        // The stream was dropped without stopping, nobody is listening anymore
+                        Err(std::sync::mpsc::TrySendError::Disconnected(_)) => break,
+                    },
+                    Some(Err(_)) => {
+                        thread_dropped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    None => {}
+                }
+            }
+
+            Ok(ed)
+        });
+
+        Self {
+            frames,
+            dropped,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Blocks until the next frame is received. Fails once the stream has ended, for example
+    /// after a receive error, which is returned by `stop`.
+    pub fn recv(&self) -> Result<Screenshot, std::sync::mpsc::RecvError> {
+        self.frames.recv()
+    }
+
+    /// Returns the next frame if one is buffered, without blocking
+    pub fn try_recv(&self) -> Result<Screenshot, std::sync::mpsc::TryRecvError> {
+        self.frames.try_recv()
+    }
+
+    /// Returns a blocking iterator over received frames
+    pub fn iter(&self) -> std::sync::mpsc::Iter<'_, Screenshot> {
+        self.frames.iter()
+    }
+
+    /// Number of frames dropped because the buffer was full or the frame could not be decoded
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Stops the stream and returns the `Everdrive`, or the error which ended the stream
+    pub fn stop(mut self) -> std::io::Result<crate::Everdrive> {
+        self.stop.cancel();
+
+        self.thread
+            .take()
+            .unwrap()
+            .join()
+            .map_err(|_| std::io::Error::other("Frame stream thread panicked"))?
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        self.stop.cancel();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
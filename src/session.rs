@@ -0,0 +1,137 @@
+//! Runtime tracking of the session phase, so operations which the device can't serve in the current
+//! phase fail with a descriptive error instead of hanging until a timeout.
+//!
+//! The Everdrive OS answers commands until a program is started with `ed_app_start`, after which only
+//! the program can talk over USB, using UNF packets.
+
+use crate::Everdrive;
+
+/// Phase of the connection, see `Everdrive::session_state`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SessionState {
+    /// Nothing is known yet, such as right after opening the port. All operations are allowed, so a
+    /// program started in an earlier session can still be talked to.
+    #[default]
+    Unknown,
+    /// The Everdrive OS answered the status handshake and no rom has been written since
+    Menu,
+    /// Rom data has been written, the program can be started
+    RomLoaded,
+    /// The program has been started, the Everdrive OS no longer answers commands
+    Running,
+}
+
+/// Error payload returned for operations which are not valid in the current session state.
+/// Available through `std::io::Error::get_ref`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Everdrive, SessionError};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// ed.ed_status().unwrap();
+///
+/// // No rom has been loaded yet
+/// let err = ed.ed_app_start(None).unwrap_err();
+/// let session = err.get_ref().and_then(|e| e.downcast_ref::<SessionError>());
+///
+/// println!("{:?}", session);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionError {
+    /// State the operation was rejected in
+    pub state: SessionState,
+    /// Name of the rejected operation
+    pub operation: &'static str,
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.state {
+            SessionState::Running => write!(
+                f,
+                "{} is not available while a program is running, reset the console to the menu and call reset_session",
+                self.operation
+            ),
+            SessionState::Menu => write!(
+                f,
+                "{} requires a rom, load one before starting it",
+                self.operation
+            ),
+            _ => write!(
+                f,
+                "{} requires a running program, start the rom with ed_app_start first",
+                self.operation
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl Everdrive {
+    /// Returns the tracked phase of the connection. Commands which the device can't serve in the
+    /// current phase return an error carrying a `SessionError`:
+    ///
+    /// - Everdrive OS commands once a program is running
+    /// - `ed_app_start` after the status handshake without a rom being written
+    /// - UNF packets before the program has been started
+    ///
+    /// `reset_session` returns to `SessionState::Unknown` before running the handshake again.
+    pub fn session_state(&self) -> SessionState {
+        self.session
+    }
+
+    /// Overrides the tracked phase, such as `SessionState::Running` when attaching to a program
+    /// started by another process
+    pub fn set_session_state(&mut self, state: SessionState) {
+        self.session = state;
+    }
+
+    #[cfg(any(feature = "edos", feature = "unf"))]
+    fn session_error(&self, operation: &'static str) -> std::io::Error {
+        std::io::Error::other(SessionError {
+            state: self.session,
+            operation,
+        })
+    }
+
+    /// Checks that an Everdrive OS command can be sent, `app_start` selects the stricter check for
+    /// starting the program
+    #[cfg(feature = "edos")]
+    pub(crate) fn session_check_command(&self, app_start: bool) -> std::io::Result<()> {
+        match self.session {
+            SessionState::Running => Err(self.session_error("Everdrive OS command")),
+            SessionState::Menu if app_start => Err(self.session_error("ed_app_start")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns to the menu before reloading over a running program, for flows which reload without
+    /// the caller resetting the session. Fails with the `SessionError` asking to reset the console if
+    /// the Everdrive OS doesn't answer the handshake, since the program is then still running.
+    #[cfg(any(feature = "watch", feature = "rpc"))]
+    pub(crate) fn session_reenter_menu(&mut self, operation: &'static str) -> std::io::Result<()> {
+        if self.session != SessionState::Running {
+            return Ok(());
+        }
+
+        if self.reset_session().is_err() {
+            self.session = SessionState::Running;
+            return Err(self.session_error(operation));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that UNF packets can be exchanged with the program
+    #[cfg(feature = "unf")]
+    pub(crate) fn session_check_unf(&self) -> std::io::Result<()> {
+        match self.session {
+            SessionState::Menu | SessionState::RomLoaded => Err(self.session_error("UNF transfer")),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Everdrive;
+
+/// Counters for data transferred over the serial port. Times only include the time spent
+/// in serial reads and writes, so throughput reflects the link rather than host side processing.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransferStats {
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    pub write_time: std::time::Duration,
+    pub read_time: std::time::Duration,
+}
+
+impl TransferStats {
+    /// Write throughput in bytes per second, or 0 if nothing has been written
+    pub fn write_throughput(&self) -> f64 {
+        Self::throughput(self.bytes_written, self.write_time)
+    }
+
+    /// Read throughput in bytes per second, or 0 if nothing has been read
+    pub fn read_throughput(&self) -> f64 {
+        Self::throughput(self.bytes_read, self.read_time)
+    }
+
+    fn throughput(bytes: u64, time: std::time::Duration) -> f64 {
+        if time.is_zero() {
+            0.0
+        } else {
+            bytes as f64 / time.as_secs_f64()
+        }
+    }
+}
+
+impl Everdrive {
+    /// Returns the transfer statistics collected since the device was opened or the stats were last reset.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    /// use std::fs;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let rom_data = fs::read("your_rom.z64").unwrap();
+    ///
+    /// ed.reset_transfer_stats();
+    /// ed.ed_load_rom(rom_data, None, None, None).unwrap();
+    ///
+    /// let stats = ed.transfer_stats();
+    /// println!("{:.1} MB/s", stats.write_throughput() / (1024.0 * 1024.0));
+    /// ```
+    pub fn transfer_stats(&self) -> &TransferStats {
+        &self.stats
+    }
+
+    pub fn reset_transfer_stats(&mut self) {
+        self.stats = TransferStats::default();
+    }
+}
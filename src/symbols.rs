@@ -0,0 +1,165 @@
+//! Address to symbol resolution for debug output, from ELF symbol tables or linker map files.
+
+/// Symbol from an ELF symbol table or map file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    /// Size of the symbol in bytes, 0 if unknown
+    pub size: u32,
+    /// True if the symbol is a function, false for data and symbols of unknown kind
+    pub is_function: bool,
+}
+
+/// Symbols sorted by address for resolving addresses in debug output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+/// Returns true if `addr` looks like a KSEG0/KSEG1 address, where code and data are located on the N64
+fn is_kseg_address(addr: u32) -> bool {
+    (0x80000000..0xC0000000).contains(&addr)
+}
+
+impl SymbolTable {
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.address);
+        Self { symbols }
+    }
+
+    /// Creates a symbol table from the symbols of a parsed ELF file
+    pub fn from_elf(elf: &crate::elf::Elf) -> Self {
+        Self::new(elf.symbols.clone())
+    }
+
+    /// Parses a symbol list as produced by `nm` (`80000400 T main`) or a GNU ld map file
+    /// (`0x0000000080000400  main`). Lines which don't start with an address followed by a symbol name are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::symbols::SymbolTable;
+    ///
+    /// let symbols = SymbolTable::parse_map("80000400 T main\n80000480 t update\n");
+    ///
+    /// assert_eq!(symbols.resolve(0x80000410).unwrap().name, "main");
+    /// ```
+    pub fn parse_map(map: &str) -> Self {
+        let symbols = map
+            .lines()
+            .filter_map(|line| {
+                let mut tokens = line.split_whitespace();
+                let address = tokens.next()?;
+                let rest: Vec<&str> = tokens.collect();
+
+                let address =
+                    u64::from_str_radix(address.trim_start_matches("0x"), 16).ok()? as u32;
+
+                // nm lines have a type letter between the address and name
+                let (kind, name) = match rest.as_slice() {
+                    [name] => (None, *name),
+                    [kind, name] if kind.len() == 1 => (kind.chars().next(), *name),
+                    _ => return None,
+                };
+
+                if !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "_.$".contains(c))
+                {
+                    return None;
+                }
+
+                Some(Symbol {
+                    name: name.to_string(),
+                    address,
+                    size: 0,
+                    is_function: matches!(kind, Some('T' | 't')),
+                })
+            })
+            .collect();
+
+        Self::new(symbols)
+    }
+
+    /// Loads a symbol table from an ELF file or a map file, depending on the file contents
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+
+        if data.starts_with(b"\x7fELF") {
+            Ok(Self::from_elf(&crate::elf::Elf::parse(&data)?))
+        } else {
+            Ok(Self::parse_map(&String::from_utf8_lossy(&data)))
+        }
+    }
+
+    /// Returns the symbol containing `addr`: the closest symbol at or below `addr`, if `addr` is within
+    /// its size. Symbols of unknown size match any address up to the next symbol.
+    pub fn resolve(&self, addr: u32) -> Option<&Symbol> {
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= addr);
+        let symbol = self.symbols[..index].last()?;
+
+        if symbol.size == 0 || addr - symbol.address < symbol.size {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    /// Appends the symbol name to every address in `text` which resolves to a symbol, such as
+    /// `EPC 0x80001234` becoming `EPC 0x80001234 <main+0x34>`. Only 8 digit hexadecimal addresses
+    /// in KSEG0/KSEG1 are considered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::symbols::SymbolTable;
+    ///
+    /// let symbols = SymbolTable::parse_map("80000400 T main\n");
+    ///
+    /// assert_eq!(symbols.annotate("crash at 80000410"), "crash at 80000410 <main+0x10>");
+    /// ```
+    pub fn annotate(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find(|c: char| c.is_ascii_hexdigit()) {
+            let end = rest[start..]
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .map_or(rest.len(), |len| start + len);
+
+            let word = &rest[start..end];
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+
+            let digits = word.strip_prefix("0x").unwrap_or(word);
+
+            // Words preceded by letters (such as "a80001234") are not addresses
+            let standalone = out[..out.len() - word.len()]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_ascii_alphanumeric());
+
+            if digits.len() != 8 || !standalone {
+                continue;
+            }
+
+            let symbol = u32::from_str_radix(digits, 16)
+                .ok()
+                .filter(|addr| is_kseg_address(*addr))
+                .and_then(|addr| Some((addr, self.resolve(addr)?)));
+
+            if let Some((addr, symbol)) = symbol {
+                match addr - symbol.address {
+                    0 => out.push_str(&format!(" <{}>", symbol.name)),
+                    offset => out.push_str(&format!(" <{}+{:#x}>", symbol.name, offset)),
+                }
+            }
+        }
+
+        out.push_str(rest);
+        out
+    }
+}
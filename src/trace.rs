@@ -0,0 +1,51 @@
+//! Internal helpers for the optional `tracing` instrumentation. Without the `tracing`
+//! feature the macros expand to nothing.
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        tracing::$level!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {};
+}
+
+/// Enters a span for the rest of the enclosing scope
+#[cfg(feature = "tracing")]
+#[allow(unused_macros)]
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
+macro_rules! trace_span {
+    ($($arg:tt)*) => {};
+}
+
+pub(crate) use trace_event;
+// Only used by the EDOS commands
+#[allow(unused_imports)]
+pub(crate) use trace_span;
+
+/// Formats bytes as a hex dump for TRACE level events
+#[cfg(feature = "tracing")]
+pub(crate) struct HexDump<'a>(pub &'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//! Transfers larger than a single UNF packet.
+//!
+//! Large payloads are split into chunks sent as separate packets. Every chunk starts with an 8 byte
+//! header of the big-endian `u32` chunk index and total chunk count, followed by the chunk data.
+
+use crate::Everdrive;
+use crate::PacketReader;
+use crate::ProgressOperation;
+use crate::unf::{UnfDataType, UnfRecvPacket, UnfSendPacket};
+
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Largest data size of a single UNF packet
+const UNF_MAX_PACKET_SIZE: usize = 0x00FFFFFF;
+
+fn invalid_chunk(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn chunk_count(size: u64, chunk_size: usize) -> std::io::Result<u32> {
+    if chunk_size == 0 || chunk_size > UNF_MAX_PACKET_SIZE - CHUNK_HEADER_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Chunk size must be between 1 byte and the UNF packet size limit minus the chunk header",
+        ));
+    }
+
+    u32::try_from(size.div_ceil(chunk_size as u64).max(1)).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Payload has too many chunks for the chunk size",
+        )
+    })
+}
+
+fn chunk_packet(
+    datatype: UnfDataType,
+    index: u32,
+    total: u32,
+    chunk: &[u8],
+) -> std::io::Result<UnfSendPacket> {
+    let mut packet = UnfSendPacket::new(datatype, CHUNK_HEADER_SIZE + chunk.len())?;
+    let data = packet.get_data();
+    data[0..4].copy_from_slice(&index.to_be_bytes());
+    data[4..8].copy_from_slice(&total.to_be_bytes());
+    data[CHUNK_HEADER_SIZE..].copy_from_slice(chunk);
+    Ok(packet)
+}
+
+/// Reassembles payloads sent in chunks. Chunks must arrive in order, a chunk with index 0 starts a new payload.
+#[derive(Debug, Default)]
+pub struct UnfReassembler {
+    datatype: Option<UnfDataType>,
+    next_index: u32,
+    /// Chunk count declared by the first chunk
+    total: u32,
+    data: Vec<u8>,
+}
+
+impl UnfReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk packet, returning the complete payload as a packet once its last chunk has been received.
+    /// Returns an `InvalidData` error for malformed or out of order chunks and for chunks declaring a different
+    /// chunk count than the first one, such as chunks of interleaved transfers, discarding the partial payload.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, UnfDataType, UnfReassembler};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let mut reassembler = UnfReassembler::new();
+    ///
+    /// loop {
+    ///     let packet = ed.unf_rx().unwrap();
+    ///
+    ///     if packet.get_datatype() == UnfDataType::DataTypeBinary {
+    ///         if let Some(payload) = reassembler.push(&packet).unwrap() {
+    ///             println!("Received {} bytes", payload.get_data().len());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn push(&mut self, packet: &UnfRecvPacket) -> std::io::Result<Option<UnfRecvPacket>> {
+        let data = packet.get_data();
+
+        if data.len() < CHUNK_HEADER_SIZE {
+            self.reset();
+            return Err(invalid_chunk("Chunk is smaller than the chunk header"));
+        }
+
+        let mut reader = PacketReader::new(data);
+        let index = reader.consume_word()?;
+        let total = reader.consume_word()?;
+
+        if index == 0 {
+            self.reset();
+            self.datatype = Some(packet.get_datatype());
+            self.total = total;
+        }
+
+        if index != self.next_index
+            || index >= total
+            || self.datatype != Some(packet.get_datatype())
+        {
+            self.reset();
+            return Err(invalid_chunk("Chunk received out of order"));
+        }
+
+        if total != self.total {
+            self.reset();
+            return Err(invalid_chunk(
+                "Chunk count differs from the first chunk of the payload",
+            ));
+        }
+
+        self.data.extend_from_slice(&data[CHUNK_HEADER_SIZE..]);
+        self.next_index += 1;
+
+        if self.next_index < total {
+            return Ok(None);
+        }
+
+        let datatype = packet.get_datatype();
+        let payload = std::mem::take(&mut self.data);
+        self.reset();
+
+        Ok(Some(UnfRecvPacket::new(datatype, payload)))
+    }
+
+    fn reset(&mut self) {
+        self.datatype = None;
+        self.next_index = 0;
+        self.total = 0;
+        self.data.clear();
+    }
+}
+
+impl Everdrive {
+    /// Sends a payload of any size as a sequence of chunk packets of up to `chunk_size` data bytes,
+    /// see the `transfer` module for the chunk format.
+    pub fn unf_tx_chunked(
+        &mut self,
+        datatype: UnfDataType,
+        payload: &[u8],
+        chunk_size: usize,
+    ) -> std::io::Result<()> {
+        let total = chunk_count(payload.len() as u64, chunk_size)?;
+
+        for (index, chunk) in (0..total).zip(payload.chunks(chunk_size).chain(
+            // An empty payload is sent as a single empty chunk
+            payload.is_empty().then_some(&[][..]),
+        )) {
+            self.check_cancelled()?;
+            self.unf_tx(&chunk_packet(datatype, index, total, chunk)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives chunk packets until a complete payload has been reassembled and returns it as one packet.
+    /// Every received packet is treated as a chunk, use `UnfReassembler` for streams mixing chunked and
+    /// regular packets.
+    pub fn unf_rx_chunked(&mut self) -> std::io::Result<UnfRecvPacket> {
+        let mut reassembler = UnfReassembler::new();
+
+        loop {
+            let packet = self.unf_rx()?;
+
+            if let Some(payload) = reassembler.push(&packet)? {
+                return Ok(payload);
+            }
+        }
+    }
+
+    /// Sends a host file to the console in `DataTypeBinary` chunk packets of up to `chunk_size` data bytes,
+    /// reading the file one chunk at a time. See the `transfer` module for the chunk format.
+    ///
+    /// `progress` is called after every chunk with the number of bytes sent so far and the file size.
+    /// The chunks are also reported to `EverdriveEvents::on_progress` as a `FileTransfer`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.unf_send_file("assets/level1.bin", 0x8000, |sent, total| {
+    ///     println!("{}/{} bytes", sent, total);
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn unf_send_file(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        chunk_size: usize,
+        mut progress: impl FnMut(u64, u64),
+    ) -> std::io::Result<()> {
+        let mut file = std::fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        let total = chunk_count(size, chunk_size)?;
+
+        let mut chunk = vec![0; chunk_size];
+        let mut sent = 0;
+
+        self.with_progress(ProgressOperation::FileTransfer, Some(size), |ed| {
+            for index in 0..total {
+                ed.check_cancelled()?;
+
+                let len = crate::read_chunk(&mut file, &mut chunk)?;
+                ed.unf_tx(&chunk_packet(
+                    UnfDataType::DataTypeBinary,
+                    index,
+                    total,
+                    &chunk[..len],
+                )?)?;
+
+                sent += len as u64;
+                ed.progress_advance(len);
+                progress(sent, size);
+            }
+
+            Ok(())
+        })
+    }
+}
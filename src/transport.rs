@@ -0,0 +1,333 @@
+//! Byte transports used to talk to the device. The serial port is the default transport,
+//! the capture transports allow recording sessions and replaying them without hardware.
+//!
+//! Other backends, such as WebSerial in a browser, implement `Transport` and are passed to
+//! `Everdrive::from_transport`. The protocol code only needs blocking reads which return a `TimedOut`
+//! error when no data arrives in time. WebSerial is asynchronous, so a WASM backend either reads from
+//! a buffer filled by the JavaScript side (returning `TimedOut` when it is empty and polling with calls
+//! such as `unf_try_rx`), or runs the protocol in a worker which can block. Timing goes through
+//! helpers which skip sleeps and report no elapsed time on `wasm32-unknown-unknown`.
+//!
+//! On `wasm32` targets the serial port backend is not compiled in, along with the functions built on
+//! it such as `Everdrive::new`, `Everdrive::detect` and `Everdrive::find_usb_devices`, and devices are
+//! only opened through `Everdrive::from_transport`.
+//!
+//! Capture files start with the `EDCAP001` magic followed by entries of a direction byte
+//! (`W` for data written to the device, `R` for data read from it), a big-endian `u64` timestamp
+//! in microseconds since the start of the capture, a big-endian `u32` length and the data.
+
+use crate::clock::Stopwatch;
+use std::io::{Read, Write};
+
+const CAPTURE_MAGIC: &[u8; 8] = b"EDCAP001";
+
+/// Transport carrying the bytes exchanged with the device
+pub trait Transport: Read + Write + Send + std::fmt::Debug {
+    fn set_timeout(&mut self, timeout: std::time::Duration) -> std::io::Result<()>;
+
+    /// Returns the number of received bytes which can be read without blocking
+    fn bytes_to_read(&self) -> std::io::Result<u32>;
+
+    /// Discards received bytes which have not been read yet
+    fn clear_input(&mut self) -> std::io::Result<()>;
+
+    /// Discards bytes queued for sending which have not been transmitted yet
+    fn clear_output(&mut self) -> std::io::Result<()>;
+}
+
+/// Size of the receive and transmit buffers requested from the Windows serial driver. The default
+/// buffers of the virtual COM port driver are a few KB, which overflow on rom reads and UNF bursts.
+#[cfg(windows)]
+const WINDOWS_DRIVER_BUFFER_SIZE: u32 = 0x10000;
+
+/// Opens the serial port `port_name` for talking to the Everdrive.
+///
+/// On Windows, names are accepted with or without the `\\.\` device namespace prefix, so ports
+/// above `COM9` such as `COM12` open like any other, and the driver buffers are enlarged. The FT245R
+/// FIFO bridge of the Everdrive 64 ignores the modem control lines, so DTR and RTS are left as the
+/// driver sets them.
+///
+/// Errors carry a `PortOpenError` telling missing devices, missing permissions and ports held by
+/// other processes apart.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn open_serial(port_name: &str) -> std::io::Result<Box<dyn serialport::SerialPort>> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+
+        // serialport adds the prefix itself, some versions add it a second time
+        let port_name = port_name.trim();
+        let port_name = port_name
+            .strip_prefix(r"\\.\")
+            .or_else(|| port_name.strip_prefix("//./"))
+            .unwrap_or(port_name);
+
+        let port = serialport::new(port_name, 115_200)
+            .open_native()
+            .map_err(|err| crate::PortOpenError::from_serial(port_name, err))?;
+
+        // Best effort, the driver keeps its default buffers if it rejects the sizes
+        // SAFETY: the handle is owned by `port` and stays open for the call
+        let resized = unsafe {
+            winapi::um::commapi::SetupComm(
+                port.as_raw_handle() as winapi::um::winnt::HANDLE,
+                WINDOWS_DRIVER_BUFFER_SIZE,
+                WINDOWS_DRIVER_BUFFER_SIZE,
+            )
+        };
+
+        if resized == 0 {
+            crate::trace::trace_event!(
+                debug,
+                error = %std::io::Error::last_os_error(),
+                "serial driver buffers not resized"
+            );
+        }
+
+        Ok(Box::new(port))
+    }
+
+    #[cfg(not(windows))]
+    {
+        serialport::new(port_name, 115_200)
+            .open()
+            .map_err(|err| crate::PortOpenError::from_serial(port_name, err))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Transport for Box<dyn serialport::SerialPort> {
+    fn set_timeout(&mut self, timeout: std::time::Duration) -> std::io::Result<()> {
+        Ok(serialport::SerialPort::set_timeout(self.as_mut(), timeout)?)
+    }
+
+    fn bytes_to_read(&self) -> std::io::Result<u32> {
+        Ok(serialport::SerialPort::bytes_to_read(self.as_ref())?)
+    }
+
+    fn clear_input(&mut self) -> std::io::Result<()> {
+        Ok(self.clear(serialport::ClearBuffer::Input)?)
+    }
+
+    fn clear_output(&mut self) -> std::io::Result<()> {
+        Ok(self.clear(serialport::ClearBuffer::Output)?)
+    }
+}
+
+/// Transport wrapper which records every byte exchanged through the inner transport to a capture
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::Everdrive;
+/// use libeverdrive::transport::RecordingTransport;
+/// use std::fs::File;
+///
+/// let port = serialport::new("COM3", 115_200).open().unwrap();
+/// let capture = File::create("session.edcap").unwrap();
+///
+/// let mut ed = Everdrive::from_transport(RecordingTransport::new(port, capture).unwrap()).unwrap();
+/// ed.ed_status().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RecordingTransport<T, W: Write> {
+    inner: T,
+    capture: std::io::BufWriter<W>,
+    start: Stopwatch,
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, capture: W) -> std::io::Result<Self> {
+        let mut capture = std::io::BufWriter::new(capture);
+        capture.write_all(CAPTURE_MAGIC)?;
+
+        Ok(Self {
+            inner,
+            capture,
+            start: Stopwatch::start(),
+        })
+    }
+
+    fn record(&mut self, direction: u8, data: &[u8]) -> std::io::Result<()> {
+        let timestamp = self.start.elapsed().unwrap_or_default().as_micros() as u64;
+
+        self.capture.write_all(&[direction])?;
+        self.capture.write_all(&timestamp.to_be_bytes())?;
+        self.capture.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.capture.write_all(data)
+    }
+}
+
+impl<T: Transport, W: Write> Read for RecordingTransport<T, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.record(b'R', &buf[..len])?;
+        Ok(len)
+    }
+}
+
+impl<T: Transport, W: Write> Write for RecordingTransport<T, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.record(b'W', &buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.capture.flush()?;
+        self.inner.flush()
+    }
+}
+
+impl<T: Transport, W: Write + Send + std::fmt::Debug> Transport for RecordingTransport<T, W> {
+    fn set_timeout(&mut self, timeout: std::time::Duration) -> std::io::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn bytes_to_read(&self) -> std::io::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+
+    fn clear_input(&mut self) -> std::io::Result<()> {
+        self.inner.clear_input()
+    }
+
+    fn clear_output(&mut self) -> std::io::Result<()> {
+        self.inner.clear_output()
+    }
+}
+
+/// Single direction entry of a capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureEntry {
+    /// True for data written to the device, false for data read from it
+    pub written: bool,
+    pub timestamp: std::time::Duration,
+    pub data: Vec<u8>,
+}
+
+/// Reads all entries of a capture file
+pub fn read_capture<R: Read>(mut reader: R) -> std::io::Result<Vec<CaptureEntry>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != CAPTURE_MAGIC {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Invalid capture file magic",
+        ));
+    }
+
+    let mut entries = Vec::new();
+    let mut header = [0; 13];
+
+    loop {
+        match crate::read_chunk(&mut reader, &mut header)? {
+            0 => return Ok(entries),
+            13 => {}
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Truncated capture entry",
+                ));
+            }
+        }
+
+        let written = match header[0] {
+            b'W' => true,
+            b'R' => false,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid capture entry direction",
+                ));
+            }
+        };
+
+        let timestamp = u64::from_be_bytes(header[1..9].try_into().unwrap());
+        let len = u32::from_be_bytes(header[9..13].try_into().unwrap());
+
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+
+        entries.push(CaptureEntry {
+            written,
+            timestamp: std::time::Duration::from_micros(timestamp),
+            data,
+        });
+    }
+}
+
+/// Transport which replays the device side of a capture. Data written by the host is discarded,
+/// reads return the recorded device responses in order and time out once the capture is exhausted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::Everdrive;
+/// use libeverdrive::transport::ReplayTransport;
+/// use std::fs::File;
+///
+/// let capture = File::open("session.edcap").unwrap();
+///
+/// let mut ed = Everdrive::from_transport(ReplayTransport::new(capture).unwrap()).unwrap();
+/// ed.ed_status().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ReplayTransport {
+    reads: std::collections::VecDeque<u8>,
+}
+
+impl ReplayTransport {
+    pub fn new<R: Read>(capture: R) -> std::io::Result<Self> {
+        let reads = read_capture(capture)?
+            .into_iter()
+            .filter(|entry| !entry.written)
+            .flat_map(|entry| entry.data)
+            .collect();
+
+        Ok(Self { reads })
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.reads.is_empty() && !buf.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Capture exhausted",
+            ));
+        }
+
+        self.reads.read(buf)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn set_timeout(&mut self, _timeout: std::time::Duration) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn bytes_to_read(&self) -> std::io::Result<u32> {
+        Ok(self.reads.len() as u32)
+    }
+
+    fn clear_input(&mut self) -> std::io::Result<()> {
+        // Responses are replayed in order, discarding them would desync the replay
+        Ok(())
+    }
+
+    fn clear_output(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
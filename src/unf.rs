@@ -1,9 +1,25 @@
-use crate::Everdrive;
+use crate::crash::{CrashCollector, CrashReport};
+use crate::symbols::SymbolTable;
+use crate::trace::trace_event;
+use crate::{CancellationToken, Everdrive, PacketReader};
 
 const UNF_MAGIC: u32 = 0x444d4140;
 const UNF_FOOTER: u32 = 0x434d5048;
 
+/// Datatype of a UNF packet. Converts from and to the datatype byte of the packet header.
+///
+/// # Examples
+///
+/// ```
+/// use libeverdrive::UnfDataType;
+///
+/// assert_eq!(UnfDataType::from(0x01), UnfDataType::DataTypeText);
+/// assert_eq!(UnfDataType::from(0x42), UnfDataType::DataTypeUnknown(0x42));
+/// assert_eq!(u8::from(UnfDataType::DataTypeUnknown(0x42)), 0x42);
+/// ```
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnfDataType {
     DataTypeText,
     DataTypeBinary,
@@ -11,7 +27,9 @@ pub enum UnfDataType {
     DataTypeScreenshot,
     DataTypeHeartbeat,
     DataTypeRdbPacket,
-    DataTypeUnknown,
+    /// Datatype not known to this library, with the raw datatype byte. Known datatype bytes are
+    /// always converted to their named variant.
+    DataTypeUnknown(u8),
 }
 
 impl From<u8> for UnfDataType {
@@ -23,68 +41,128 @@ impl From<u8> for UnfDataType {
             0x04 => UnfDataType::DataTypeScreenshot,
             0x05 => UnfDataType::DataTypeHeartbeat,
             0x06 => UnfDataType::DataTypeRdbPacket,
-            _ => UnfDataType::DataTypeUnknown,
+            byte => UnfDataType::DataTypeUnknown(byte),
         }
     }
 }
 
-impl Into<u8> for UnfDataType {
-    fn into(self) -> u8 {
-        match self {
+impl From<UnfDataType> for u8 {
+    fn from(datatype: UnfDataType) -> Self {
+        match datatype {
             UnfDataType::DataTypeText => 0x01,
             UnfDataType::DataTypeBinary => 0x02,
             UnfDataType::DataTypeHeader => 0x03,
             UnfDataType::DataTypeScreenshot => 0x04,
             UnfDataType::DataTypeHeartbeat => 0x05,
             UnfDataType::DataTypeRdbPacket => 0x06,
-            UnfDataType::DataTypeUnknown => 0xFF,
+            UnfDataType::DataTypeUnknown(byte) => byte,
         }
     }
 }
 
-struct PacketReader<'a> {
-    buf: &'a [u8],
-    offset: usize,
-}
+impl PacketReader<'_> {
+    /// Reads a complete UNF packet. `aligned` selects whether odd sized packets carry an
+    /// alignment byte, which the console sends since protocol version 2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::{PacketReader, UnfDataType};
+    ///
+    /// let capture = [
+    ///     b'D', b'M', b'A', b'@', 0x01, 0x00, 0x00, 0x02, b'h', b'i', b'C', b'M', b'P', b'H',
+    /// ];
+    /// let mut reader = PacketReader::new(&capture);
+    ///
+    /// let packet = reader.consume_unf_packet(true).unwrap();
+    ///
+    /// assert_eq!(packet.get_datatype(), UnfDataType::DataTypeText);
+    /// assert_eq!(packet.as_text().unwrap(), "hi");
+    /// assert_eq!(reader.remaining(), 0);
+    /// ```
+    pub fn consume_unf_packet(&mut self, aligned: bool) -> std::io::Result<UnfRecvPacket> {
+        let magic = self.consume_word()?;
 
-impl<'a> PacketReader<'a> {
-    fn new(buf: &'a [u8]) -> Self {
-        Self { buf, offset: 0 }
-    }
+        if magic != /* "DMA@" */ UNF_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid UNF packet magic {}, expected {}", magic, UNF_MAGIC),
+            ));
+        }
 
-    fn consume_byte(&mut self) -> u8 {
-        let byte = self.buf[self.offset];
-        self.offset += 1;
-        byte
-    }
+        let header = self.consume_word()?;
+        let dsize = (header & 0x00FFFFFF) as usize;
+        let datatype = UnfDataType::from((header >> 24) as u8);
 
-    fn consume_word(&mut self) -> u32 {
-        let word = u32::from_be_bytes([
-            self.buf[self.offset],
-            self.buf[self.offset + 1],
-            self.buf[self.offset + 2],
-            self.buf[self.offset + 3],
-        ]);
-        self.offset += 4;
-        word
-    }
+        let data = self.consume_bytes(dsize)?.to_vec();
+
+        if dsize & 1 != 0 && aligned {
+            self.skip(1)?;
+        }
+
+        let cmp = self.consume_word()?;
+
+        if cmp != /* "CMPH" */ UNF_FOOTER {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid UNF packet footer {}, expected {}", cmp, UNF_FOOTER),
+            ));
+        }
 
-    fn get_offset(&self) -> usize {
-        self.offset
+        Ok(UnfRecvPacket { datatype, data })
     }
+}
+
+/// Contents of a `DataTypeHeartbeat` packet sent by the console
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UnfHeartbeat {
+    /// USB protocol version used by the console
+    pub protocol_version: u16,
+    /// Version of the heartbeat packet itself
+    pub heartbeat_version: u16,
+}
+
+impl UnfHeartbeat {
+    /// Parses the payload of a heartbeat packet
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libeverdrive::UnfHeartbeat;
+    ///
+    /// let heartbeat = UnfHeartbeat::parse(&[0x00, 0x02, 0x00, 0x01]).unwrap();
+    ///
+    /// assert_eq!(heartbeat.protocol_version, 2);
+    /// assert_eq!(heartbeat.heartbeat_version, 1);
+    /// ```
+    pub fn parse(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < 4 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Heartbeat packet is too small",
+            ));
+        }
 
-    fn skip(&mut self, n: usize) {
-        self.offset += n;
+        let mut reader = PacketReader::new(data);
+
+        Ok(Self {
+            protocol_version: reader.consume_half()?,
+            heartbeat_version: reader.consume_half()?,
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnfRecvPacket {
     datatype: UnfDataType,
     data: Vec<u8>,
 }
 
 impl UnfRecvPacket {
+    pub(crate) fn new(datatype: UnfDataType, data: Vec<u8>) -> Self {
+        Self { datatype, data }
+    }
+
     pub fn get_data(&self) -> &[u8] {
         &self.data
     }
@@ -92,6 +170,48 @@ impl UnfRecvPacket {
     pub fn get_datatype(&self) -> UnfDataType {
         self.datatype
     }
+
+    /// Consumes the packet and returns its payload without copying it.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the payload as a string slice, or an error if it is not valid UTF-8.
+    ///
+    /// Usually used with [`UnfDataType::DataTypeText`] packets.
+    pub fn as_text(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.data)
+    }
+}
+
+impl From<UnfRecvPacket> for Vec<u8> {
+    fn from(packet: UnfRecvPacket) -> Self {
+        packet.into_data()
+    }
+}
+
+/// First packet received after a program was started, with the bytes which preceded it.
+/// Returned by `unf_rx_startup`.
+#[derive(Debug, Clone)]
+pub struct UnfStartup {
+    /// Bytes received before the first packet, such as boot text
+    pub junk: Vec<u8>,
+    pub packet: UnfRecvPacket,
+}
+
+impl UnfStartup {
+    /// Returns true if the leading bytes look like text: printable ASCII, whitespace or valid UTF-8
+    pub fn is_text(&self) -> bool {
+        std::str::from_utf8(&self.junk).is_ok_and(|text| {
+            text.chars()
+                .all(|c| !c.is_control() || c.is_ascii_whitespace())
+        })
+    }
+
+    /// Returns the leading bytes as text, replacing invalid UTF-8
+    pub fn junk_text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.junk)
+    }
 }
 
 #[derive(Debug)]
@@ -100,38 +220,44 @@ pub struct UnfSendPacket {
     backing: Vec<u8>,
 }
 
-impl UnfSendPacket {
-    pub fn new(data_type: UnfDataType, data_size: usize) -> std::io::Result<Self> {
-        if data_size > 0x00FFFFFF {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Data size must be less than 0x00FFFFFF",
-            ));
-        }
+/// Builds the packet header for `data_size` bytes of `data_type`
+fn unf_header(data_type: UnfDataType, data_size: usize) -> std::io::Result<[u8; 8]> {
+    if data_size > 0x00FFFFFF {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Data size must be less than 0x00FFFFFF",
+        ));
+    }
 
-        let align_bytes = data_size & 1;
+    Ok([
+        (UNF_MAGIC >> 24) as u8,
+        (UNF_MAGIC >> 16) as u8,
+        (UNF_MAGIC >> 8) as u8,
+        UNF_MAGIC as u8,
+        data_type.into(),
+        (data_size >> 16) as u8,
+        (data_size >> 8) as u8,
+        data_size as u8,
+    ])
+}
 
-        let mut data = vec![0; data_size + 12 + align_bytes];
+/// Builds the alignment byte (if any) and footer following `data_size` bytes of data
+fn unf_trailer(data_size: usize) -> ([u8; 5], usize) {
+    let align_bytes = data_size & 1;
+    let mut trailer = [0xFF; 5];
+    trailer[align_bytes..align_bytes + 4].copy_from_slice(&UNF_FOOTER.to_be_bytes());
+    (trailer, align_bytes + 4)
+}
 
-        data[0..8].copy_from_slice(&[
-            (UNF_MAGIC >> 24) as u8,
-            (UNF_MAGIC >> 16) as u8,
-            (UNF_MAGIC >> 8) as u8,
-            UNF_MAGIC as u8,
-            data_type.into(),
-            (data_size >> 16) as u8,
-            (data_size >> 8) as u8,
-            data_size as u8,
-        ]);
+impl UnfSendPacket {
+    pub fn new(data_type: UnfDataType, data_size: usize) -> std::io::Result<Self> {
+        let header = unf_header(data_type, data_size)?;
+        let (trailer, trailer_len) = unf_trailer(data_size);
 
-        data[data_size + 8..data_size + 8 + align_bytes].fill(0xFF);
+        let mut data = vec![0; data_size + 8 + trailer_len];
 
-        data[data_size + 8 + align_bytes..].copy_from_slice(&[
-            (UNF_FOOTER >> 24) as u8,
-            (UNF_FOOTER >> 16) as u8,
-            (UNF_FOOTER >> 8) as u8,
-            UNF_FOOTER as u8,
-        ]);
+        data[0..8].copy_from_slice(&header);
+        data[data_size + 8..].copy_from_slice(&trailer[..trailer_len]);
 
         Ok(Self {
             backing: data,
@@ -139,59 +265,534 @@ impl UnfSendPacket {
         })
     }
 
+    /// Builds a packet of `data_type` carrying a copy of `data`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, UnfDataType, UnfSendPacket};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let packet = UnfSendPacket::from_bytes(UnfDataType::DataTypeBinary, &[1, 2, 3]).unwrap();
+    /// ed.unf_tx(&packet).unwrap();
+    /// ```
+    pub fn from_bytes(data_type: UnfDataType, data: &[u8]) -> std::io::Result<Self> {
+        let mut packet = Self::new(data_type, data.len())?;
+        packet.get_data().copy_from_slice(data);
+        Ok(packet)
+    }
+
+    /// Builds a text packet carrying `text`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(text: &str) -> std::io::Result<Self> {
+        Self::from_bytes(UnfDataType::DataTypeText, text.as_bytes())
+    }
+
     pub fn get_data(&mut self) -> &mut [u8] {
         &mut self.backing[8..8 + self.data_size as usize]
     }
+
+    /// Returns the complete packet as sent over the wire
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.backing
+    }
+}
+
+type TextHandler<'a> = Box<dyn FnMut(&str) + 'a>;
+type DataHandler<'a> = Box<dyn FnMut(&[u8]) + 'a>;
+type HeartbeatHandler<'a> = Box<dyn FnMut(UnfHeartbeat) + 'a>;
+type CrashHandler<'a> = Box<dyn FnMut(CrashReport) + 'a>;
+type PacketHandler<'a> = Box<dyn FnMut(&UnfRecvPacket) + 'a>;
+
+/// Per-datatype packet handlers for `unf_listen`. Packets without a matching
+/// handler are passed to the `on_packet` handler, or dropped if it is not set.
+#[derive(Default)]
+pub struct UnfHandlers<'a> {
+    text: Option<TextHandler<'a>>,
+    binary: Option<DataHandler<'a>>,
+    screenshot: Option<DataHandler<'a>>,
+    heartbeat: Option<HeartbeatHandler<'a>>,
+    crash: Option<CrashHandler<'a>>,
+    crash_collector: CrashCollector,
+    packet: Option<PacketHandler<'a>>,
+    symbols: Option<&'a SymbolTable>,
+}
+
+impl<'a> UnfHandlers<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the handler for `DataTypeText` packets. Invalid UTF-8 is replaced.
+    pub fn on_text(mut self, handler: impl FnMut(&str) + 'a) -> Self {
+        self.text = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for `DataTypeBinary` packets
+    pub fn on_binary(mut self, handler: impl FnMut(&[u8]) + 'a) -> Self {
+        self.binary = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for `DataTypeScreenshot` packets
+    pub fn on_screenshot(mut self, handler: impl FnMut(&[u8]) + 'a) -> Self {
+        self.screenshot = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for `DataTypeHeartbeat` packets. Not called when heartbeats are consumed.
+    pub fn on_heartbeat(mut self, handler: impl FnMut(UnfHeartbeat) + 'a) -> Self {
+        self.heartbeat = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for the exception dumps libdragon prints when a program crashes. The dump is
+    /// parsed out of `DataTypeText` packets, which are still passed to the `on_text` handler. See
+    /// the `crash` module for the format.
+    pub fn on_crash(mut self, handler: impl FnMut(CrashReport) + 'a) -> Self {
+        self.crash = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for packets which have no datatype specific handler
+    pub fn on_packet(mut self, handler: impl FnMut(&UnfRecvPacket) + 'a) -> Self {
+        self.packet = Some(Box::new(handler));
+        self
+    }
+
+    /// Annotates addresses in `DataTypeText` packets with the symbols they resolve to before they are
+    /// passed to the `on_text` handler, see `SymbolTable::annotate`
+    pub fn with_symbols(mut self, symbols: &'a SymbolTable) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub(crate) fn dispatch(&mut self, packet: &UnfRecvPacket) {
+        let handled = match packet.datatype {
+            UnfDataType::DataTypeText => {
+                let text = String::from_utf8_lossy(&packet.data);
+
+                if let Some(h) = self.crash.as_mut() {
+                    for report in self.crash_collector.push(&text) {
+                        h(report);
+                    }
+                }
+
+                self.text.as_mut().map(|h| match self.symbols {
+                    Some(symbols) => h(&symbols.annotate(&text)),
+                    None => h(&text),
+                })
+            }
+            UnfDataType::DataTypeBinary => self.binary.as_mut().map(|h| h(&packet.data)),
+            UnfDataType::DataTypeScreenshot => self.screenshot.as_mut().map(|h| h(&packet.data)),
+            UnfDataType::DataTypeHeartbeat => match UnfHeartbeat::parse(&packet.data) {
+                Ok(heartbeat) => self.heartbeat.as_mut().map(|h| h(heartbeat)),
+                Err(_) => None,
+            },
+            _ => None,
+        };
+
+        if handled.is_none()
+            && let Some(h) = self.packet.as_mut()
+        {
+            h(packet);
+        }
+    }
+}
+
+/// Wraps an error from reading part of a packet after its first byte. A timeout is reported as
+/// `InvalidData`, since the bytes read so far are lost and the stream is out of sync.
+fn unf_read_error(err: std::io::Error, part: &str) -> std::io::Error {
+    let kind = match err.kind() {
+        std::io::ErrorKind::TimedOut => std::io::ErrorKind::InvalidData,
+        kind => kind,
+    };
+
+    std::io::Error::new(kind, format!("Failed to read UNF packet {} {}", part, err))
 }
 
 impl Everdrive {
+    /// Runs a blocking receive loop, dispatching incoming packets to `handlers` until `stop` is cancelled
+    /// or receiving fails. Serial read timeouts while waiting for a packet are not treated as errors,
+    /// a timeout in the middle of a packet fails with `InvalidData`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{CancellationToken, Everdrive, UnfHandlers};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// let stop = CancellationToken::new();
+    /// let handler_stop = stop.clone();
+    ///
+    /// let handlers = UnfHandlers::new()
+    ///     .on_text(|text| print!("{}", text))
+    ///     .on_binary(|data| {
+    ///         println!("Received {} bytes, stopping", data.len());
+    ///         handler_stop.cancel();
+    ///     });
+    ///
+    /// ed.unf_listen(handlers, &stop).unwrap();
+    /// ```
+    pub fn unf_listen(
+        &mut self,
+        mut handlers: UnfHandlers,
+        stop: &CancellationToken,
+    ) -> std::io::Result<()> {
+        while !stop.is_cancelled() {
+            match self.unf_rx() {
+                Ok(packet) => handlers.dispatch(&packet),
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn unf_tx(&mut self, packet: &UnfSendPacket) -> std::io::Result<()> {
+        self.session_check_unf()?;
+
+        trace_event!(debug, size = packet.data_size, "UNF packet sent");
+
         self.write_all(&packet.backing)
     }
 
+    /// Sends `data` as a packet of `data_type` without building the full frame in memory.
+    ///
+    /// The header, payload and footer are written separately, so large payloads are not copied.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, UnfDataType};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let data = vec![0; 0x100000];
+    ///
+    /// ed.unf_tx_slice(UnfDataType::DataTypeBinary, &data).unwrap();
+    /// ```
+    pub fn unf_tx_slice(&mut self, data_type: UnfDataType, data: &[u8]) -> std::io::Result<()> {
+        self.session_check_unf()?;
+
+        let header = unf_header(data_type, data.len())?;
+        let (trailer, trailer_len) = unf_trailer(data.len());
+
+        trace_event!(debug, size = data.len(), "UNF packet sent");
+
+        self.write_all(&header)?;
+        self.write_all(data)?;
+        self.write_all(&trailer[..trailer_len])
+    }
+
+    /// Sends `size` bytes read from `reader` as a packet of `data_type`, streaming the payload
+    /// through a small buffer instead of loading it into memory.
+    ///
+    /// Returns an `UnexpectedEof` error if `reader` ends before `size` bytes were read. The
+    /// console is left with a partial packet in that case and the session should be reset.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, UnfDataType};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    /// let file = std::fs::File::open("level.bin").unwrap();
+    /// let size = file.metadata().unwrap().len() as usize;
+    ///
+    /// ed.unf_tx_reader(UnfDataType::DataTypeBinary, file, size).unwrap();
+    /// ```
+    pub fn unf_tx_reader(
+        &mut self,
+        data_type: UnfDataType,
+        mut reader: impl std::io::Read,
+        size: usize,
+    ) -> std::io::Result<()> {
+        self.session_check_unf()?;
+
+        let header = unf_header(data_type, size)?;
+        let (trailer, trailer_len) = unf_trailer(size);
+
+        trace_event!(debug, size, "UNF packet sent");
+
+        self.write_all(&header)?;
+
+        let mut buf = [0; 0x8000];
+        let mut remaining = size;
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+            reader.read_exact(&mut buf[..len])?;
+            self.write_all(&buf[..len])?;
+            remaining -= len;
+        }
+
+        self.write_all(&trailer[..trailer_len])
+    }
+
+    /// Enables or disables transparent heartbeat handling. When enabled, `unf_rx` consumes
+    /// `DataTypeHeartbeat` packets instead of returning them. The protocol version from
+    /// heartbeats is always recorded and available through `unf_heartbeat`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.unf_set_consume_heartbeats(true);
+    ///
+    /// loop {
+    ///     let packet = ed.unf_rx().unwrap();
+    ///     println!("{:?} (protocol {:?})", packet, ed.unf_heartbeat());
+    /// }
+    /// ```
+    pub fn unf_set_consume_heartbeats(&mut self, consume: bool) {
+        self.unf_consume_heartbeats = consume;
+    }
+
+    /// Sets how many bytes `unf_rx` may discard while scanning for the start of the next packet
+    /// when the stream is out of sync, for example after a dropped byte. Defaults to 0, which
+    /// returns an error on the first invalid packet magic.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.unf_set_resync_limit(0x10000);
+    /// ```
+    pub fn unf_set_resync_limit(&mut self, limit: usize) {
+        self.unf_resync_limit = limit;
+    }
+
+    /// Returns the last heartbeat received from the console, if any
+    pub fn unf_heartbeat(&self) -> Option<UnfHeartbeat> {
+        self.unf_heartbeat
+    }
+
+    /// Receives the next packet. Returns a `TimedOut` error if no packet starts within the serial
+    /// timeout, a timeout once the packet has started fails with `InvalidData`.
     pub fn unf_rx(&mut self) -> std::io::Result<UnfRecvPacket> {
-        let magic = self.read_word_be().map_err(|e| {
+        loop {
+            let packet = self.unf_rx_packet()?;
+
+            if self.unf_handle_heartbeat(&packet) {
+                continue;
+            }
+
+            return Ok(packet);
+        }
+    }
+
+    /// Receives a packet if one is pending, without blocking while the port is idle.
+    ///
+    /// Returns `Ok(None)` until at least a full packet header has been buffered by the serial port.
+    /// Once the header is available the rest of the packet is read like in `unf_rx`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// loop {
+    ///     match ed.unf_try_rx().unwrap() {
+    ///         Some(packet) => println!("{:?}", packet),
+    ///         None => std::thread::sleep(std::time::Duration::from_millis(10)),
+    ///     }
+    /// }
+    /// ```
+    pub fn unf_try_rx(&mut self) -> std::io::Result<Option<UnfRecvPacket>> {
+        const UNF_HEADER_SIZE: u32 = 8;
+
+        while self.bytes_to_read()? >= UNF_HEADER_SIZE {
+            let packet = self.unf_rx_packet()?;
+
+            if self.unf_handle_heartbeat(&packet) {
+                continue;
+            }
+
+            return Ok(Some(packet));
+        }
+
+        Ok(None)
+    }
+
+    /// Receives the first packet after a program has been started, collecting the bytes which arrive
+    /// before it instead of failing on an invalid packet magic. Programs often print boot text to the
+    /// port before their UNF code runs.
+    ///
+    /// Returns an `InvalidData` error if more than `limit` bytes arrive before the first packet.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::Everdrive;
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.ed_load_rom(std::fs::read("your_rom.z64").unwrap(), None, None, None).unwrap();
+    /// ed.ed_app_start(None).unwrap();
+    ///
+    /// let startup = ed.unf_rx_startup(0x10000).unwrap();
+    ///
+    /// if startup.is_text() {
+    ///     print!("{}", startup.junk_text());
+    /// }
+    /// println!("{:?}", startup.packet);
+    /// ```
+    pub fn unf_rx_startup(&mut self, limit: usize) -> std::io::Result<UnfStartup> {
+        self.session_check_unf()?;
+
+        let mut junk = Vec::new();
+
+        loop {
+            let mut buf = [0; 4];
+            self.read_exact_polling(&mut buf)?;
+            let mut magic = u32::from_be_bytes(buf);
+
+            while magic != UNF_MAGIC {
+                if junk.len() >= limit {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("No UNF packet within the first {} bytes", limit),
+                    ));
+                }
+
+                let mut byte = [0; 1];
+                self.read_exact_polling(&mut byte)?;
+
+                junk.push((magic >> 24) as u8);
+                magic = (magic << 8) | byte[0] as u32;
+            }
+
+            let packet = self.unf_rx_packet_body()?;
+
+            if self.unf_handle_heartbeat(&packet) {
+                continue;
+            }
+
+            if !junk.is_empty() {
+                trace_event!(
+                    debug,
+                    len = junk.len(),
+                    "bytes received before the first UNF packet"
+                );
+            }
+
+            return Ok(UnfStartup { junk, packet });
+        }
+    }
+
+    /// Records the protocol version from heartbeat packets and returns true if the packet should be consumed.
+    /// Malformed heartbeats are returned to the caller, the packet was read completely so the stream is still in sync.
+    fn unf_handle_heartbeat(&mut self, packet: &UnfRecvPacket) -> bool {
+        if packet.datatype != UnfDataType::DataTypeHeartbeat {
+            return false;
+        }
+
+        match UnfHeartbeat::parse(&packet.data) {
+            Ok(heartbeat) => self.unf_heartbeat = Some(heartbeat),
+            Err(_err) => {
+                trace_event!(warn, error = %_err, "malformed UNF heartbeat");
+                return false;
+            }
+        }
+
+        if let Some(tracker) = &self.heartbeat_tracker {
+            tracker.beat();
+        }
+
+        self.unf_consume_heartbeats
+    }
+
+    fn unf_rx_packet(&mut self) -> std::io::Result<UnfRecvPacket> {
+        self.session_check_unf()?;
+
+        // Only a timeout before the first byte means the port is idle, later timeouts leave the
+        // stream in the middle of a packet
+        let mut buf = [0; 4];
+        self.read_exact_polling(&mut buf[..1]).map_err(|e| {
             std::io::Error::new(e.kind(), format!("Failed to read UNF packet magic {}", e))
         })?;
+        self.read_exact(&mut buf[1..])
+            .map_err(|e| unf_read_error(e, "magic"))?;
+        let mut magic = u32::from_be_bytes(buf);
 
-        if magic != /* "DMA@" */ UNF_MAGIC {
+        // Slide over the stream one byte at a time looking for the next packet
+        let mut discarded = 0;
+
+        while magic != /* "DMA@" */ UNF_MAGIC && discarded < self.unf_resync_limit {
+            let mut byte = [0; 1];
+            self.read_exact_polling(&mut byte).map_err(|e| {
+                std::io::Error::new(e.kind(), format!("Failed to read UNF packet magic {}", e))
+            })?;
+
+            magic = (magic << 8) | byte[0] as u32;
+            discarded += 1;
+        }
+
+        if discarded > 0 {
+            trace_event!(warn, discarded, "UNF stream resynchronized");
+        }
+
+        if magic != UNF_MAGIC {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("Invalid UNF packet magic {}, expected {}", magic, UNF_MAGIC),
             ));
         }
 
-        let header = self.read_word_be().map_err(|e| {
-            std::io::Error::new(e.kind(), format!("Failed to read UNF packet header {}", e))
-        })?;
+        self.unf_rx_packet_body()
+    }
+
+    /// Reads the rest of a packet after its magic
+    fn unf_rx_packet_body(&mut self) -> std::io::Result<UnfRecvPacket> {
+        let header = self
+            .read_word_be()
+            .map_err(|e| unf_read_error(e, "header"))?;
 
         let dsize = header & 0x00FFFFFF;
         let dtype = (header >> 24) as u8;
 
-        let datatype = dtype.try_into().map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Invalid packet UnfDataType {}", dtype),
-            )
-        })?;
+        let datatype = UnfDataType::from(dtype);
 
         let mut data = vec![0; dsize as usize];
 
-        self.read_exact(&mut data).map_err(|e| {
-            std::io::Error::new(e.kind(), format!("Failed to read UNF packet data {}", e))
-        })?;
+        self.read_exact(&mut data)
+            .map_err(|e| unf_read_error(e, "data"))?;
 
-        let cmp = self.read_word_be().map_err(|e| {
-            std::io::Error::new(e.kind(), format!("Failed to read UNF packet footer {}", e))
-        })?;
+        // Since protocol version 2 the console pads packet data to 2 byte alignment
+        let protocol_version = self.unf_heartbeat.map(|hb| hb.protocol_version);
+
+        if dsize & 1 != 0 && protocol_version.is_some_and(|version| version >= 2) {
+            self.read_byte()
+                .map_err(|e| unf_read_error(e, "alignment"))?;
+        }
+
+        let cmp = self
+            .read_word_be()
+            .map_err(|e| unf_read_error(e, "footer"))?;
 
         if cmp != /* "CMPH" */ UNF_FOOTER {
-            return Err(std::io::Error::new(
+            return Err(self.emit_error(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("Invalid UNF packet footer {}, expected {}", cmp, UNF_FOOTER),
-            ));
+            )));
         }
 
-        Ok(UnfRecvPacket { datatype, data })
+        trace_event!(debug, ?datatype, size = dsize, "UNF packet received");
+
+        let packet = UnfRecvPacket { datatype, data };
+        self.emit_event(|events| events.on_packet_received(&packet));
+
+        Ok(packet)
     }
 }
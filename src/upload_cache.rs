@@ -0,0 +1,248 @@
+use crate::Everdrive;
+
+/// Size of the blocks compared between uploads
+const UPLOAD_BLOCK_SIZE: usize = 0x10000;
+
+/// Cache file magic, followed by the entries
+const UPLOAD_CACHE_MAGIC: &[u8; 8] = b"EDUPC001";
+
+/// 64-bit FNV-1a, stable across builds so cache files stay valid
+fn block_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Block hashes of the rom last uploaded to a device
+#[derive(Debug, Clone, PartialEq)]
+struct UploadEntry {
+    addr: u32,
+    hashes: Vec<u64>,
+}
+
+/// Remembers the rom last uploaded to each device, so the next upload only sends the blocks which
+/// changed. Iterative builds usually change a small part of the rom, which makes uploads of large
+/// roms much faster.
+///
+/// Devices are identified by a caller chosen id, such as the USB serial number from
+/// `find_usb_devices_matching`. The cache can't tell if the cartridge lost its rom, for example
+/// after a power cycle, so `invalidate` has to be called in that case. Rom writes outside of cached
+/// uploads invalidate the device automatically.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libeverdrive::{Everdrive, UploadCache};
+///
+/// let mut ed = Everdrive::new("COM3").unwrap();
+///
+/// ed.set_upload_cache(Some(UploadCache::open("upload.cache", "ED64-A1B2C3").unwrap()));
+///
+/// // Only the blocks which changed since the last build are sent
+/// ed.ed_load_rom(std::fs::read("build/game.z64").unwrap(), None, None, None).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct UploadCache {
+    path: Option<std::path::PathBuf>,
+    device: String,
+    entries: std::collections::HashMap<String, UploadEntry>,
+}
+
+impl UploadCache {
+    /// Creates an in-memory cache for `device`
+    pub fn new(device: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            device: device.into(),
+            entries: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Opens the cache file at `path` for `device`. A missing file starts an empty cache. The file
+    /// is rewritten after every upload and can be shared by several devices.
+    pub fn open(
+        path: impl Into<std::path::PathBuf>,
+        device: impl Into<String>,
+    ) -> std::io::Result<Self> {
+        let path = path.into();
+
+        let entries = match std::fs::read(&path) {
+            Ok(data) => Self::parse(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                std::collections::HashMap::new()
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            device: device.into(),
+            entries,
+        })
+    }
+
+    fn parse(data: &[u8]) -> std::io::Result<std::collections::HashMap<String, UploadEntry>> {
+        let invalid =
+            || std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid upload cache file");
+
+        let mut reader = crate::PacketReader::new(data);
+
+        if reader.consume_bytes(UPLOAD_CACHE_MAGIC.len())? != UPLOAD_CACHE_MAGIC {
+            return Err(invalid());
+        }
+
+        let mut entries = std::collections::HashMap::new();
+
+        while reader.remaining() > 0 {
+            let device = reader.consume_str()?.to_string();
+            let addr = reader.consume_word()?;
+            let count = reader.consume_word()? as usize;
+
+            let hashes = (0..count)
+                .map(
+                    |_| Ok(((reader.consume_word()? as u64) << 32) | reader.consume_word()? as u64),
+                )
+                .collect::<std::io::Result<Vec<u64>>>()?;
+
+            entries.insert(device, UploadEntry { addr, hashes });
+        }
+
+        Ok(entries)
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut data = UPLOAD_CACHE_MAGIC.to_vec();
+
+        for (device, entry) in &self.entries {
+            let device = &device.as_bytes()[..device.len().min(u16::MAX as usize)];
+            data.extend_from_slice(&(device.len() as u16).to_be_bytes());
+            data.extend_from_slice(device);
+            data.extend_from_slice(&entry.addr.to_be_bytes());
+            data.extend_from_slice(&(entry.hashes.len() as u32).to_be_bytes());
+
+            for hash in &entry.hashes {
+                data.extend_from_slice(&hash.to_be_bytes());
+            }
+        }
+
+        std::fs::write(path, data)
+    }
+
+    /// Forgets the rom uploaded to the device, so the next upload is sent in full
+    pub fn invalidate(&mut self) -> std::io::Result<()> {
+        if self.entries.remove(&self.device).is_some() {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Everdrive {
+    /// Sets the cache used for differential rom uploads, or disables them with `None`.
+    ///
+    /// While a cache is set, `ed_load_rom` and the other rom loading functions only send the blocks
+    /// of the rom which changed since the previous upload to the device. See [`UploadCache`].
+    pub fn set_upload_cache(&mut self, cache: Option<UploadCache>) {
+        self.upload_cache = cache;
+    }
+
+    /// Returns the cache used for differential rom uploads, if any
+    pub fn upload_cache(&mut self) -> Option<&mut UploadCache> {
+        self.upload_cache.as_mut()
+    }
+
+    /// Invalidates the upload cache after a rom write which bypassed it
+    pub(crate) fn upload_cache_invalidate(&mut self) -> std::io::Result<()> {
+        match &mut self.upload_cache {
+            Some(cache) => cache.invalidate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes `data` to `addr`, skipping the blocks whose hashes match the previous upload to the
+    /// device. Returns the number of bytes sent, or `None` without writing anything if no upload
+    /// cache is set.
+    pub(crate) fn ed_rom_write_cached(
+        &mut self,
+        addr: u32,
+        data: &[u8],
+    ) -> std::io::Result<Option<usize>> {
+        let Some(mut cache) = self.upload_cache.take() else {
+            return Ok(None);
+        };
+
+        let result = self.ed_rom_write_diff(addr, data, &cache);
+
+        let sent = match result {
+            Ok((hashes, sent)) => {
+                let device = cache.device.clone();
+                cache.entries.insert(device, UploadEntry { addr, hashes });
+                Ok(sent)
+            }
+            // The cartridge contents are unknown after a failed upload
+            Err(err) => {
+                cache.entries.remove(&cache.device);
+                Err(err)
+            }
+        };
+
+        let saved = cache.save();
+        self.upload_cache = Some(cache);
+
+        let sent = sent?;
+        saved?;
+
+        // Unchanged blocks are not sent, the rom counts as loaded even if nothing was written
+        self.set_session_state(crate::SessionState::RomLoaded);
+
+        crate::trace::trace_event!(debug, sent, size = data.len(), "differential rom upload");
+
+        Ok(Some(sent))
+    }
+
+    fn ed_rom_write_diff(
+        &mut self,
+        addr: u32,
+        data: &[u8],
+        cache: &UploadCache,
+    ) -> std::io::Result<(Vec<u64>, usize)> {
+        let hashes: Vec<u64> = data.chunks(UPLOAD_BLOCK_SIZE).map(block_hash).collect();
+
+        let previous = cache
+            .entries
+            .get(&cache.device)
+            .filter(|entry| entry.addr == addr)
+            .map(|entry| entry.hashes.as_slice())
+            .unwrap_or_default();
+
+        let mut sent = 0;
+        let mut index = 0;
+
+        while index < hashes.len() {
+            if previous.get(index) == Some(&hashes[index]) {
+                index += 1;
+                continue;
+            }
+
+            // Send runs of changed blocks with a single write
+            let run_start = index;
+
+            while index < hashes.len() && previous.get(index) != Some(&hashes[index]) {
+                index += 1;
+            }
+
+            let start = run_start * UPLOAD_BLOCK_SIZE;
+            let end = (index * UPLOAD_BLOCK_SIZE).min(data.len());
+
+            self.rom_write_blocks(addr + start as u32, &data[start..end])?;
+            sent += end - start;
+        }
+
+        Ok((hashes, sent))
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Everdrive, EverdriveError};
+
+/// USB vendor and product id of the FTDI FT245R bridge of the Everdrive 64
+pub(crate) const FT245R_VID_PID: (u16, u16) = (0x0403, 0x6001);
+
+/// Rule for recognizing Everdrive USB serial ports
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UsbDeviceRule {
+    /// Matches the USB vendor and product id of the serial bridge
+    VidPid(u16, u16),
+    /// Matches ports whose USB product string contains the given text, ignoring case
+    Product(String),
+}
+
+impl UsbDeviceRule {
+    /// Rules used by `find_usb_devices`: the FTDI FT245R bridge of the Everdrive 64 and any
+    /// bridge reporting an Everdrive product string
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            UsbDeviceRule::VidPid(FT245R_VID_PID.0, FT245R_VID_PID.1),
+            UsbDeviceRule::Product("EverDrive".to_string()),
+        ]
+    }
+
+    fn matches(&self, info: &serialport::UsbPortInfo) -> bool {
+        match self {
+            UsbDeviceRule::VidPid(vid, pid) => info.vid == *vid && info.pid == *pid,
+            UsbDeviceRule::Product(product) => info
+                .product
+                .as_ref()
+                .is_some_and(|name| name.to_lowercase().contains(&product.to_lowercase())),
+        }
+    }
+}
+
+/// USB serial port matched by a `UsbDeviceRule`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbDevice {
+    pub port_name: String,
+    /// First rule which matched the port
+    pub rule: UsbDeviceRule,
+    /// USB serial number of the bridge, if it reports one
+    pub serial_number: Option<String>,
+}
+
+impl Everdrive {
+    /// Finds USB serial ports matching any of `rules`, such as carts using an FT2232H or CDC-ACM bridge.
+    /// Each port is returned once, with the first rule it matched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, UsbDeviceRule};
+    ///
+    /// let mut rules = UsbDeviceRule::defaults();
+    /// rules.push(UsbDeviceRule::VidPid(0x0403, 0x6010));
+    ///
+    /// for device in Everdrive::find_usb_devices_matching(&rules).unwrap() {
+    ///     println!("{} matched {:?}", device.port_name, device.rule);
+    /// }
+    /// ```
+    pub fn find_usb_devices_matching(
+        rules: &[UsbDeviceRule],
+    ) -> Result<Vec<UsbDevice>, EverdriveError> {
+        let ports = serialport::available_ports().map_err(EverdriveError::Enumeration)?;
+
+        let devices = ports.into_iter().filter_map(|p| match &p.port_type {
+            serialport::SerialPortType::UsbPort(info) => rules
+                .iter()
+                .find(|rule| rule.matches(info))
+                .map(|rule| UsbDevice {
+                    port_name: p.port_name.clone(),
+                    rule: rule.clone(),
+                    serial_number: info.serial_number.clone(),
+                }),
+            _ => None,
+        });
+
+        Ok(devices.collect())
+    }
+}
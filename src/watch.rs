@@ -0,0 +1,173 @@
+use crate::Everdrive;
+use crate::clock::Stopwatch;
+use crate::run::RunOptions;
+use crate::trace::trace_event;
+use crate::unf::{UnfDataType, UnfRecvPacket};
+use notify::Watcher;
+
+/// Time to wait for further file changes before reloading, since builds
+/// usually write the rom in several steps
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// Interval for polling the UNF channel while waiting for file changes
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Bytes a started program may print before its first UNF packet
+const WATCH_STARTUP_LIMIT: usize = 0x10000;
+
+/// Resolves the links of the parent directory of `path`, keeping the file name, which may not exist
+fn canonical_in_dir(path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => Ok(dir.canonicalize()?.join(name)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Path does not name a file",
+        )),
+    }
+}
+
+/// Receive errors which only lose the current packet, the session continues with the next one
+fn is_skippable(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::InvalidData
+            | std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::TimedOut
+    )
+}
+
+impl Everdrive {
+    /// Loads and starts the rom at `path`, then reloads and restarts it every time the file changes.
+    ///
+    /// Packets received from the console are dispatched to `options.listen` handlers for the whole
+    /// session, across reloads. Runs until `options.stop` is cancelled. `on_reload` is called after
+    /// every reload with the result of loading the rom, a failed reload does not end the session.
+    /// Errors of the file watcher are passed to `on_reload` as well.
+    ///
+    /// The running program can't be replaced from the Everdrive OS, so every reload resets the
+    /// session first. Reset the console to the menu before the file changes, otherwise the reload
+    /// fails with a `SessionError`.
+    ///
+    /// After every start the output preceding the program's first packet is collected with
+    /// `unf_rx_startup` and passed to the text handler. Invalid packets are logged and skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, RunOptions, UnfHandlers};
+    ///
+    /// let mut ed = Everdrive::new("COM3").unwrap();
+    ///
+    /// ed.watch_rom(
+    ///     "build/your_rom.z64",
+    ///     RunOptions {
+    ///         listen: Some(UnfHandlers::new().on_text(|text| print!("{}", text))),
+    ///         ..Default::default()
+    ///     },
+    ///     |result| match result {
+    ///         Ok(_) => println!("Rom reloaded"),
+    ///         Err(err) => eprintln!("Reload failed: {:?}", err),
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn watch_rom(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        mut options: RunOptions,
+        mut on_reload: impl FnMut(std::io::Result<()>),
+    ) -> std::io::Result<()> {
+        // Canonicalize through the directory, the rom itself may be replaced while watching
+        let path = canonical_in_dir(&std::path::absolute(path.as_ref())?)?;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(std::io::Error::other)?;
+
+        // Watch the directory, since build tools and editors often replace the file instead of writing it
+        let watch_dir = path.parent().unwrap_or(&path);
+        watcher
+            .watch(watch_dir, notify::RecursiveMode::NonRecursive)
+            .map_err(std::io::Error::other)?;
+
+        self.load_and_start(&path, &options)?;
+        self.watch_startup(&mut options)?;
+
+        let mut changed_at: Option<Stopwatch> = None;
+
+        while !options.stop.is_cancelled() {
+            while let Ok(event) = rx.try_recv() {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        on_reload(Err(std::io::Error::other(err)));
+                        continue;
+                    }
+                };
+
+                // Event paths may name the directory through a different link, such as /private/var on macOS
+                let changed = event.paths.iter().any(|changed| {
+                    *changed == path
+                        || canonical_in_dir(changed).is_ok_and(|changed| changed == path)
+                });
+
+                if changed && !event.kind.is_access() {
+                    changed_at = Some(Stopwatch::start());
+                }
+            }
+
+            // Without a clock the change is reloaded right away
+            if changed_at.is_some_and(|at| at.elapsed().is_none_or(|t| t >= WATCH_DEBOUNCE)) {
+                changed_at = None;
+                let result = self
+                    .session_reenter_menu("rom reload")
+                    .and_then(|_| self.load_and_start(&path, &options))
+                    .map(|_| ());
+                let started = result.is_ok();
+
+                on_reload(result);
+
+                if started {
+                    self.watch_startup(&mut options)?;
+                }
+            }
+
+            match self.unf_try_rx() {
+                Ok(Some(packet)) => {
+                    if let Some(handlers) = options.listen.as_mut() {
+                        handlers.dispatch(&packet);
+                    }
+                }
+                Ok(None) => crate::clock::sleep(WATCH_POLL_INTERVAL),
+                Err(err) if is_skippable(&err) => {
+                    trace_event!(warn, %err, "invalid UNF packet skipped");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receives the first packet of a freshly started program, so text it prints before its UNF
+    /// code runs doesn't desync the stream
+    fn watch_startup(&mut self, options: &mut RunOptions) -> std::io::Result<()> {
+        let startup = match self.unf_rx_startup(WATCH_STARTUP_LIMIT) {
+            Ok(startup) => startup,
+            Err(err) if is_skippable(&err) => {
+                trace_event!(warn, %err, "no UNF packet after starting the rom");
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+        if let Some(handlers) = options.listen.as_mut() {
+            if !startup.junk.is_empty() && startup.is_text() {
+                handlers.dispatch(&UnfRecvPacket::new(UnfDataType::DataTypeText, startup.junk));
+            }
+
+            handlers.dispatch(&startup.packet);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::Everdrive;
+use std::sync::mpsc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatcherEvent {
+    /// A new Everdrive device appeared on the given port
+    Connected(String),
+    /// A previously seen Everdrive device is no longer present
+    Disconnected(String),
+}
+
+/// Polls for Everdrive devices in a background thread and emits
+/// `WatcherEvent`s when devices are plugged in or removed.
+///
+/// The background thread is stopped when the watcher is dropped.
+#[derive(Debug)]
+pub struct EverdriveWatcher {
+    events: mpsc::Receiver<WatcherEvent>,
+    /// Dropped to stop the thread, which waits on it between polls
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl EverdriveWatcher {
+    /// Starts watching for Everdrive devices, checking the available USB ports every `poll_interval`.
+    ///
+    /// Devices which are already connected when the watcher starts are reported as `Connected` events.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libeverdrive::{Everdrive, EverdriveWatcher, WatcherEvent};
+    ///
+    /// let watcher = EverdriveWatcher::new(std::time::Duration::from_millis(500));
+    ///
+    /// while let Ok(event) = watcher.recv() {
+    ///     if let WatcherEvent::Connected(port_name) = event {
+    ///         let mut ed = Everdrive::new(&port_name).unwrap();
+    ///         ed.ed_status().unwrap();
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    pub fn new(poll_interval: std::time::Duration) -> Self {
+        let (tx, events) = mpsc::channel();
+        let (stop, thread_stop) = mpsc::channel::<()>();
+
+        let thread = std::thread::spawn(move || {
+            let mut known: Vec<String> = Vec::new();
+
+            loop {
+                // Enumeration can fail transiently while devices are being (re)attached,
+                // skip this round and try again on the next poll
+                if let Ok(ports) = Everdrive::find_usb_devices() {
+                    for port in known.iter().filter(|p| !ports.contains(p)) {
+                        if tx.send(WatcherEvent::Disconnected(port.clone())).is_err() {
+                            return;
+                        }
+                    }
+
+                    for port in ports.iter().filter(|p| !known.contains(p)) {
+                        if tx.send(WatcherEvent::Connected(port.clone())).is_err() {
+                            return;
+                        }
+                    }
+
+                    known = ports;
+                }
+
+                if thread_stop.recv_timeout(poll_interval) != Err(mpsc::RecvTimeoutError::Timeout) {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            events,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Blocks until the next device event is received
+    pub fn recv(&self) -> Result<WatcherEvent, mpsc::RecvError> {
+        self.events.recv()
+    }
+
+    /// Returns the next device event if one is pending, without blocking
+    pub fn try_recv(&self) -> Result<WatcherEvent, mpsc::TryRecvError> {
+        self.events.try_recv()
+    }
+
+    /// Blocks until the next device event is received or `timeout` elapses
+    pub fn recv_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<WatcherEvent, mpsc::RecvTimeoutError> {
+        self.events.recv_timeout(timeout)
+    }
+}
+
+impl Drop for EverdriveWatcher {
+    fn drop(&mut self) {
+        // Disconnects the channel the thread waits on, so it stops without finishing the interval
+        self.stop.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}